@@ -0,0 +1,71 @@
+//! Machine-readable diagnostics, for editors and other tools.
+//!
+//! Each diagnostic is rendered as a single JSON object on its own line.
+
+use ccherry_span::FileId;
+use codespan_reporting::diagnostic::{Diagnostic, LabelStyle, Severity};
+use serde_json::{json, Value};
+
+use crate::line_index::ColumnEncoding;
+use crate::source_map::SourceMap;
+
+/// Returns the URL of the documentation for the diagnostic code `code`, under
+/// the base URL `base`.
+pub fn documentation_url(base: &str, code: &str) -> String {
+    format!("{}/{}", base.trim_end_matches('/'), code)
+}
+
+/// Renders `diagnostic` as JSON.  Lines and columns are one-based, and columns
+/// are counted with `encoding`.
+pub(crate) fn to_json(
+    source_map: &SourceMap,
+    diagnostic: &Diagnostic<FileId>,
+    encoding: ColumnEncoding,
+    documentation_base: Option<&str>,
+) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    };
+
+    let labels: Vec<Value> = diagnostic
+        .labels
+        .iter()
+        .map(|label| {
+            let file = source_map.get(label.file_id).ok();
+            let position = |byte_index| {
+                file.map(|file| {
+                    let position = file.line_col(byte_index, encoding);
+                    json!({ "line": position.line + 1, "column": position.column + 1 })
+                })
+            };
+
+            json!({
+                "style": match label.style {
+                    LabelStyle::Primary => "primary",
+                    LabelStyle::Secondary => "secondary",
+                },
+                "file": file.map(|file| file.name.as_str()),
+                "byte_start": label.range.start,
+                "byte_end": label.range.end,
+                "start": position(label.range.start),
+                "end": position(label.range.end),
+                "message": label.message,
+            })
+        })
+        .collect();
+
+    json!({
+        "severity": severity,
+        "code": diagnostic.code,
+        "message": diagnostic.message,
+        "documentation_url": documentation_base
+            .zip(diagnostic.code.as_deref())
+            .map(|(base, code)| documentation_url(base, code)),
+        "labels": labels,
+        "notes": diagnostic.notes,
+    })
+}
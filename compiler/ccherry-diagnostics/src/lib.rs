@@ -1,10 +1,34 @@
 //! Diagnostics for the Cherry compiler.
 
+use std::collections::HashMap;
+
+mod json;
+mod line_index;
+mod pager;
+mod source_map;
+
+pub use ccherry_span::{FileId, Span};
 pub use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle, Severity};
 pub use codespan_reporting::term::{Chars, DisplayStyle, Styles as Colors, termcolor::{Color, ColorChoice, ColorSpec}};
 
-use codespan_reporting::term::{Config, termcolor};
-use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term::{Config, termcolor::{self, WriteColor}};
+
+pub use json::documentation_url;
+pub use line_index::{ColumnEncoding, LineCol, LineIndex, LINE_INDEX_CACHE_VERSION};
+pub use pager::page;
+pub use source_map::{Expansion, Generated, OriginMapping, SourceFile, SourceMap};
+
+use source_map::WithColumns;
+
+/// The format diagnostics are emitted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// Human-readable diagnostics, rendered with the theme's display style.
+    Human,
+
+    /// One JSON object per diagnostic, for editors and other tools.
+    Json,
+}
 
 /// The "theme" to use for diagnostics.
 #[derive(Clone, Debug)]
@@ -18,6 +42,11 @@ pub struct DiagnosticTheme {
     /// The display style for the diagnostic to use.
     pub display_style: DisplayStyle,
 
+    /// Display styles which replace `display_style` for diagnostics of a
+    /// certain severity, such as showing notes in the short style while errors
+    /// stay rich.
+    pub style_overrides: HashMap<Severity, DisplayStyle>,
+
     /// The colors to use for the diagnostic theme.
     pub colors: Colors,
 
@@ -29,6 +58,13 @@ pub struct DiagnosticTheme {
 
     /// The minimum number of lines to be shown before the line on which a multiline Label ends.
     pub end_context_lines: usize,
+
+    /// The format to emit diagnostics in.
+    pub format: DiagnosticFormat,
+
+    /// The base URL of the error code documentation.  If set, JSON diagnostics
+    /// with a code link to `<url>/<code>`.
+    pub documentation_url: Option<String>,
 }
 
 impl DiagnosticTheme {
@@ -40,10 +76,13 @@ impl DiagnosticTheme {
             color_choice: ColorChoice::Auto,
             chars: Chars::ascii(),
             display_style: DisplayStyle::Rich,
+            style_overrides: HashMap::new(),
             colors: Colors::default(),
             tab_width: 4,
             start_context_lines: 2,
             end_context_lines: 1,
+            format: DiagnosticFormat::Human,
+            documentation_url: None,
         }
     }
 
@@ -59,6 +98,34 @@ impl DiagnosticTheme {
         self
     }
 
+    /// Returns this diagnostic theme after using the provided display style for
+    /// diagnostics with the provided severity, instead of `display_style`.
+    pub fn with_style_override(mut self, severity: Severity, display_style: DisplayStyle) -> Self {
+        self.style_overrides.insert(severity, display_style);
+        self
+    }
+
+    /// Returns the display style used for diagnostics with the provided
+    /// severity.
+    pub fn display_style_for(&self, severity: Severity) -> DisplayStyle {
+        self.style_overrides.get(&severity)
+            .unwrap_or(&self.display_style)
+            .clone()
+    }
+
+    /// Returns this diagnostic theme after using the provided format.
+    pub fn with_format(mut self, format: DiagnosticFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Returns this diagnostic theme after using the provided base URL for
+    /// error code documentation.
+    pub fn with_documentation_url(mut self, documentation_url: String) -> Self {
+        self.documentation_url = Some(documentation_url);
+        self
+    }
+
     /// Returns this diagnostic theme after using the provided colors.
     pub fn with_colors(mut self, colors: Colors) -> Self {
         self.colors = colors;
@@ -121,15 +188,15 @@ impl Default for DiagnosticTheme {
     }
 }
 
-impl Into<Config> for DiagnosticTheme {
-    fn into(self) -> Config {
+impl From<DiagnosticTheme> for Config {
+    fn from(theme: DiagnosticTheme) -> Self {
         Config {
-            chars: self.chars,
-            display_style: self.display_style,
-            styles: self.colors,
-            tab_width: self.tab_width,
-            start_context_lines: self.start_context_lines,
-            end_context_lines: self.end_context_lines,
+            chars: theme.chars,
+            display_style: theme.display_style,
+            styles: theme.colors,
+            tab_width: theme.tab_width,
+            start_context_lines: theme.start_context_lines,
+            end_context_lines: theme.end_context_lines,
         }
     }
 }
@@ -164,10 +231,61 @@ impl DiagnosticEmitter {
 
     /// Emits a diagnostic message to the terminal.
     pub fn emit(&self, diagnostic: &Diagnostic<()>) {
-        let files = SimpleFile::new(self.filename.to_string(), self.source.to_string());
+        let mut stdout = termcolor::BufferedStandardStream::stdout(self.theme.color_choice);
+        self.write(&mut stdout, diagnostic);
+    }
+
+    /// Writes a diagnostic message to `writer`, the same way it would be
+    /// emitted to the terminal.
+    fn write(&self, writer: &mut dyn WriteColor, diagnostic: &Diagnostic<()>) {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file(self.filename.to_string(), self.source.to_string());
+
+        let diagnostic = Diagnostic {
+            severity: diagnostic.severity,
+            code: diagnostic.code.clone(),
+            message: diagnostic.message.clone(),
+            labels: diagnostic.labels.iter()
+                .map(|label| Label {
+                    style: label.style,
+                    file_id: file,
+                    range: label.range.clone(),
+                    message: label.message.clone(),
+                })
+                .collect(),
+            notes: diagnostic.notes.clone(),
+        };
+
+        self.write_in(writer, &source_map, &diagnostic);
+    }
+
+    /// Emits a diagnostic message which may refer to any file in `source_map`
+    /// to the terminal.  Diagnostics in virtual files are also labeled with the
+    /// sites they were expanded from.
+    pub fn emit_in(&self, source_map: &SourceMap, diagnostic: &Diagnostic<FileId>) {
+        let mut stdout = termcolor::BufferedStandardStream::stdout(self.theme.color_choice);
+        self.write_in(&mut stdout, source_map, diagnostic);
+    }
+
+    /// Writes a diagnostic message which may refer to any file in
+    /// `source_map` to `writer`.
+    fn write_in(&self, writer: &mut dyn WriteColor, source_map: &SourceMap, diagnostic: &Diagnostic<FileId>) {
+        let diagnostic = source_map.with_expansion_labels(diagnostic.clone());
+        let encoding = ColumnEncoding::Display { tab_width: self.theme.tab_width };
+
+        if self.theme.format == DiagnosticFormat::Json {
+            let documentation_url = self.theme.documentation_url.as_deref();
+            writeln!(writer, "{}", json::to_json(source_map, &diagnostic, encoding, documentation_url)).unwrap();
+            return;
+        }
+
+        let mut config: Config = self.theme.clone().into();
+        config.display_style = self.theme.display_style_for(diagnostic.severity);
+
+        let files = WithColumns { source_map, encoding };
         codespan_reporting::term::emit(
-            &mut termcolor::BufferedStandardStream::stdout(self.theme.color_choice),
-            &self.theme.clone().into(),
+            writer,
+            &config,
             &files,
             &diagnostic).unwrap();
     }
@@ -178,4 +296,33 @@ impl DiagnosticEmitter {
             self.emit(diagnostic);
         }
     }
+
+    /// Renders all diagnostics in a [`Vec`] the way they would be emitted to
+    /// the terminal, and returns the output, such as for [`page`].  Colors are
+    /// written as ANSI escapes, unless the theme never uses colors.
+    pub fn render_all(&self, diagnostics: &Vec<Diagnostic<()>>) -> Vec<u8> {
+        let mut buffer = if self.theme.color_choice == ColorChoice::Never {
+            termcolor::Buffer::no_color()
+        } else {
+            termcolor::Buffer::ansi()
+        };
+
+        for diagnostic in diagnostics {
+            self.write(&mut buffer, diagnostic);
+        }
+
+        buffer.into_inner()
+    }
+}
+
+/// Returns a primary label at `span`, for a diagnostic which may refer to any
+/// file in a [`SourceMap`].
+pub fn primary_label(span: Span) -> Label<FileId> {
+    Label::primary(span.file, span.loc())
+}
+
+/// Returns a secondary label at `span`, for a diagnostic which may refer to
+/// any file in a [`SourceMap`].
+pub fn secondary_label(span: Span) -> Label<FileId> {
+    Label::secondary(span.file, span.loc())
 }
@@ -0,0 +1,219 @@
+//! Conversion between byte indices and line/column positions.
+//!
+//! Every place which shows or accepts a column, such as diagnostics and the
+//! language server, should go through a [`LineIndex`], so tabs and non-ASCII
+//! characters are counted the same way everywhere.
+
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::Diagnostic;
+use codespan_reporting::files::{line_starts, Error};
+
+/// The magic bytes at the start of every line index cache entry.
+const MAGIC: &[u8; 4] = b"CHLI";
+
+/// The version of the line index cache format, written by
+/// [`LineIndex::to_bytes`].  This must be bumped whenever the format changes.
+pub const LINE_INDEX_CACHE_VERSION: u32 = 1;
+
+/// How columns are counted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnEncoding {
+    /// Columns are counted in characters, and a tab moves to the next multiple
+    /// of `tab_width`, the way diagnostics render it.
+    Display {
+        /// The width of a tab, usually the `tab_width` of the diagnostic theme.
+        tab_width: usize,
+    },
+
+    /// Columns are counted in UTF-16 code units, which is what the Language
+    /// Server Protocol uses by default.  A tab counts as one unit.
+    Utf16,
+}
+
+impl ColumnEncoding {
+    /// Returns the column after `char`, if `char` starts at `column`.
+    fn advance(self, column: usize, char: char) -> usize {
+        match self {
+            ColumnEncoding::Display { tab_width } if char == '\t' && tab_width > 0 => {
+                column + tab_width - column % tab_width
+            }
+            ColumnEncoding::Display { .. } => column + 1,
+            ColumnEncoding::Utf16 => column + char.len_utf16(),
+        }
+    }
+}
+
+/// A zero-based line and column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    /// The zero-based line number.
+    pub line: usize,
+
+    /// The zero-based column, counted with a [`ColumnEncoding`].
+    pub column: usize,
+}
+
+/// The start of every line in a source string.
+#[derive(Clone, Debug)]
+pub struct LineIndex {
+    /// The byte index of the start of each line.
+    line_starts: Vec<usize>,
+
+    /// The length of the source string, in bytes.
+    len: usize,
+}
+
+impl LineIndex {
+    /// Creates a line index for `source`.
+    pub fn new(source: &str) -> Self {
+        Self {
+            line_starts: line_starts(source).collect(),
+            len: source.len(),
+        }
+    }
+
+    /// Encodes this line index for the on-disk build cache.
+    ///
+    /// The entry starts with the magic bytes `CHLI` and the format version as a
+    /// little-endian `u32`, followed by the number of lines, the length of the
+    /// source and the start of each line, all as little-endian `u64`s.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(24 + self.line_starts.len() * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&LINE_INDEX_CACHE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.line_starts.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.len as u64).to_le_bytes());
+
+        for &line_start in &self.line_starts {
+            bytes.extend_from_slice(&(line_start as u64).to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Decodes a line index encoded by [`LineIndex::to_bytes`], which may be
+    /// memory-mapped straight from the build cache.
+    ///
+    /// Returns an error if `bytes` was created by a different version of the
+    /// format, or if it isn't a valid line index.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Diagnostic<()>> {
+        let corrupt =
+            || Diagnostic::error().with_message("line index cache entry is truncated or corrupt");
+
+        if bytes.len() < 24 || &bytes[..4] != MAGIC {
+            return Err(corrupt());
+        }
+
+        let u64_at = |idx: usize| {
+            let value = u64::from_le_bytes(bytes[idx..idx + 8].try_into().unwrap());
+            usize::try_from(value).map_err(|_| corrupt())
+        };
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != LINE_INDEX_CACHE_VERSION {
+            return Err(Diagnostic::error().with_message(format!(
+                "line index cache entry has version {}, expected version {}",
+                version, LINE_INDEX_CACHE_VERSION
+            )));
+        }
+
+        let line_count = u64_at(8)?;
+        let len = u64_at(16)?;
+
+        if line_count
+            .checked_mul(8)
+            .and_then(|size| size.checked_add(24))
+            != Some(bytes.len())
+        {
+            return Err(corrupt());
+        }
+
+        let line_starts = (0..line_count)
+            .map(|line| u64_at(24 + line * 8))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the first line always starts at 0, and every line starts after the
+        // one before it, inside of the source.
+        let valid = line_starts.first() == Some(&0)
+            && line_starts.windows(2).all(|pair| pair[0] < pair[1])
+            && line_starts.last().is_some_and(|&last| last <= len);
+
+        if !valid {
+            return Err(corrupt());
+        }
+
+        Ok(Self { line_starts, len })
+    }
+
+    /// Returns the number of lines.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the index of the line containing `byte_index`.
+    pub fn line_index(&self, byte_index: usize) -> usize {
+        match self.line_starts.binary_search(&byte_index) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        }
+    }
+
+    /// Returns the byte index of the start of the provided line.  The line
+    /// after the last line starts at the end of the source.
+    pub fn line_start(&self, line_index: usize) -> Result<usize, Error> {
+        use std::cmp::Ordering;
+
+        match line_index.cmp(&self.line_starts.len()) {
+            Ordering::Less => Ok(self.line_starts[line_index]),
+            Ordering::Equal => Ok(self.len),
+            Ordering::Greater => Err(Error::LineTooLarge {
+                given: line_index,
+                max: self.line_starts.len() - 1,
+            }),
+        }
+    }
+
+    /// Returns the byte range of the provided line, including its line break.
+    pub fn line_range(&self, line_index: usize) -> Result<Range<usize>, Error> {
+        Ok(self.line_start(line_index)?..self.line_start(line_index + 1)?)
+    }
+
+    /// Returns the line and column of `byte_index` in `source`, which must be
+    /// the string this index was created for.
+    pub fn line_col(&self, source: &str, byte_index: usize, encoding: ColumnEncoding) -> LineCol {
+        let line = self.line_index(byte_index);
+        let column = source[self.line_starts[line]..byte_index]
+            .chars()
+            .fold(0, |column, char| encoding.advance(column, char));
+
+        LineCol { line, column }
+    }
+
+    /// Returns the byte index of `position` in `source`, which must be the
+    /// string this index was created for.  A column past the end of the line,
+    /// or inside of a tab or a surrogate pair, is moved back to the closest
+    /// character boundary before it.
+    pub fn offset(
+        &self,
+        source: &str,
+        position: LineCol,
+        encoding: ColumnEncoding,
+    ) -> Option<usize> {
+        let range = self.line_range(position.line).ok()?;
+        let line = &source[range.clone()];
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let mut column = 0;
+        for (idx, char) in line.char_indices() {
+            column = encoding.advance(column, char);
+
+            if column > position.column {
+                return Some(range.start + idx);
+            }
+        }
+
+        Some(range.start + line.len())
+    }
+}
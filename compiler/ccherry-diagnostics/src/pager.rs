@@ -0,0 +1,57 @@
+//! Showing rendered diagnostics in a pager, so hundreds of errors don't
+//! scroll past the user.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Shows `output` in the user's pager, `$PAGER`, or `less` if it isn't set.
+/// Colors are kept, and `less` quits immediately if the output fits on one
+/// screen, unless `$LESS` says otherwise.
+///
+/// If standard output isn't a terminal, or the pager can't be started,
+/// `output` is written to standard output instead.
+pub fn page(output: &[u8]) -> io::Result<()> {
+    if output.is_empty() {
+        return Ok(());
+    }
+
+    if io::stdout().is_terminal() {
+        if let Some(mut pager) = pager_command() {
+            if let Ok(mut child) = pager.stdin(Stdio::piped()).spawn() {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // the user may quit the pager before reading everything,
+                    // which closes the pipe.  That isn't an error.
+                    match stdin.write_all(output) {
+                        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err),
+                        _ => {}
+                    }
+                }
+
+                child.wait()?;
+                return Ok(());
+            }
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(output)?;
+    stdout.flush()
+}
+
+/// Returns the command to run the pager with, or `None` if paging is turned
+/// off with an empty `$PAGER`.
+fn pager_command() -> Option<Command> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut args = pager.split_whitespace();
+    let mut command = Command::new(args.next()?);
+    command.args(args);
+
+    if env::var_os("LESS").is_none() {
+        // -F quits if the output fits on one screen, -R keeps colors and -X
+        // leaves the output on the screen after quitting.
+        command.env("LESS", "FRX");
+    }
+
+    Some(command)
+}
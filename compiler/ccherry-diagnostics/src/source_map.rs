@@ -0,0 +1,331 @@
+//! The source map, which keeps track of every source file in a compilation.
+//!
+//! Besides files on disk, the source map can hold *virtual* files, which are
+//! created by the compiler itself: REPL input, macro expansion buffers and
+//! generated code.  A virtual file may link back to the place it was expanded
+//! from, or to the input its code was generated from, so diagnostics in it can
+//! also point at the original source.
+
+use std::ops::Range;
+
+use ccherry_span::{FileId, Span};
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::{Error, Files};
+
+use crate::line_index::{ColumnEncoding, LineCol, LineIndex};
+
+/// Where a virtual file was expanded from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expansion {
+    /// The file containing the expansion site.
+    pub file: FileId,
+
+    /// The location of the expansion site in `file`.
+    pub loc: Range<usize>,
+
+    /// A description of the expansion, such as "in this expansion of
+    /// `println!`".
+    pub description: String,
+}
+
+impl Expansion {
+    /// Returns the span of the expansion site.
+    pub fn span(&self) -> Span {
+        Span::at(self.file, self.loc.clone())
+    }
+}
+
+/// Where the code in a generated file came from, such as the output of a
+/// build script or a macro.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generated {
+    /// A description of the generator, such as "`build.ch`".
+    pub generator: String,
+
+    /// The parts of the generated file which came from a specific location in
+    /// another file.
+    pub mappings: Vec<OriginMapping>,
+}
+
+/// A part of a generated file which was produced from a location in another
+/// file, such as a function generated from an entry in a schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OriginMapping {
+    /// The location in the generated file.
+    pub loc: Range<usize>,
+
+    /// The file the code was generated from.
+    pub origin_file: FileId,
+
+    /// The location in `origin_file` the code was generated from.
+    pub origin_loc: Range<usize>,
+}
+
+impl OriginMapping {
+    /// Returns the span in the file the code was generated from.
+    pub fn origin_span(&self) -> Span {
+        Span::at(self.origin_file, self.origin_loc.clone())
+    }
+}
+
+/// A single file in a [`SourceMap`].
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    /// The name of this file.  For virtual files, this is a synthetic name such
+    /// as `<repl:3>`.
+    pub name: String,
+
+    /// The contents of this file.
+    pub source: String,
+
+    /// Whether or not this file is virtual, rather than a file on disk.
+    pub is_virtual: bool,
+
+    /// Where this file was expanded from, if it is a virtual file created by
+    /// an expansion.
+    pub expansion: Option<Expansion>,
+
+    /// What generated this file, if it is a virtual file containing generated
+    /// code.
+    pub generated: Option<Generated>,
+
+    /// The start of every line in this file.
+    line_index: LineIndex,
+}
+
+impl SourceFile {
+    /// Returns the start of every line in this file.
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    /// Returns the line and column of `byte_index` in this file.
+    pub fn line_col(&self, byte_index: usize, encoding: ColumnEncoding) -> LineCol {
+        self.line_index.line_col(&self.source, byte_index, encoding)
+    }
+
+    /// Returns the byte index of `position` in this file.
+    pub fn offset(&self, position: LineCol, encoding: ColumnEncoding) -> Option<usize> {
+        self.line_index.offset(&self.source, position, encoding)
+    }
+}
+
+/// A collection of source files, both real and virtual.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// The files in this source map, indexed by [`FileId`].
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Creates a new, empty source map.
+    pub fn new() -> Self {
+        Self { files: vec![] }
+    }
+
+    /// Adds a file and returns its identifier.
+    fn add(
+        &mut self,
+        name: String,
+        source: String,
+        is_virtual: bool,
+        expansion: Option<Expansion>,
+        generated: Option<Generated>,
+    ) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile {
+            line_index: LineIndex::new(&source),
+            name,
+            source,
+            is_virtual,
+            expansion,
+            generated,
+        });
+        id
+    }
+
+    /// Adds a file from disk to this source map.
+    pub fn add_file(&mut self, name: String, source: String) -> FileId {
+        self.add(name, source, false, None, None)
+    }
+
+    /// Adds a virtual file to this source map.  `name` should be a synthetic
+    /// name which can't be confused with a path, such as `<repl:3>`.
+    pub fn add_virtual(
+        &mut self,
+        name: String,
+        source: String,
+        expansion: Option<Expansion>,
+    ) -> FileId {
+        self.add(name, source, true, expansion, None)
+    }
+
+    /// Adds a virtual file containing generated code to this source map.
+    /// Diagnostics in it are also labeled with the origin of the code they
+    /// point at, if it has a mapping, and note what generated the file.
+    pub fn add_generated(&mut self, name: String, source: String, generated: Generated) -> FileId {
+        self.add(name, source, true, None, Some(generated))
+    }
+
+    /// Returns the file with the provided identifier.
+    pub fn get(&self, id: FileId) -> Result<&SourceFile, Error> {
+        self.files.get(id.0).ok_or(Error::FileMissing)
+    }
+
+    /// Returns the source code `span` covers, or `None` if its file isn't in
+    /// this source map, or it isn't on character boundaries of the file.
+    pub fn text(&self, span: Span) -> Option<&str> {
+        self.get(span.file).ok()?.source.get(span.loc())
+    }
+
+    /// Returns the chain of expansions leading to `id`, starting with the
+    /// innermost one.
+    pub fn expansion_chain(&self, id: FileId) -> Vec<&Expansion> {
+        let mut chain = vec![];
+        let mut current = id;
+
+        while let Some(expansion) = self
+            .get(current)
+            .ok()
+            .and_then(|file| file.expansion.as_ref())
+        {
+            // a file can't be expanded from itself, but guard against cycles.
+            if chain.len() > self.files.len() {
+                break;
+            }
+
+            chain.push(expansion);
+            current = expansion.file;
+        }
+
+        chain
+    }
+
+    /// Returns the location that `loc` in the generated file `id` was generated
+    /// from, using the smallest mapping which contains it.
+    pub fn origin(&self, id: FileId, loc: Range<usize>) -> Option<&OriginMapping> {
+        self.get(id)
+            .ok()?
+            .generated
+            .as_ref()?
+            .mappings
+            .iter()
+            .filter(|mapping| mapping.loc.start <= loc.start && loc.end <= mapping.loc.end)
+            .min_by_key(|mapping| mapping.loc.len())
+    }
+
+    /// Adds a secondary label for every expansion site leading to the files of
+    /// the primary labels of `diagnostic`, so a diagnostic in expanded code
+    /// also points at the code that was written by the user.  Primary labels in
+    /// generated files get a label at their origin, and a note saying what
+    /// generated the file.
+    pub fn with_expansion_labels(&self, mut diagnostic: Diagnostic<FileId>) -> Diagnostic<FileId> {
+        let mut files = vec![];
+        let mut origins = vec![];
+        for label in &diagnostic.labels {
+            if label.style != codespan_reporting::diagnostic::LabelStyle::Primary {
+                continue;
+            }
+
+            if !files.contains(&label.file_id) {
+                files.push(label.file_id);
+            }
+
+            if let Some(origin) = self.origin(label.file_id, label.range.clone()) {
+                origins.push(
+                    Label::secondary(origin.origin_file, origin.origin_loc.clone())
+                        .with_message("the code was generated from here"),
+                );
+            }
+        }
+
+        diagnostic.labels.append(&mut origins);
+
+        for file in files {
+            for expansion in self.expansion_chain(file) {
+                diagnostic.labels.push(
+                    Label::secondary(expansion.file, expansion.loc.clone())
+                        .with_message(expansion.description.clone()),
+                );
+            }
+
+            if let Ok(SourceFile {
+                name,
+                generated: Some(generated),
+                ..
+            }) = self.get(file)
+            {
+                diagnostic.notes.push(format!(
+                    "`{}` was generated by {}, so it shouldn't be edited directly",
+                    name, generated.generator
+                ));
+            }
+        }
+
+        diagnostic
+    }
+}
+
+impl<'a> Files<'a> for SourceMap {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<&'a str, Error> {
+        Ok(&self.get(id)?.name)
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, Error> {
+        Ok(&self.get(id)?.source)
+    }
+
+    fn line_index(&'a self, id: FileId, byte_index: usize) -> Result<usize, Error> {
+        Ok(self.get(id)?.line_index.line_index(byte_index))
+    }
+
+    fn line_range(&'a self, id: FileId, line_index: usize) -> Result<Range<usize>, Error> {
+        self.get(id)?.line_index.line_range(line_index)
+    }
+}
+
+/// A [`SourceMap`] which reports columns with a [`ColumnEncoding`], rather
+/// than counting characters, so the columns in emitted diagnostics agree
+/// with the way their source lines are rendered.
+pub(crate) struct WithColumns<'a> {
+    /// The source map to read files from.
+    pub source_map: &'a SourceMap,
+
+    /// How columns are counted.
+    pub encoding: ColumnEncoding,
+}
+
+impl<'a> Files<'a> for WithColumns<'a> {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<&'a str, Error> {
+        self.source_map.name(id)
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, Error> {
+        self.source_map.source(id)
+    }
+
+    fn line_index(&'a self, id: FileId, byte_index: usize) -> Result<usize, Error> {
+        self.source_map.line_index(id, byte_index)
+    }
+
+    fn line_range(&'a self, id: FileId, line_index: usize) -> Result<Range<usize>, Error> {
+        self.source_map.line_range(id, line_index)
+    }
+
+    fn column_number(&'a self, id: FileId, _: usize, byte_index: usize) -> Result<usize, Error> {
+        Ok(self
+            .source_map
+            .get(id)?
+            .line_col(byte_index, self.encoding)
+            .column
+            + 1)
+    }
+}
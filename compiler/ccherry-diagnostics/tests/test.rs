@@ -0,0 +1,219 @@
+extern crate ccherry_diagnostics;
+
+use ccherry_diagnostics::{
+    documentation_url, primary_label, secondary_label, ColorChoice, ColumnEncoding, Diagnostic,
+    DiagnosticEmitter, DiagnosticTheme, DisplayStyle, Expansion, Generated, Label, LabelStyle,
+    LineCol, LineIndex, OriginMapping, Severity, SourceMap, Span,
+};
+
+#[test]
+fn expansion_labels() {
+    let mut source_map = SourceMap::new();
+    let main = source_map.add_file("main.ch".into(), "greet!(\"world\");\n".into());
+    let expanded = source_map.add_virtual(
+        "<expansion of greet!>".into(),
+        "println(\"hello, \" + \"world\");".into(),
+        Some(Expansion {
+            file: main,
+            loc: 0..16,
+            description: "in this expansion of `greet!`".into(),
+        }),
+    );
+
+    assert!(source_map.get(expanded).unwrap().is_virtual);
+    assert_eq!(source_map.expansion_chain(expanded).len(), 1);
+
+    let diagnostic = source_map.with_expansion_labels(
+        Diagnostic::error().with_labels(vec![Label::primary(expanded, 8..17)]),
+    );
+
+    assert_eq!(diagnostic.labels.len(), 2);
+    assert_eq!(diagnostic.labels[1].file_id, main);
+    assert_eq!(diagnostic.labels[1].range, 0..16);
+}
+
+#[test]
+fn spans() {
+    let mut source_map = SourceMap::new();
+    let main = source_map.add_file("main.ch".into(), "import \"util\"\n".into());
+    let util = source_map.add_file("util.ch".into(), "export let x = 1\n".into());
+
+    assert_eq!(source_map.text(Span::at(main, 7..13)), Some("\"util\""));
+    assert_eq!(source_map.text(Span::at(util, 11..12)), Some("x"));
+    assert_eq!(source_map.text(Span::at(util, 11..99)), None);
+
+    // a diagnostic can point at more than one file.
+    let diagnostic = Diagnostic::error().with_labels(vec![
+        primary_label(Span::at(main, 7..13)),
+        secondary_label(Span::at(util, 11..12)),
+    ]);
+    assert_eq!(diagnostic.labels[0].file_id, main);
+    assert_eq!(diagnostic.labels[1].file_id, util);
+    assert_eq!(diagnostic.labels[1].range, 11..12);
+    assert_eq!(diagnostic.labels[1].style, LabelStyle::Secondary);
+
+    let expansion = Expansion {
+        file: main,
+        loc: 0..6,
+        description: String::new(),
+    };
+    assert_eq!(expansion.span(), Span::at(main, 0..6));
+}
+
+#[test]
+fn generated_origins() {
+    let mut source_map = SourceMap::new();
+    let schema = source_map.add_file(
+        "schema/messages.json".into(),
+        "{ \"ping\": {}, \"pong\": {} }".into(),
+    );
+    let generated = source_map.add_generated(
+        "target/generated/messages.ch".into(),
+        "export struct Ping {}\nexport struct Pong {}\n".into(),
+        Generated {
+            generator: "`build.ch`".into(),
+            mappings: vec![
+                OriginMapping {
+                    loc: 0..44,
+                    origin_file: schema,
+                    origin_loc: 0..28,
+                },
+                OriginMapping {
+                    loc: 22..43,
+                    origin_file: schema,
+                    origin_loc: 14..20,
+                },
+            ],
+        },
+    );
+
+    assert!(source_map.get(generated).unwrap().is_virtual);
+    assert_eq!(
+        source_map.origin(generated, 36..40).unwrap().origin_loc,
+        14..20
+    );
+    assert_eq!(
+        source_map.origin(generated, 7..13).unwrap().origin_loc,
+        0..28
+    );
+    assert!(source_map.origin(schema, 0..1).is_none());
+
+    let diagnostic = source_map.with_expansion_labels(
+        Diagnostic::error().with_labels(vec![Label::primary(generated, 36..40)]),
+    );
+
+    assert_eq!(diagnostic.labels.len(), 2);
+    assert_eq!(diagnostic.labels[1].file_id, schema);
+    assert_eq!(diagnostic.labels[1].range, 14..20);
+    assert_eq!(
+        diagnostic.notes,
+        ["`target/generated/messages.ch` was generated by `build.ch`, so it shouldn't be edited directly"]
+    );
+}
+
+#[test]
+fn columns() {
+    let source = "a\n\tb 😀 c\n";
+    let index = LineIndex::new(source);
+    let display = ColumnEncoding::Display { tab_width: 4 };
+    let c = source.find('c').unwrap();
+
+    assert_eq!(
+        index.line_col(source, 3, display),
+        LineCol { line: 1, column: 4 }
+    );
+    assert_eq!(
+        index.line_col(source, c, display),
+        LineCol { line: 1, column: 8 }
+    );
+    assert_eq!(
+        index.line_col(source, c, ColumnEncoding::Utf16),
+        LineCol { line: 1, column: 6 }
+    );
+
+    assert_eq!(
+        index.offset(
+            source,
+            LineCol { line: 1, column: 6 },
+            ColumnEncoding::Utf16
+        ),
+        Some(c)
+    );
+    assert_eq!(
+        index.offset(source, LineCol { line: 1, column: 8 }, display),
+        Some(c)
+    );
+    assert_eq!(
+        index.offset(
+            source,
+            LineCol {
+                line: 1,
+                column: 99
+            },
+            display
+        ),
+        Some(c + 1)
+    );
+}
+
+#[test]
+fn documentation_urls() {
+    assert_eq!(
+        documentation_url("https://example.com/errors", "E0001"),
+        "https://example.com/errors/E0001"
+    );
+    assert_eq!(
+        documentation_url("https://example.com/errors/", "E0001"),
+        "https://example.com/errors/E0001"
+    );
+}
+
+#[test]
+fn line_index_cache() {
+    let source = "a\nb\r\n\nc";
+    let index = LineIndex::new(source);
+    let bytes = index.to_bytes();
+    let decoded = LineIndex::from_bytes(&bytes).unwrap();
+
+    assert_eq!(decoded.line_count(), 4);
+    assert_eq!(decoded.line_range(1).unwrap(), index.line_range(1).unwrap());
+
+    assert!(LineIndex::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+    let mut unsorted = bytes.clone();
+    unsorted[32..40].copy_from_slice(&9u64.to_le_bytes());
+    assert!(LineIndex::from_bytes(&unsorted).is_err());
+}
+
+#[test]
+fn style_overrides() {
+    let theme = DiagnosticTheme::new().with_style_override(Severity::Note, DisplayStyle::Short);
+
+    assert!(matches!(
+        theme.display_style_for(Severity::Note),
+        DisplayStyle::Short
+    ));
+    assert!(matches!(
+        theme.display_style_for(Severity::Error),
+        DisplayStyle::Rich
+    ));
+}
+
+#[test]
+fn render_all() {
+    let mut theme = DiagnosticTheme::new().with_display_style(DisplayStyle::Short);
+    theme.color_choice = ColorChoice::Never;
+    let emitter = DiagnosticEmitter::new("main.ch".into(), "let x = ;\n".into()).with_theme(theme);
+
+    let output = emitter.render_all(&vec![
+        Diagnostic::error()
+            .with_message("expected an expression")
+            .with_labels(vec![Label::primary((), 8..9)]),
+        Diagnostic::warning().with_message("unused variable `x`"),
+    ]);
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "main.ch:1:9: error: expected an expression\nwarning: unused variable `x`\n"
+    );
+}
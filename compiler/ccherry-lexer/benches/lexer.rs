@@ -0,0 +1,121 @@
+//! Benchmarks of the lexer's throughput on generated sources, each stressing a
+//! different part of it.  Run them with `cargo bench -p ccherry-lexer`.
+
+use ccherry_lexer::Lexer;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// The rough size of each generated source, in bytes.
+const SIZE: usize = 256 * 1024;
+
+/// Repeats `line`, with `idx` being the number of the line, until the source
+/// is at least [`SIZE`] bytes long.
+fn generate(line: impl Fn(usize) -> String) -> String {
+    let mut source = String::with_capacity(SIZE + 256);
+    let mut idx = 0;
+
+    while source.len() < SIZE {
+        source.push_str(&line(idx));
+        source.push('\n');
+        idx += 1;
+    }
+
+    source
+}
+
+/// Returns the sources to benchmark, along with their names.
+fn corpora() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "idents",
+            generate(|idx| {
+                format!(
+                    "let some_rather_long_identifier_{0} = another_long_identifier_name_{0};",
+                    idx
+                )
+            }),
+        ),
+        (
+            "comments",
+            generate(|idx| {
+                format!(
+                    "/// documentation of item {0}, which goes on for a while\n\
+                     // a line comment\n\
+                     /* a block /* nested */ comment */ item_{0} // trailing",
+                    idx
+                )
+            }),
+        ),
+        (
+            "numbers",
+            generate(|idx| {
+                format!(
+                    "[{0}, 0x{0:X}, 0b{0:b}, 0o{0:o}, {0}.5, {0}e-3, 1_000_{1:03}, 0x1.8p{2}],",
+                    idx,
+                    idx % 1000,
+                    idx % 64
+                )
+            }),
+        ),
+        (
+            "strings",
+            generate(|idx| {
+                format!(
+                    "\"line {0}\\n\\twith \\u{{1F600}} escapes ${{value_{0}}}\" r#\"raw {0}\"#",
+                    idx
+                )
+            }),
+        ),
+        (
+            "nested_groups",
+            generate(|idx| {
+                let depth = 1 + idx % 32;
+                format!("{}f(x_{}){}", "({[".repeat(depth), idx, "]})".repeat(depth))
+            }),
+        ),
+        (
+            "mixed",
+            generate(|idx| {
+                format!(
+                    "// function {0}\nfn function_{0}(a: i32, b: &str) -> Option<i32> {{\n    \
+                     let x = a * {0} + 0x{0:x} - 1.5e2; // compute\n    \
+                     if b == \"value ${{a}}\" {{ return Some(x); }}\n    None\n}}",
+                    idx
+                )
+            }),
+        ),
+    ]
+}
+
+fn lex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+
+    for (name, source) in corpora() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| Lexer::lex_all(black_box(&source)));
+        });
+    }
+
+    group.finish();
+}
+
+fn lex_lossless(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex_lossless");
+
+    for (name, source) in corpora() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                Lexer::new(black_box(&source))
+                    .with_lossless(true)
+                    .with_recovery(true)
+                    .count()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, lex, lex_lossless);
+criterion_main!(benches);
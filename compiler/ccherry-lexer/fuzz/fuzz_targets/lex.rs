@@ -0,0 +1,24 @@
+//! Lexes arbitrary sources, to find inputs which make the lexer panic or
+//! loop forever.  Run it with `cargo +nightly fuzz run lex -- -timeout=5`
+//! from `compiler/ccherry-lexer`, so a source which takes more than 5 seconds
+//! is reported as a hang.
+
+#![no_main]
+
+use ccherry_lexer::{Lexer, ToSource, TokenTree};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let (tokens, _, _) = Lexer::lex_all(source);
+
+    // in lossless mode, the tokens give back exactly the source they were
+    // lexed from, even when it has errors.
+    if !tokens.is_empty() {
+        let lossless: Vec<TokenTree> = Lexer::new(source)
+            .with_lossless(true)
+            .with_recovery(true)
+            .filter_map(Result::ok)
+            .collect();
+        assert_eq!(lossless.to_source(), source);
+    }
+});
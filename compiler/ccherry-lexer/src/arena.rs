@@ -0,0 +1,149 @@
+//! A flat representation of token trees, for large inputs.
+
+use std::mem;
+use std::ops::{Index, Range};
+
+use crate::token::TokenTree;
+
+/// The index of a token in a [`TokenArena`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TokenId(usize);
+
+impl TokenId {
+    /// Returns the index of this token in its arena.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A single token in a [`TokenArena`].
+#[derive(Clone, Debug, PartialEq)]
+struct Node {
+    /// The token, with no tokens if it is a group.
+    token: TokenTree,
+
+    /// The indices of the tokens in this token, if it is a group.
+    children: Range<usize>,
+}
+
+/// Token trees stored in a single list, rather than in a [`Vec`] for every
+/// group, and referred to by [`TokenId`]s.
+///
+/// The tokens are stored breadth first, so the tokens of each group are next
+/// to each other, and the top-level tokens come first.  The groups in the
+/// arena have no [`Group::tokens`](crate::Group::tokens) of their own, and
+/// their tokens are found with [`TokenArena::children`] instead.  The groups
+/// of interpolations stay in their strings, since they are part of the
+/// string's value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenArena {
+    /// Every token in the arena, breadth first.
+    nodes: Vec<Node>,
+
+    /// The number of top-level tokens.
+    roots: usize,
+}
+
+impl TokenArena {
+    /// Creates an arena by moving `tokens` and the tokens of their groups into
+    /// it.
+    pub fn new(tokens: Vec<TokenTree>) -> Self {
+        let roots = tokens.len();
+        let mut nodes: Vec<Node> = tokens
+            .into_iter()
+            .map(|token| Node {
+                token,
+                children: 0..0,
+            })
+            .collect();
+
+        // the children of each group are appended as the group is reached, so
+        // they end up next to each other, after every token before them.
+        let mut idx = 0;
+        while idx < nodes.len() {
+            if let TokenTree::Group(group) = &mut nodes[idx].token {
+                let tokens = mem::take(&mut group.tokens);
+                let start = nodes.len();
+                nodes.extend(tokens.into_iter().map(|token| Node {
+                    token,
+                    children: 0..0,
+                }));
+                nodes[idx].children = start..nodes.len();
+            }
+
+            idx += 1;
+        }
+
+        Self { nodes, roots }
+    }
+
+    /// Returns the number of tokens in this arena, including the tokens in
+    /// groups.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns whether or not this arena has no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the top-level tokens, in order.
+    pub fn roots(&self) -> impl ExactSizeIterator<Item = TokenId> {
+        (0..self.roots).map(TokenId)
+    }
+
+    /// Returns the tokens in the group `id`, in order, or nothing if it isn't
+    /// a group.
+    pub fn children(&self, id: TokenId) -> impl ExactSizeIterator<Item = TokenId> {
+        self.nodes[id.0].children.clone().map(TokenId)
+    }
+
+    /// Returns the token `id`, or `None` if it isn't in this arena.
+    pub fn get(&self, id: TokenId) -> Option<&TokenTree> {
+        self.nodes.get(id.0).map(|node| &node.token)
+    }
+
+    /// Moves the tokens in this arena back into token trees, with the tokens
+    /// of each group in the group.
+    pub fn into_tokens(self) -> Vec<TokenTree> {
+        let (mut tokens, children): (Vec<_>, Vec<_>) = self
+            .nodes
+            .into_iter()
+            .map(|node| (Some(node.token), node.children))
+            .unzip();
+
+        // the children of a group always come after it, so they are complete
+        // by the time the group is reached.
+        for idx in (0..tokens.len()).rev() {
+            if children[idx].is_empty() {
+                continue;
+            }
+
+            let nested = children[idx]
+                .clone()
+                .filter_map(|child| tokens[child].take())
+                .collect();
+
+            if let Some(TokenTree::Group(group)) = &mut tokens[idx] {
+                group.tokens = nested;
+            }
+        }
+
+        tokens.into_iter().take(self.roots).flatten().collect()
+    }
+}
+
+impl Index<TokenId> for TokenArena {
+    type Output = TokenTree;
+
+    fn index(&self, id: TokenId) -> &TokenTree {
+        &self.nodes[id.0].token
+    }
+}
+
+impl From<Vec<TokenTree>> for TokenArena {
+    fn from(tokens: Vec<TokenTree>) -> Self {
+        Self::new(tokens)
+    }
+}
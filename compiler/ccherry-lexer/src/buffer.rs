@@ -0,0 +1,137 @@
+//! A token stream which can be edited, for macro expansion and refactoring
+//! tools.
+
+use std::ops::Range;
+
+use crate::token::{Loc, TokenTree};
+
+/// Where a token in a [`TokenBuffer`] came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenOrigin {
+    /// The token was in the original token stream, so its location points
+    /// into the original source.
+    Source,
+
+    /// The token was inserted by an edit, so its location doesn't point into
+    /// the original source.  `site` is the location in the original source the
+    /// token was inserted at, which is empty for insertions and covers the
+    /// replaced tokens for replacements.
+    Inserted { site: Loc },
+}
+
+/// A list of top-level token trees which supports inserting, removing and
+/// replacing ranges of tokens, while keeping track of which tokens still have
+/// a location in the original source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenBuffer {
+    /// The tokens in this buffer.
+    tokens: Vec<TokenTree>,
+
+    /// Where each token in `tokens` came from.
+    origins: Vec<TokenOrigin>,
+}
+
+impl TokenBuffer {
+    /// Creates a buffer from the token stream of a source file.
+    pub fn new(tokens: Vec<TokenTree>) -> Self {
+        Self {
+            origins: vec![TokenOrigin::Source; tokens.len()],
+            tokens,
+        }
+    }
+
+    /// Returns the tokens in this buffer.
+    pub fn tokens(&self) -> &[TokenTree] {
+        &self.tokens
+    }
+
+    /// Returns the token at `index`, if any.
+    pub fn get(&self, index: usize) -> Option<&TokenTree> {
+        self.tokens.get(index)
+    }
+
+    /// Returns where the token at `index` came from, if there is a token at
+    /// `index`.
+    pub fn origin(&self, index: usize) -> Option<&TokenOrigin> {
+        self.origins.get(index)
+    }
+
+    /// Returns the number of tokens in this buffer.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Returns whether or not this buffer has no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Returns the location in the original source of the token at `index`.
+    fn site(&self, index: usize) -> Loc {
+        match &self.origins[index] {
+            TokenOrigin::Source => self.tokens[index].loc().clone(),
+            TokenOrigin::Inserted { site } => site.clone(),
+        }
+    }
+
+    /// Returns the location in the original source of the tokens in `range`.
+    /// For an empty range, this is the empty location between the tokens
+    /// before and after it.
+    fn range_site(&self, range: &Range<usize>) -> Loc {
+        if range.start < range.end {
+            return self.site(range.start).start..self.site(range.end - 1).end;
+        }
+
+        if range.start < self.tokens.len() {
+            let start = self.site(range.start).start;
+            start..start
+        } else if range.start > 0 {
+            let end = self.site(range.start - 1).end;
+            end..end
+        } else {
+            0..0
+        }
+    }
+
+    /// Inserts `tokens` before the token at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is greater than the number of tokens.
+    pub fn insert(&mut self, index: usize, tokens: impl IntoIterator<Item = TokenTree>) {
+        self.replace(index..index, tokens);
+    }
+
+    /// Removes the tokens in `range`, and returns them.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds.
+    pub fn remove(&mut self, range: Range<usize>) -> Vec<TokenTree> {
+        self.replace(range, [])
+    }
+
+    /// Replaces the tokens in `range` with `tokens`, and returns the tokens
+    /// which were replaced.  The new tokens are marked as
+    /// [`TokenOrigin::Inserted`], at the location of the replaced tokens.
+    ///
+    /// # Panics
+    /// Panics if `range` is out of bounds.
+    pub fn replace(
+        &mut self,
+        range: Range<usize>,
+        tokens: impl IntoIterator<Item = TokenTree>,
+    ) -> Vec<TokenTree> {
+        let site = self.range_site(&range);
+        let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+
+        self.origins.splice(
+            range.clone(),
+            vec![TokenOrigin::Inserted { site }; tokens.len()],
+        );
+        self.tokens.splice(range, tokens).collect()
+    }
+
+    /// Returns the tokens in this buffer, discarding their origins.
+    pub fn into_tokens(self) -> Vec<TokenTree> {
+        self.tokens
+    }
+}
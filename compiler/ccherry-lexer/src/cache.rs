@@ -0,0 +1,594 @@
+//! A versioned binary format for token streams, used by the on-disk build
+//! cache.
+//!
+//! A cache entry starts with a header: the magic bytes `CHTK`, the format
+//! version as a little-endian `u32` and the length of the rest of the entry as
+//! a little-endian `u64`.  Everything after the header is read with bounds
+//! checks, so an entry can be decoded straight out of a memory-mapped file,
+//! and a truncated or corrupt entry is reported as an error instead of
+//! panicking.  The build cache should throw away entries with the wrong version
+//! and re-lex the source file.
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use crate::token::{
+    Char, Comment, CommentKind, Delimiter, Error, Float, FloatKind, Group, Iden, Int, IntKind,
+    Keyword, Loc, Punct, Spacing, Str, StrKind, StrSegment, TokenTree, Trivia, TriviaKind,
+    TriviaToken,
+};
+
+/// The magic bytes at the start of every token cache entry.
+const MAGIC: &[u8; 4] = b"CHTK";
+
+/// The version of the token cache format.  This must be bumped whenever the
+/// format, or the meaning of a token, changes.
+pub const TOKEN_CACHE_VERSION: u32 = 14;
+
+/// Encodes `tokens` as a token cache entry.
+pub fn encode_tokens(tokens: &[TokenTree]) -> Vec<u8> {
+    let mut payload = Writer::default();
+    payload.tokens(tokens);
+
+    let mut bytes = Vec::with_capacity(16 + payload.bytes.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&TOKEN_CACHE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(payload.bytes.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload.bytes);
+    bytes
+}
+
+/// Decodes a token cache entry created by [`encode_tokens`].
+///
+/// Returns an error if `bytes` isn't a token cache entry, if it was created by
+/// a different version of the format, or if it is truncated or corrupt.
+pub fn decode_tokens(bytes: &[u8]) -> Result<Vec<TokenTree>, Diagnostic<()>> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(4)? != MAGIC {
+        return Err(Diagnostic::error().with_message("not a token cache entry"));
+    }
+
+    let version = reader.u32()?;
+    if version != TOKEN_CACHE_VERSION {
+        return Err(Diagnostic::error().with_message(format!(
+            "token cache entry has version {}, expected version {}",
+            version, TOKEN_CACHE_VERSION
+        )));
+    }
+
+    let len = reader.u64()?;
+    if len != reader.bytes.len() as u64 {
+        return Err(corrupt());
+    }
+
+    let tokens = reader.tokens()?;
+
+    if !reader.bytes.is_empty() {
+        return Err(corrupt());
+    }
+
+    Ok(tokens)
+}
+
+/// Returns the error for a truncated or corrupt cache entry.
+fn corrupt() -> Diagnostic<()> {
+    Diagnostic::error().with_message("token cache entry is truncated or corrupt")
+}
+
+/// Writes the payload of a cache entry.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u128(&mut self, value: u128) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Writes a length or byte index, which is always 64 bits wide so entries
+    /// don't depend on the width of `usize`.
+    fn len(&mut self, len: usize) {
+        self.u64(len as u64);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn loc(&mut self, loc: &Loc) {
+        self.len(loc.start);
+        self.len(loc.end);
+    }
+
+    fn spacing(&mut self, spacing: &Spacing) {
+        self.u8(match spacing {
+            Spacing::None => 0,
+            Spacing::Whitespace => 1,
+            Spacing::LineBreak => 2,
+            Spacing::Joint => 3,
+        });
+    }
+
+    fn trivia(&mut self, trivia: Option<&Trivia>) {
+        match trivia {
+            Some(trivia) => {
+                self.u8(1);
+                self.str(&trivia.leading);
+                self.str(&trivia.source);
+                self.str(&trivia.trailing);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn comments(&mut self, comments: &[Comment]) {
+        self.len(comments.len());
+
+        for comment in comments {
+            self.comment(comment);
+        }
+    }
+
+    fn comment(&mut self, comment: &Comment) {
+        self.loc(&comment.loc);
+        self.str(&comment.value);
+        self.u8(match comment.kind {
+            CommentKind::Line => 0,
+            CommentKind::Doc => 1,
+            CommentKind::Block => 2,
+            CommentKind::InnerDoc => 3,
+            CommentKind::BlockDoc => 4,
+            CommentKind::InnerBlockDoc => 5,
+        });
+    }
+
+    fn segments(&mut self, segments: &[StrSegment]) {
+        self.len(segments.len());
+
+        for segment in segments {
+            match segment {
+                StrSegment::Text(text) => {
+                    self.u8(0);
+                    self.str(text);
+                }
+                StrSegment::Interpolation(group) => {
+                    self.u8(1);
+                    self.tokens(&group.tokens);
+                    self.comments(&group.inner_comments);
+                    self.loc(&group.loc);
+                    self.comments(&group.comments);
+                }
+            }
+        }
+    }
+
+    fn tokens(&mut self, tokens: &[TokenTree]) {
+        self.len(tokens.len());
+
+        for token in tokens {
+            self.token(token);
+        }
+    }
+
+    fn token(&mut self, token: &TokenTree) {
+        match token {
+            TokenTree::Iden(token) => {
+                self.u8(0);
+                self.str(&token.value);
+                self.u8(token.raw as u8);
+            }
+            TokenTree::Keyword(token) => {
+                self.u8(1);
+                self.str(&token.value);
+            }
+            TokenTree::Punct(token) => {
+                self.u8(2);
+                self.u32(token.value as u32);
+            }
+            TokenTree::Int(token) => {
+                self.u8(3);
+                self.u8(match token.kind {
+                    IntKind::Decimal => 0,
+                    IntKind::Hexadecimal => 1,
+                    IntKind::Octal => 2,
+                    IntKind::Binary => 3,
+                });
+                self.u128(token.value);
+                self.u8(token.negative as u8);
+                self.str(&token.digits);
+            }
+            TokenTree::Float(token) => {
+                self.u8(4);
+                self.u64(token.value.to_bits());
+                self.u8(match token.kind {
+                    FloatKind::Decimal => 0,
+                    FloatKind::Exponent => 1,
+                    FloatKind::LeadingDot => 2,
+                    FloatKind::TrailingDot => 3,
+                    FloatKind::Hexadecimal => 4,
+                });
+            }
+            TokenTree::Str(token) => {
+                self.u8(5);
+                self.segments(&token.value);
+                match token.kind {
+                    StrKind::Normal => self.u8(0),
+                    StrKind::Raw(hashes) => {
+                        self.u8(1);
+                        self.len(hashes);
+                    }
+                    StrKind::Triple => self.u8(2),
+                }
+            }
+            TokenTree::Char(token) => {
+                self.u8(6);
+                self.u32(token.value as u32);
+            }
+            TokenTree::Group(token) => {
+                self.u8(7);
+                self.u8(match token.delimiter {
+                    Delimiter::Paren => 0,
+                    Delimiter::Bracket => 1,
+                    Delimiter::Brace => 2,
+                });
+                self.tokens(&token.tokens);
+                self.comments(&token.inner_comments);
+            }
+            TokenTree::Error(_) => self.u8(8),
+            TokenTree::Trivia(token) => {
+                self.u8(9);
+                match &token.kind {
+                    TriviaKind::Whitespace => self.u8(0),
+                    TriviaKind::LineBreak => self.u8(1),
+                    TriviaKind::Comment(comment) => {
+                        self.u8(2);
+                        self.comment(comment);
+                    }
+                    TriviaKind::Shebang => self.u8(3),
+                }
+            }
+        }
+
+        // trivia tokens have nothing but their location, so nothing else is
+        // written for them.
+        self.loc(token.loc());
+        if !token.is_trivia() {
+            self.comments(token.comments());
+            self.spacing(token.spacing());
+            self.comments(token.trailing_comments());
+            self.trivia(token.trivia());
+        }
+    }
+}
+
+/// Reads the payload of a cache entry, checking that every read is in bounds.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Takes the next `len` bytes.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Diagnostic<()>> {
+        if len > self.bytes.len() {
+            return Err(corrupt());
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Diagnostic<()>> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Diagnostic<()>> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, Diagnostic<()>> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupt()),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, Diagnostic<()>> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Diagnostic<()>> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn u128(&mut self) -> Result<u128, Diagnostic<()>> {
+        Ok(u128::from_le_bytes(self.array()?))
+    }
+
+    /// Reads a length or byte index written by [`Writer::len`].
+    fn len(&mut self) -> Result<usize, Diagnostic<()>> {
+        usize::try_from(self.u64()?).map_err(|_| corrupt())
+    }
+
+    fn char(&mut self) -> Result<char, Diagnostic<()>> {
+        char::from_u32(self.u32()?).ok_or_else(corrupt)
+    }
+
+    fn str(&mut self) -> Result<String, Diagnostic<()>> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+
+        std::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| corrupt())
+    }
+
+    fn loc(&mut self) -> Result<Loc, Diagnostic<()>> {
+        let start = self.len()?;
+        let end = self.len()?;
+
+        if start > end {
+            return Err(corrupt());
+        }
+
+        Ok(start..end)
+    }
+
+    fn spacing(&mut self) -> Result<Spacing, Diagnostic<()>> {
+        match self.u8()? {
+            0 => Ok(Spacing::None),
+            1 => Ok(Spacing::Whitespace),
+            2 => Ok(Spacing::LineBreak),
+            3 => Ok(Spacing::Joint),
+            _ => Err(corrupt()),
+        }
+    }
+
+    fn trivia(&mut self) -> Result<Option<Trivia>, Diagnostic<()>> {
+        if !self.bool()? {
+            return Ok(None);
+        }
+
+        Ok(Some(Trivia {
+            leading: self.str()?,
+            source: self.str()?,
+            trailing: self.str()?,
+        }))
+    }
+
+    fn comments(&mut self) -> Result<Box<[Comment]>, Diagnostic<()>> {
+        let len = self.len()?;
+        let mut comments = vec![];
+
+        for _ in 0..len {
+            comments.push(self.comment()?);
+        }
+
+        Ok(comments.into_boxed_slice())
+    }
+
+    fn comment(&mut self) -> Result<Comment, Diagnostic<()>> {
+        let loc = self.loc()?;
+        let value = self.str()?;
+        let kind = match self.u8()? {
+            0 => CommentKind::Line,
+            1 => CommentKind::Doc,
+            2 => CommentKind::Block,
+            3 => CommentKind::InnerDoc,
+            4 => CommentKind::BlockDoc,
+            5 => CommentKind::InnerBlockDoc,
+            _ => return Err(corrupt()),
+        };
+
+        Ok(Comment { loc, value, kind })
+    }
+
+    fn segments(&mut self) -> Result<Vec<StrSegment>, Diagnostic<()>> {
+        let len = self.len()?;
+        let mut segments = vec![];
+
+        for _ in 0..len {
+            segments.push(match self.u8()? {
+                0 => StrSegment::Text(self.str()?),
+                1 => StrSegment::Interpolation(Group {
+                    delimiter: Delimiter::Brace,
+                    tokens: self.tokens()?,
+                    inner_comments: self.comments()?,
+                    loc: self.loc()?,
+                    comments: self.comments()?,
+                    spacing: Spacing::None,
+                    trailing_comments: Box::default(),
+                    trivia: None,
+                }),
+                _ => return Err(corrupt()),
+            });
+        }
+
+        Ok(segments)
+    }
+
+    fn tokens(&mut self) -> Result<Vec<TokenTree>, Diagnostic<()>> {
+        let len = self.len()?;
+        let mut tokens = vec![];
+
+        for _ in 0..len {
+            tokens.push(self.token()?);
+        }
+
+        Ok(tokens)
+    }
+
+    fn token(&mut self) -> Result<TokenTree, Diagnostic<()>> {
+        // the location, comments, spacing and trivia come after the fields
+        // specific to each kind of token, so they're filled in afterwards.
+        let mut token = match self.u8()? {
+            0 => {
+                let value = self.str()?;
+                let raw = self.bool()?;
+
+                TokenTree::Iden(Iden {
+                    loc: 0..0,
+                    value,
+                    raw,
+                    comments: Box::default(),
+                    spacing: Spacing::None,
+                    trailing_comments: Box::default(),
+                    trivia: None,
+                })
+            }
+            1 => TokenTree::Keyword(Keyword {
+                loc: 0..0,
+                value: self.str()?,
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+            2 => TokenTree::Punct(Punct {
+                loc: 0..0,
+                value: self.char()?,
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+            3 => {
+                let kind = match self.u8()? {
+                    0 => IntKind::Decimal,
+                    1 => IntKind::Hexadecimal,
+                    2 => IntKind::Octal,
+                    3 => IntKind::Binary,
+                    _ => return Err(corrupt()),
+                };
+                let value = self.u128()?;
+                let negative = self.bool()?;
+                let digits = self.str()?;
+
+                TokenTree::Int(Int {
+                    loc: 0..0,
+                    kind,
+                    value,
+                    negative,
+                    digits,
+                    comments: Box::default(),
+                    spacing: Spacing::None,
+                    trailing_comments: Box::default(),
+                    trivia: None,
+                })
+            }
+            4 => {
+                let value = f64::from_bits(self.u64()?);
+                let kind = match self.u8()? {
+                    0 => FloatKind::Decimal,
+                    1 => FloatKind::Exponent,
+                    2 => FloatKind::LeadingDot,
+                    3 => FloatKind::TrailingDot,
+                    4 => FloatKind::Hexadecimal,
+                    _ => return Err(corrupt()),
+                };
+
+                TokenTree::Float(Float {
+                    loc: 0..0,
+                    value,
+                    kind,
+                    comments: Box::default(),
+                    spacing: Spacing::None,
+                    trailing_comments: Box::default(),
+                    trivia: None,
+                })
+            }
+            5 => {
+                let value = self.segments()?;
+                let kind = match self.u8()? {
+                    0 => StrKind::Normal,
+                    1 => StrKind::Raw(self.len()?),
+                    2 => StrKind::Triple,
+                    _ => return Err(corrupt()),
+                };
+
+                TokenTree::Str(Str {
+                    loc: 0..0,
+                    value,
+                    kind,
+                    comments: Box::default(),
+                    spacing: Spacing::None,
+                    trailing_comments: Box::default(),
+                    trivia: None,
+                })
+            }
+            6 => TokenTree::Char(Char {
+                loc: 0..0,
+                value: self.char()?,
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+            7 => TokenTree::Group(Group {
+                loc: 0..0,
+                delimiter: match self.u8()? {
+                    0 => Delimiter::Paren,
+                    1 => Delimiter::Bracket,
+                    2 => Delimiter::Brace,
+                    _ => return Err(corrupt()),
+                },
+                tokens: self.tokens()?,
+                inner_comments: self.comments()?,
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+            8 => TokenTree::Error(Error {
+                loc: 0..0,
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+            9 => TokenTree::Trivia(TriviaToken {
+                loc: 0..0,
+                kind: match self.u8()? {
+                    0 => TriviaKind::Whitespace,
+                    1 => TriviaKind::LineBreak,
+                    2 => TriviaKind::Comment(self.comment()?),
+                    3 => TriviaKind::Shebang,
+                    _ => return Err(corrupt()),
+                },
+            }),
+            _ => return Err(corrupt()),
+        };
+
+        // only the location of a trivia token is written.
+        *token.loc_mut() = self.loc()?;
+        if let Some(comments) = token.comments_mut() {
+            *comments = self.comments()?;
+        }
+        if let Some(spacing) = token.spacing_mut() {
+            *spacing = self.spacing()?;
+        }
+        if let Some(comments) = token.trailing_comments_mut() {
+            *comments = self.comments()?;
+        }
+        if let Some(trivia) = token.trivia_mut() {
+            *trivia = self.trivia()?;
+        }
+
+        Ok(token)
+    }
+}
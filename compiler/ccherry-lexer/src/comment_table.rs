@@ -0,0 +1,62 @@
+//! A side table of comments, used instead of attaching comments to tokens.
+
+use crate::token::Comment;
+
+/// Every comment in a source string, in the order they appear.
+///
+/// A lexer built with [`Lexer::with_comment_table`](crate::Lexer::with_comment_table)
+/// collects comments here, instead of attaching them to the token after them,
+/// so tools like the formatter can look at comment placement as a whole.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommentTable {
+    /// The comments, sorted by their location.
+    comments: Vec<Comment>,
+}
+
+impl CommentTable {
+    /// Creates a new, empty comment table.
+    pub fn new() -> Self {
+        Self { comments: vec![] }
+    }
+
+    /// Adds a comment to this table.  Comments must be added in the order they
+    /// appear in the source.
+    pub(crate) fn push(&mut self, comment: Comment) {
+        self.comments.push(comment);
+    }
+
+    /// Removes every comment after the first `len`, when the lexer rewinds to
+    /// before them.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.comments.truncate(len);
+    }
+
+    /// Returns every comment in this table.
+    pub fn all(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Returns the comments which are entirely between the byte indices
+    /// `start` and `end`.  To get the comments between two tokens, use the end
+    /// of the first token and the start of the second.
+    pub fn between(&self, start: usize, end: usize) -> &[Comment] {
+        let first = self
+            .comments
+            .partition_point(|comment| comment.loc.start < start);
+        let last = self
+            .comments
+            .partition_point(|comment| comment.loc.end <= end);
+
+        &self.comments[first..last.max(first)]
+    }
+
+    /// Returns the number of comments in this table.
+    pub fn len(&self) -> usize {
+        self.comments.len()
+    }
+
+    /// Returns whether or not this table has no comments.
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+}
@@ -0,0 +1,161 @@
+//! A compact, single-line rendering of tokens, for tests.
+//!
+//! A token is rendered as its kind and value, its location and its spacing,
+//! such as `Iden("test")@0..4 ws`.  Groups render their tokens between
+//! brackets, such as `Group[Iden("a")@1..2]@0..3`, and interpolated strings
+//! render their segments between brackets, such as
+//! `Str["a ", Group[Iden("b")@5..6]@4..7]@0..8`.  Comments aren't rendered,
+//! so tests which check comments should still compare the tokens themselves.
+
+use std::fmt;
+
+use crate::token::{IntKind, Spacing, StrKind, StrSegment, TokenTree, TriviaKind};
+
+/// Renders a [`TokenTree`] on a single line, created by
+/// [`TokenTree::compact`].
+#[derive(Clone, Copy, Debug)]
+pub struct Compact<'a>(&'a TokenTree);
+
+impl TokenTree {
+    /// Returns an adapter which displays this token on a single line.
+    pub fn compact(&self) -> Compact<'_> {
+        Compact(self)
+    }
+}
+
+/// Writes the tokens of a group, such as `Group[Iden("a")@1..2]`, without the
+/// location of the group.
+fn write_tokens(f: &mut fmt::Formatter<'_>, tokens: &[TokenTree]) -> fmt::Result {
+    f.write_str("Group[")?;
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", token.compact())?;
+    }
+    f.write_str("]")
+}
+
+impl fmt::Display for Compact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (loc, spacing) = match self.0 {
+            TokenTree::Iden(token) if token.raw => {
+                write!(f, "RawIden({:?})", token.value)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Iden(token) => {
+                write!(f, "Iden({:?})", token.value)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Keyword(token) => {
+                write!(f, "Keyword({:?})", token.value)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Punct(token) => {
+                write!(f, "Punct({:?})", token.value)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Int(token) => {
+                let prefix = match token.kind {
+                    IntKind::Decimal => "",
+                    IntKind::Hexadecimal => "0x",
+                    IntKind::Octal => "0o",
+                    IntKind::Binary => "0b",
+                };
+                let sign = if token.negative { "-" } else { "" };
+
+                write!(f, "Int({}{}{})", sign, prefix, token.digits)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Float(token) => {
+                write!(f, "Float({:?})", token.value)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Str(token) => {
+                match &token.kind {
+                    StrKind::Normal => f.write_str("Str")?,
+                    StrKind::Raw(hashes) => write!(f, "RawStr{}", hashes)?,
+                    StrKind::Triple => f.write_str("TripleStr")?,
+                }
+
+                match token.text() {
+                    Some(text) => write!(f, "({:?})", text)?,
+                    None => {
+                        f.write_str("[")?;
+                        for (i, segment) in token.value.iter().enumerate() {
+                            if i > 0 {
+                                f.write_str(", ")?;
+                            }
+                            match segment {
+                                StrSegment::Text(text) => write!(f, "{:?}", text)?,
+                                StrSegment::Interpolation(group) => {
+                                    write_tokens(f, &group.tokens)?;
+                                    write!(f, "@{}..{}", group.loc.start, group.loc.end)?;
+                                }
+                            }
+                        }
+                        f.write_str("]")?;
+                    }
+                }
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Char(token) => {
+                write!(f, "Char({:?})", token.value)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Group(token) => {
+                write_tokens(f, &token.tokens)?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Error(token) => {
+                f.write_str("Error")?;
+                (&token.loc, &token.spacing)
+            }
+            TokenTree::Trivia(token) => {
+                match &token.kind {
+                    TriviaKind::Whitespace => f.write_str("Whitespace")?,
+                    TriviaKind::LineBreak => f.write_str("LineBreak")?,
+                    TriviaKind::Comment(comment) => write!(f, "Comment({:?})", comment.value)?,
+                    TriviaKind::Shebang => f.write_str("Shebang")?,
+                }
+                (&token.loc, &Spacing::None)
+            }
+        };
+
+        write!(f, "@{}..{}", loc.start, loc.end)?;
+
+        match spacing {
+            Spacing::None => Ok(()),
+            Spacing::Whitespace => f.write_str(" ws"),
+            Spacing::LineBreak => f.write_str(" lb"),
+            Spacing::Joint => f.write_str(" joint"),
+        }
+    }
+}
+
+/// Asserts that lexing a source string produces the provided tokens, written
+/// in their [compact rendering](TokenTree::compact), such as
+/// `assert_tokens!("a", [r#"Iden("a")@0..1"#])`.  Panics if the lexer returns
+/// an error.
+///
+/// A configured lexer can be used instead of a source string, by putting the
+/// lexer before a `=>`, such as `assert_tokens!(lexer => [..])`.
+#[macro_export]
+macro_rules! assert_tokens {
+    ($lexer:expr => [$($token:expr),* $(,)?]) => {{
+        let tokens: ::std::vec::Vec<::std::string::String> = $lexer
+            .map(|token| match token {
+                ::std::result::Result::Ok(token) => token.compact().to_string(),
+                ::std::result::Result::Err(error) => {
+                    panic!("unexpected lexer error: {}", error)
+                }
+            })
+            .collect();
+        let expected: &[&str] = &[$($token),*];
+
+        assert_eq!(tokens, expected);
+    }};
+    ($source:expr, [$($token:expr),* $(,)?]) => {
+        $crate::assert_tokens!($crate::Lexer::new($source) => [$($token),*])
+    };
+}
@@ -0,0 +1,368 @@
+//! Configuration for the lexer, so it can be reused for languages other than
+//! Cherry.
+
+use codespan_reporting::diagnostic::Diagnostic;
+
+use crate::Lexer;
+
+/// Configuration for a [`Lexer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexerConfig {
+    /// Characters which may continue an identifier, in addition to the
+    /// characters with the Unicode `XID_Continue` property.
+    iden_continue: Vec<char>,
+
+    /// Identifiers which are lexed as [`Keyword`](crate::Keyword)s.
+    keywords: Vec<String>,
+
+    /// Whether or not the indentation of triple-quoted strings is removed.
+    strip_indent: bool,
+
+    /// Whether or not floats may start with a `.`, such as `.5`.
+    leading_dot_floats: bool,
+
+    /// Whether or not floats may end with a `.`, such as `1.`.
+    trailing_dot_floats: bool,
+
+    /// The characters which are lexed as [`Punct`](crate::Punct)s.
+    puncts: Vec<char>,
+
+    /// The markers which start a line comment, such as `//`.
+    line_comments: Vec<String>,
+
+    /// Whether or not `/* ... */` block comments are recognized.
+    block_comments: bool,
+
+    /// Whether or not `'...'` is a string, rather than a character literal.
+    single_quote_strings: bool,
+
+    /// Whether or not identifiers with letters which look like letters from
+    /// another script are warned about.
+    confusable_warnings: bool,
+
+    /// Whether or not a `-` followed by a digit is part of a negative number
+    /// literal, rather than a punctuator.
+    negative_literals: bool,
+
+    /// How many groups and interpolations deep a group may be nested.
+    max_depth: usize,
+}
+
+impl LexerConfig {
+    /// Creates the default configuration, which lexes Cherry.
+    pub fn new() -> Self {
+        Self {
+            iden_continue: vec![],
+            keywords: vec![],
+            strip_indent: true,
+            leading_dot_floats: false,
+            trailing_dot_floats: false,
+            puncts: "!@#$%&*;:,.<>/|-=+?~".chars().collect(),
+            line_comments: vec!["//".to_string()],
+            block_comments: true,
+            single_quote_strings: false,
+            confusable_warnings: true,
+            negative_literals: true,
+            max_depth: 256,
+        }
+    }
+
+    /// Returns this configuration after allowing `chars` to continue an
+    /// identifier, such as `-` for `kebab-case` or `'` for `x'`.
+    ///
+    /// If one of `chars` is a punctuator, it only continues an identifier when
+    /// another identifier character follows it, so `a-b` is one identifier,
+    /// but `a - b` and `a-` still contain a `-` punctuator.
+    ///
+    /// Returns an error if one of `chars` can't be part of an identifier
+    /// without changing how other tokens are lexed: whitespace, line breaks,
+    /// `"` and group delimiters.
+    pub fn with_iden_continue(mut self, chars: &[char]) -> Result<Self, Diagnostic<()>> {
+        for &char in chars {
+            if let Some(conflict) = conflict(char) {
+                return Err(Diagnostic::error().with_message(format!(
+                    "`{}` can't continue an identifier, because it {}",
+                    char.escape_debug(),
+                    conflict
+                )));
+            }
+
+            if !self.iden_continue.contains(&char) {
+                self.iden_continue.push(char);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Returns this configuration after adding `keywords` to the keyword
+    /// table.  Identifiers in the keyword table are lexed as
+    /// [`Keyword`](crate::Keyword)s, unless they are raw identifiers.
+    pub fn with_keywords(mut self, keywords: &[&str]) -> Self {
+        for keyword in keywords {
+            if !self.is_keyword(keyword) {
+                self.keywords.push(keyword.to_string());
+            }
+        }
+
+        self
+    }
+
+    /// Returns the keyword table.
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+
+    /// Returns whether or not `iden` is in the keyword table.
+    pub fn is_keyword(&self, iden: &str) -> bool {
+        self.keywords.iter().any(|keyword| keyword == iden)
+    }
+
+    /// Returns this configuration after enabling or disabling indentation
+    /// stripping for triple-quoted strings, which is enabled by default.  When
+    /// it is disabled, the indentation before the closing `"""` is kept on
+    /// every line.
+    pub fn with_indent_stripping(mut self, strip_indent: bool) -> Self {
+        self.strip_indent = strip_indent;
+        self
+    }
+
+    /// Returns whether or not the indentation of triple-quoted strings is
+    /// removed.
+    pub fn strips_indent(&self) -> bool {
+        self.strip_indent
+    }
+
+    /// Returns this configuration after allowing or disallowing floats which
+    /// start with a `.`, such as `.5`.  They are disallowed by default, since
+    /// `x.0` would be lexed as `x` followed by `.0`, rather than a field
+    /// access.
+    pub fn with_leading_dot_floats(mut self, leading_dot_floats: bool) -> Self {
+        self.leading_dot_floats = leading_dot_floats;
+        self
+    }
+
+    /// Returns whether or not floats may start with a `.`.
+    pub fn leading_dot_floats(&self) -> bool {
+        self.leading_dot_floats
+    }
+
+    /// Returns this configuration after allowing or disallowing floats which
+    /// end with a `.`, such as `1.`.  They are disallowed by default, since
+    /// they look like the start of a method call.  Even when they are allowed,
+    /// a `.` followed by an identifier or another `.` isn't part of the
+    /// number, so `1.max(2)` and `1..2` lex the same either way.
+    pub fn with_trailing_dot_floats(mut self, trailing_dot_floats: bool) -> Self {
+        self.trailing_dot_floats = trailing_dot_floats;
+        self
+    }
+
+    /// Returns whether or not floats may end with a `.`.
+    pub fn trailing_dot_floats(&self) -> bool {
+        self.trailing_dot_floats
+    }
+
+    /// Returns the characters which may continue an identifier, in addition
+    /// to the characters with the Unicode `XID_Continue` property.
+    pub fn iden_continue(&self) -> &[char] {
+        &self.iden_continue
+    }
+
+    /// Returns whether or not `char` continues an identifier, if `next` is the
+    /// character after it.
+    pub(crate) fn is_iden_continue(&self, char: char, next: Option<char>) -> bool {
+        if unicode_xid::UnicodeXID::is_xid_continue(char) {
+            return true;
+        }
+
+        if !self.iden_continue.contains(&char) {
+            return false;
+        }
+
+        !self.is_punct(char) || next.is_some_and(|next| self.is_iden_continue(next, None))
+    }
+
+    /// Returns this configuration after replacing the punctuators with
+    /// `chars`, which are all of the characters lexed as
+    /// [`Punct`](crate::Punct)s.  By default, they are
+    /// `! @ # $ % & * ; : , . < > / | - = + ? ~`.
+    ///
+    /// Returns an error if one of `chars` is already part of other tokens:
+    /// whitespace, line breaks, `"`, group delimiters, and the characters of
+    /// identifiers and numbers.
+    pub fn with_puncts(mut self, chars: &[char]) -> Result<Self, Diagnostic<()>> {
+        for &char in chars {
+            let conflict = conflict(char).or_else(|| {
+                (Lexer::is_iden(char) || unicode_xid::UnicodeXID::is_xid_continue(char))
+                    .then_some("is part of identifiers or numbers")
+            });
+
+            if let Some(conflict) = conflict {
+                return Err(Diagnostic::error().with_message(format!(
+                    "`{}` can't be a punctuator, because it {}",
+                    char.escape_debug(),
+                    conflict
+                )));
+            }
+        }
+
+        self.puncts = chars.to_vec();
+        Ok(self)
+    }
+
+    /// Returns the characters which are lexed as punctuators.
+    pub fn puncts(&self) -> &[char] {
+        &self.puncts
+    }
+
+    /// Returns whether or not `char` is lexed as a punctuator.
+    pub fn is_punct(&self, char: char) -> bool {
+        self.puncts.contains(&char)
+    }
+
+    /// Returns this configuration after replacing the markers which start a
+    /// line comment with `markers`, such as `#` or `--`.  The default is `//`,
+    /// and no markers disables line comments.  Only `//` comments can be
+    /// documentation comments, as `///` and `//!`.
+    ///
+    /// A comment marker takes precedence over punctuators, so `#` is no longer
+    /// a punctuator when it starts comments.  Returns an error if a marker is
+    /// empty, or starts with a character which starts other tokens:
+    /// whitespace, line breaks, `"`, group delimiters, identifiers and
+    /// numbers.
+    pub fn with_line_comments(mut self, markers: &[&str]) -> Result<Self, Diagnostic<()>> {
+        for marker in markers {
+            let first = match marker.chars().next() {
+                Some(first) => first,
+                None => {
+                    return Err(
+                        Diagnostic::error().with_message("a line comment marker can't be empty")
+                    )
+                }
+            };
+
+            let conflict = conflict(first).or_else(|| {
+                (Lexer::is_iden(first) || Lexer::is_digit(first))
+                    .then_some("starts an identifier or a number")
+            });
+
+            if let Some(conflict) = conflict {
+                return Err(Diagnostic::error().with_message(format!(
+                    "`{}` can't start a line comment, because `{}` {}",
+                    marker.escape_debug(),
+                    first.escape_debug(),
+                    conflict
+                )));
+            }
+        }
+
+        self.line_comments = markers.iter().map(|marker| marker.to_string()).collect();
+        Ok(self)
+    }
+
+    /// Returns the markers which start a line comment.
+    pub fn line_comments(&self) -> &[String] {
+        &self.line_comments
+    }
+
+    /// Returns this configuration after enabling or disabling `/* ... */`
+    /// block comments, which are enabled by default.  When they are disabled,
+    /// `/*` is lexed as two punctuators.
+    pub fn with_block_comments(mut self, block_comments: bool) -> Self {
+        self.block_comments = block_comments;
+        self
+    }
+
+    /// Returns whether or not `/* ... */` block comments are recognized.
+    pub fn block_comments(&self) -> bool {
+        self.block_comments
+    }
+
+    /// Returns this configuration after making `'...'` a string, like
+    /// `"..."`, rather than a character literal.  This is disabled by default.
+    /// Single-quoted strings support the same escapes and interpolations as
+    /// double-quoted ones, and are [`StrKind::Normal`](crate::StrKind::Normal)
+    /// strings.
+    pub fn with_single_quote_strings(mut self, single_quote_strings: bool) -> Self {
+        self.single_quote_strings = single_quote_strings;
+        self
+    }
+
+    /// Returns whether or not `'...'` is a string.
+    pub fn single_quote_strings(&self) -> bool {
+        self.single_quote_strings
+    }
+
+    /// Returns this configuration after enabling or disabling the warnings
+    /// for confusable identifiers, which are enabled by default.  They warn
+    /// about identifiers which look like ASCII identifiers but aren't, such as
+    /// `pаth` with a Cyrillic `а`, and identifiers which mix letters from
+    /// different scripts (see
+    /// [`LexError::ConfusableIdentifier`](crate::LexError::ConfusableIdentifier)
+    /// and
+    /// [`LexError::MixedScriptIdentifier`](crate::LexError::MixedScriptIdentifier)).
+    pub fn with_confusable_warnings(mut self, confusable_warnings: bool) -> Self {
+        self.confusable_warnings = confusable_warnings;
+        self
+    }
+
+    /// Returns whether or not confusable identifiers are warned about.
+    pub fn confusable_warnings(&self) -> bool {
+        self.confusable_warnings
+    }
+
+    /// Returns this configuration after enabling or disabling negative number
+    /// literals, which are enabled by default.  When they are enabled, a `-`
+    /// followed by a digit is part of the number, so `-1` is a single
+    /// [`Int`](crate::Int), which suits data languages.  When they are
+    /// disabled, the `-` is always a [`Punct`](crate::Punct), so `a-1` lexes
+    /// as `a`, `-` and `1`, and the sign is left to the parser, which can tell
+    /// `-1` from `- 1` by the spacing of the `-`.
+    pub fn with_negative_literals(mut self, negative_literals: bool) -> Self {
+        self.negative_literals = negative_literals;
+        self
+    }
+
+    /// Returns whether or not a `-` followed by a digit starts a negative
+    /// number literal.
+    pub fn negative_literals(&self) -> bool {
+        self.negative_literals
+    }
+
+    /// Returns this configuration after limiting how deeply groups may be
+    /// nested, which is 256 groups and interpolations by default.  A
+    /// top-level group is 1 group deep, a group in it is 2 groups deep, and so
+    /// on.  A group nested any deeper is an error
+    /// ([`LexError::GroupTooDeep`](crate::LexError::GroupTooDeep)), rather
+    /// than a problem for everything which walks the token trees
+    /// recursively.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns how many groups and interpolations deep a group may be nested.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
+
+/// Returns why `char` can't be given another meaning by the configuration, if
+/// it is whitespace, or starts a string or a group.
+fn conflict(char: char) -> Option<&'static str> {
+    if Lexer::is_whitespace(char) || Lexer::is_line_break(char) {
+        Some("is whitespace")
+    } else if char == '"' {
+        Some("starts a string")
+    } else if matches!(char, '(' | ')' | '[' | ']' | '{' | '}') {
+        Some("is a group delimiter")
+    } else {
+        None
+    }
+}
+
+impl Default for LexerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
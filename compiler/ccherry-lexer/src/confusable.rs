@@ -0,0 +1,57 @@
+//! Warnings for identifiers which are easily confused with others, because
+//! they contain letters from other scripts which look like Latin letters,
+//! such as the Cyrillic `а` in `pаth`.
+
+use unicode_script::{Script, UnicodeScript};
+use unicode_security::{skeleton, MixedScript};
+
+use crate::error::LexError;
+use crate::token::Loc;
+
+/// Returns the warning for the identifier `value` at `loc`, if it looks like
+/// an ASCII identifier, or mixes letters from different scripts.  `value`
+/// should already be normalized to NFC.
+pub(crate) fn check_identifier(value: &str, loc: &Loc) -> Option<LexError> {
+    if value.is_ascii() {
+        return None;
+    }
+
+    let skeleton: String = skeleton(value).collect();
+    if skeleton.is_ascii() {
+        return Some(LexError::ConfusableIdentifier {
+            loc: loc.clone(),
+            value: value.to_string(),
+            skeleton,
+        });
+    }
+
+    if value.is_single_script() {
+        return None;
+    }
+
+    let mut scripts = vec![];
+    for char in value.chars() {
+        let script = char.script();
+        if !matches!(script, Script::Common | Script::Inherited) && !scripts.contains(&script) {
+            scripts.push(script);
+        }
+    }
+
+    Some(LexError::MixedScriptIdentifier {
+        loc: loc.clone(),
+        scripts: scripts.into_iter().map(Script::full_name).collect(),
+    })
+}
+
+/// Returns the characters of `value` which look like ASCII characters, along
+/// with the ASCII characters they look like.
+pub(crate) fn lookalikes(value: &str) -> Vec<(char, String)> {
+    value
+        .chars()
+        .filter(|char| !char.is_ascii())
+        .filter_map(|char| {
+            let skeleton: String = skeleton(char.encode_utf8(&mut [0; 4])).collect();
+            skeleton.is_ascii().then_some((char, skeleton))
+        })
+        .collect()
+}
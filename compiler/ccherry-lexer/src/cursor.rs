@@ -0,0 +1,221 @@
+//! A cursor over a token stream, for the parser and the macro matcher.
+//!
+//! Both of them report tokens they didn't expect with
+//! [`TokenCursor::expected`], so the wording of "expected X, found Y" errors
+//! is the same wherever they come from.
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use crate::printer::write_token;
+use crate::token::{Delimiter, Group, Iden, Keyword, Loc, Punct, TokenTree};
+
+/// A position in a list of token trees, such as the top-level tokens of a file
+/// or the tokens of a group.
+///
+/// The cursor is [`Copy`], so a position can be saved by copying the cursor,
+/// and gone back to by assigning the copy back.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenCursor<'a> {
+    /// The tokens this cursor moves over.
+    tokens: &'a [TokenTree],
+
+    /// The index of the next token in `tokens`.
+    idx: usize,
+
+    /// The group `tokens` are in, if any.  Its closing delimiter is what is
+    /// found once every token has been consumed.
+    group: Option<&'a Group>,
+}
+
+impl<'a> TokenCursor<'a> {
+    /// Creates a cursor at the start of `tokens`, which are the top-level
+    /// tokens of a file.
+    pub fn new(tokens: &'a [TokenTree]) -> Self {
+        Self {
+            tokens,
+            idx: 0,
+            group: None,
+        }
+    }
+
+    /// Creates a cursor at the start of the tokens in `group`.
+    pub fn group(group: &'a Group) -> Self {
+        Self {
+            tokens: &group.tokens,
+            idx: 0,
+            group: Some(group),
+        }
+    }
+
+    /// Returns the index of the next token.
+    pub fn position(&self) -> usize {
+        self.idx
+    }
+
+    /// Returns the tokens this cursor moves over.
+    pub(crate) fn tokens(&self) -> &'a [TokenTree] {
+        self.tokens
+    }
+
+    /// Returns where the tokens end: at the closing delimiter of the group
+    /// they are in, or at the end of the last token at the top level.
+    pub(crate) fn end(&self) -> usize {
+        match self.group {
+            Some(group) => group.loc.end.saturating_sub(1),
+            None => self.tokens.last().map_or(0, |token| token.loc().end),
+        }
+    }
+
+    /// Returns whether or not every token has been consumed.
+    pub fn is_eof(&self) -> bool {
+        self.idx >= self.tokens.len()
+    }
+
+    /// Returns the next token, without consuming it.
+    pub fn peek(&self) -> Option<&'a TokenTree> {
+        self.lookahead(0)
+    }
+
+    /// Returns the token `n` tokens after the next one, without consuming
+    /// anything, so `lookahead(0)` is the next token.
+    pub fn lookahead(&self, n: usize) -> Option<&'a TokenTree> {
+        self.tokens.get(self.idx + n)
+    }
+
+    /// Returns whether or not the next token is the punctuator `value`.
+    pub fn is_punct(&self, value: char) -> bool {
+        matches!(self.peek(), Some(TokenTree::Punct(punct)) if punct.value == value)
+    }
+
+    /// Returns whether or not the next token is the keyword `value`.
+    pub fn is_keyword(&self, value: &str) -> bool {
+        matches!(self.peek(), Some(TokenTree::Keyword(keyword)) if keyword.value == value)
+    }
+
+    /// Consumes the next token if it is the punctuator `value`, and returns
+    /// whether or not it was.
+    pub fn eat_punct(&mut self, value: char) -> bool {
+        let is_punct = self.is_punct(value);
+        if is_punct {
+            self.idx += 1;
+        }
+        is_punct
+    }
+
+    /// Consumes the next token if it is the keyword `value`, and returns
+    /// whether or not it was.
+    pub fn eat_keyword(&mut self, value: &str) -> bool {
+        let is_keyword = self.is_keyword(value);
+        if is_keyword {
+            self.idx += 1;
+        }
+        is_keyword
+    }
+
+    /// Consumes the punctuator `value`, or returns an error if the next token
+    /// is something else.
+    pub fn expect_punct(&mut self, value: char) -> Result<&'a Punct, Diagnostic<()>> {
+        match self.peek() {
+            Some(TokenTree::Punct(punct)) if punct.value == value => {
+                self.idx += 1;
+                Ok(punct)
+            }
+            _ => Err(self.expected(&format!("`{}`", value))),
+        }
+    }
+
+    /// Consumes the keyword `value`, or returns an error if the next token is
+    /// something else.
+    pub fn expect_keyword(&mut self, value: &str) -> Result<&'a Keyword, Diagnostic<()>> {
+        match self.peek() {
+            Some(TokenTree::Keyword(keyword)) if keyword.value == value => {
+                self.idx += 1;
+                Ok(keyword)
+            }
+            _ => Err(self.expected(&format!("`{}`", value))),
+        }
+    }
+
+    /// Consumes an identifier, or returns an error if the next token is
+    /// something else.
+    pub fn expect_iden(&mut self) -> Result<&'a Iden, Diagnostic<()>> {
+        match self.peek() {
+            Some(TokenTree::Iden(iden)) => {
+                self.idx += 1;
+                Ok(iden)
+            }
+            _ => Err(self.expected("an identifier")),
+        }
+    }
+
+    /// Consumes a group with the given `delimiter`, or returns an error if the
+    /// next token is something else.
+    pub fn expect_group(&mut self, delimiter: Delimiter) -> Result<&'a Group, Diagnostic<()>> {
+        match self.peek() {
+            Some(TokenTree::Group(group)) if group.delimiter == delimiter => {
+                self.idx += 1;
+                Ok(group)
+            }
+            _ => Err(self.expected(&format!("`{}`", delimiter.open()))),
+        }
+    }
+
+    /// Returns the error for finding the next token where `expected` was
+    /// expected, such as "expected `;`, found `}`".  `expected` is a
+    /// description, such as "an expression" or "`;`".
+    ///
+    /// The found token is shown as its source, or as just its opening
+    /// delimiter for groups.  Past the last token, the closing delimiter of the
+    /// group is found, or the end of the input at the top level.
+    pub fn expected(&self, expected: &str) -> Diagnostic<()> {
+        let (found, loc) = self.found();
+
+        Diagnostic::error()
+            .with_code("E0020")
+            .with_labels(vec![
+                Label::primary((), loc).with_message(format!("expected {}", expected))
+            ])
+            .with_message(format!("expected {}, found {}", expected, found))
+    }
+
+    /// Returns a description of the next token and its location, for
+    /// [`TokenCursor::expected`].
+    fn found(&self) -> (String, Loc) {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => {
+                return match self.group {
+                    Some(group) => (
+                        format!("`{}`", group.delimiter.close()),
+                        self.end()..group.loc.end,
+                    ),
+                    None => ("end of input".to_string(), self.end()..self.end()),
+                };
+            }
+        };
+
+        let loc = token.loc().clone();
+        match token {
+            TokenTree::Group(group) => (
+                format!("`{}`", group.delimiter.open()),
+                loc.start..loc.start + 1,
+            ),
+            TokenTree::Error(_) => ("an invalid token".to_string(), loc),
+            _ => {
+                let mut source = String::new();
+                write_token(token, &mut source);
+                (format!("`{}`", source), loc)
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for TokenCursor<'a> {
+    type Item = &'a TokenTree;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.peek()?;
+        self.idx += 1;
+        Some(token)
+    }
+}
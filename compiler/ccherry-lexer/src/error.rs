@@ -0,0 +1,524 @@
+//! The errors found while lexing, and how they are reported.
+
+use std::fmt;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use crate::confusable;
+use crate::relex::shift_loc;
+use crate::token::{IntKind, Loc};
+
+/// An error found while lexing.
+///
+/// Each error keeps the information needed to handle it programmatically,
+/// such as its location and what was expected, and is turned into a
+/// [`Diagnostic`] with [`LexError::to_diagnostic`] when it is reported.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexError {
+    /// A block comment which never ends.  `loc` runs from the `/*` to the end
+    /// of the file.
+    UnclosedBlockComment { loc: Loc },
+
+    /// A carriage return which isn't followed by a line feed.  This is only a
+    /// warning, and the carriage return is treated as a line break.
+    StrayCarriageReturn { loc: Loc },
+
+    /// A character which doesn't start any token.
+    InvalidCharacter { loc: Loc },
+
+    /// A `0x`, `0o` or `0b` prefix without any digits after it.
+    MissingDigits { loc: Loc, kind: IntKind },
+
+    /// A character which isn't a digit of numbers of the given `kind`, at
+    /// `loc`, in the middle of a number with a prefix, such as the `8` in
+    /// `0o78`.
+    InvalidDigit { loc: Loc, kind: IntKind },
+
+    /// An integer literal which is too large for a `u128`.
+    IntOverflow { loc: Loc, kind: IntKind },
+
+    /// A float literal which is too large for an `f64`.
+    FloatOverflow { loc: Loc },
+
+    /// An exponent right after the `.` of a float, such as `1.e5`.
+    ExponentAfterDot { loc: Loc },
+
+    /// An exponent without any digits at the end of the file, such as `1e`.
+    /// `sign` is whether or not a `+` or `-` could still come before the
+    /// digits.
+    MissingExponent { loc: Loc, sign: bool },
+
+    /// An exponent followed by something other than digits, such as `1ex`.
+    InvalidExponent { loc: Loc },
+
+    /// A digit separator which isn't between two digits, such as in `1__0`.
+    /// `reason` says what is wrong with it.
+    MisplacedDigitSeparator { loc: Loc, reason: &'static str },
+
+    /// A string which never closes with `terminator`, such as `"` or `"#`.
+    /// For a `"..."` string which runs past the end of its first line,
+    /// `line_end` is the index of that line's end.
+    UnterminatedString {
+        loc: Loc,
+        terminator: String,
+        raw: bool,
+        line_end: Option<usize>,
+    },
+
+    /// One or more escapes in a string or character literal which couldn't
+    /// be decoded.  Every invalid escape in the literal is reported at once.
+    InvalidEscape { escapes: Vec<EscapeError> },
+
+    /// A character literal which never closes.
+    UnterminatedChar { loc: Loc },
+
+    /// A character literal without a character, `''`.
+    EmptyChar { loc: Loc },
+
+    /// A character literal with more than one character, such as `'ab'`.
+    MultipleChars { loc: Loc },
+
+    /// A group which never closes with `close`.  `loc` runs from the opening
+    /// delimiter to the end of the file.
+    UnclosedGroup { loc: Loc, close: char },
+
+    /// A closing delimiter, at `loc`, which doesn't match the group it closes,
+    /// such as the `]` in `( foo ]`.  `open` is the location of the group's
+    /// opening delimiter.
+    MismatchedDelimiter {
+        loc: Loc,
+        open: Loc,
+        expected: char,
+        found: char,
+    },
+
+    /// A group, whose opening delimiter is at `loc`, which is nested more than
+    /// `max_depth` groups and interpolations deep (see
+    /// [`LexerConfig::with_max_depth`](crate::LexerConfig::with_max_depth)).
+    GroupTooDeep { loc: Loc, max_depth: usize },
+
+    /// An identifier which looks like the ASCII identifier `skeleton`, but
+    /// contains letters from other scripts, such as the Cyrillic `а` in
+    /// `pаth`.  This is only a warning, which can be disabled with
+    /// [`LexerConfig::with_confusable_warnings`](crate::LexerConfig::with_confusable_warnings).
+    ConfusableIdentifier {
+        loc: Loc,
+        value: String,
+        skeleton: String,
+    },
+
+    /// An identifier which mixes letters from different scripts, such as Latin
+    /// and Greek.  `scripts` are the names of the scripts, in the order they
+    /// first appear in.  This is only a warning, like
+    /// [`LexError::ConfusableIdentifier`].
+    MixedScriptIdentifier {
+        loc: Loc,
+        scripts: Vec<&'static str>,
+    },
+}
+
+/// An escape in a string or character literal which couldn't be decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscapeError {
+    /// The location of the invalid escape, including the `\`.
+    pub loc: Loc,
+
+    /// What is wrong with the escape.
+    pub message: String,
+
+    /// Whether or not this is an invalid `\u{...}` escape.
+    pub unicode: bool,
+
+    /// The valid escape which means what was meant, if there is one.
+    pub suggestion: Option<EscapeSuggestion>,
+}
+
+/// A valid escape suggested in place of an invalid one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscapeSuggestion {
+    /// The location of the text to replace, which may be longer than the
+    /// invalid escape, such as the digits after `\u` in `\u00E9`.
+    pub loc: Loc,
+
+    /// The text to replace.
+    pub escape: String,
+
+    /// The escape to replace it with, including the `\`.
+    pub replacement: String,
+}
+
+impl LexError {
+    /// Moves every location in this error by `delta` bytes, such as for a lexer
+    /// with an offset (see [`Lexer::with_offset`](crate::Lexer::with_offset)).
+    pub(crate) fn shift(&mut self, delta: isize) {
+        match self {
+            LexError::UnclosedBlockComment { loc }
+            | LexError::StrayCarriageReturn { loc }
+            | LexError::InvalidCharacter { loc }
+            | LexError::MissingDigits { loc, .. }
+            | LexError::InvalidDigit { loc, .. }
+            | LexError::IntOverflow { loc, .. }
+            | LexError::FloatOverflow { loc }
+            | LexError::ExponentAfterDot { loc }
+            | LexError::MissingExponent { loc, .. }
+            | LexError::InvalidExponent { loc }
+            | LexError::MisplacedDigitSeparator { loc, .. }
+            | LexError::UnterminatedChar { loc }
+            | LexError::EmptyChar { loc }
+            | LexError::MultipleChars { loc }
+            | LexError::UnclosedGroup { loc, .. }
+            | LexError::GroupTooDeep { loc, .. }
+            | LexError::ConfusableIdentifier { loc, .. }
+            | LexError::MixedScriptIdentifier { loc, .. } => shift_loc(loc, delta),
+            LexError::UnterminatedString { loc, line_end, .. } => {
+                shift_loc(loc, delta);
+                if let Some(line_end) = line_end {
+                    *line_end = (*line_end as isize + delta) as usize;
+                }
+            }
+            LexError::InvalidEscape { escapes } => {
+                for escape in escapes {
+                    shift_loc(&mut escape.loc, delta);
+                    if let Some(suggestion) = &mut escape.suggestion {
+                        shift_loc(&mut suggestion.loc, delta);
+                    }
+                }
+            }
+            LexError::MismatchedDelimiter { loc, open, .. } => {
+                shift_loc(loc, delta);
+                shift_loc(open, delta);
+            }
+        }
+    }
+
+    /// Returns the error code of this error, such as `E0001`, if it has one.
+    pub fn code(&self) -> Option<&'static str> {
+        Some(match self {
+            LexError::UnclosedBlockComment { .. } => "E0001",
+            LexError::StrayCarriageReturn { .. }
+            | LexError::ConfusableIdentifier { .. }
+            | LexError::MixedScriptIdentifier { .. } => return None,
+            LexError::InvalidCharacter { .. } => "E0013",
+            LexError::MissingDigits { .. } => "E0008",
+            LexError::IntOverflow {
+                kind: IntKind::Decimal,
+                ..
+            } => "E0007",
+            LexError::IntOverflow { .. } => "E0009",
+            LexError::FloatOverflow { .. } => "E0006",
+            LexError::ExponentAfterDot { .. } => "E0002",
+            LexError::MissingExponent { .. } => "E0004",
+            LexError::InvalidExponent { .. } => "E0005",
+            LexError::MisplacedDigitSeparator { .. } => "E0018",
+            LexError::UnterminatedString { .. } => "E0010",
+            LexError::InvalidEscape { escapes } if escapes[0].unicode => "E0012",
+            LexError::InvalidEscape { .. } => "E0011",
+            LexError::UnterminatedChar { .. } => "E0017",
+            LexError::EmptyChar { .. } => "E0015",
+            LexError::MultipleChars { .. } => "E0016",
+            LexError::UnclosedGroup { .. } => "E0014",
+            LexError::MismatchedDelimiter { .. } => "E0019",
+            LexError::GroupTooDeep { .. } => "E0021",
+            LexError::InvalidDigit { .. } => "E0022",
+        })
+    }
+
+    /// Returns whether or not this error is only a warning, which doesn't
+    /// stop the source from being compiled.
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            LexError::StrayCarriageReturn { .. }
+                | LexError::ConfusableIdentifier { .. }
+                | LexError::MixedScriptIdentifier { .. }
+        )
+    }
+
+    /// Returns the diagnostic reporting this error, with its labels in the
+    /// file `file_id`.
+    pub fn to_diagnostic<FileId: Clone>(&self, file_id: FileId) -> Diagnostic<FileId> {
+        let primary = |loc: &Loc, message: &str| {
+            Label::primary(file_id.clone(), loc.clone()).with_message(message)
+        };
+        let secondary =
+            |loc: Loc, message: &str| Label::secondary(file_id.clone(), loc).with_message(message);
+
+        let (message, labels, notes) = match self {
+            LexError::UnclosedBlockComment { loc } => (
+                "block comment never ends".to_string(),
+                vec![
+                    primary(&(loc.end..loc.end), "expected block comment to end here"),
+                    secondary(loc.start..loc.start + 2, "help: block comment started here"),
+                ],
+                vec![],
+            ),
+            LexError::StrayCarriageReturn { loc } => (
+                "stray carriage return".to_string(),
+                vec![primary(loc, "carriage return without a line feed")],
+                vec![],
+            ),
+            LexError::InvalidCharacter { loc } => (
+                "invalid character".to_string(),
+                vec![primary(loc, "invalid character here")],
+                vec![],
+            ),
+            LexError::MissingDigits { loc, kind } => (
+                format!("no {} number after `{}`", radix(kind), prefix(kind)),
+                vec![primary(
+                    loc,
+                    &format!("expected {} {} number here", article(kind), radix(kind)),
+                )],
+                vec![],
+            ),
+            LexError::InvalidDigit { loc, kind } => (
+                format!("invalid digit for {} {} number", article(kind), radix(kind)),
+                vec![primary(
+                    loc,
+                    &format!("invalid digit for base {}", base(kind)),
+                )],
+                vec![],
+            ),
+            LexError::IntOverflow {
+                loc,
+                kind: IntKind::Decimal,
+            } => (
+                "integer is too large".to_string(),
+                vec![primary(loc, "integer number is too large")],
+                vec![],
+            ),
+            LexError::IntOverflow { loc, kind } => {
+                let message = format!("{} number is too large", radix(kind));
+                (
+                    format!("{}.", message),
+                    vec![primary(loc, &message)],
+                    vec![],
+                )
+            }
+            LexError::FloatOverflow { loc } => (
+                "float is too large".to_string(),
+                vec![primary(loc, "float number is too large")],
+                vec![],
+            ),
+            LexError::ExponentAfterDot { loc } => (
+                "exponent after `.`".to_string(),
+                vec![
+                    primary(loc, "exponent cannot immediately follow `.`"),
+                    secondary(
+                        loc.end - 2..loc.end - 2,
+                        "try inserting a `0` after this `.`",
+                    ),
+                ],
+                vec![],
+            ),
+            LexError::MissingExponent { loc, sign } => (
+                "expected an exponent value".to_string(),
+                vec![primary(
+                    loc,
+                    if *sign {
+                        "expected an exponent value or `+`/`-`"
+                    } else {
+                        "expected an exponent value"
+                    },
+                )],
+                vec![],
+            ),
+            LexError::InvalidExponent { loc } => (
+                "expected a valid exponent value".to_string(),
+                vec![primary(loc, "expected a valid exponent value (a number)")],
+                vec![],
+            ),
+            LexError::MisplacedDigitSeparator { loc, reason } => (
+                "misplaced digit separator".to_string(),
+                vec![primary(loc, reason)],
+                vec![],
+            ),
+            LexError::UnterminatedString {
+                loc,
+                terminator,
+                raw,
+                line_end,
+            } => {
+                let kind = if *raw { "raw string" } else { "string" };
+                let mut labels = vec![primary(
+                    loc,
+                    &format!("{} never closes with `{}`", kind, terminator),
+                )];
+                let mut notes = vec![];
+
+                if let Some(line_end) = *line_end {
+                    labels.push(secondary(
+                        line_end..line_end,
+                        "the string continues past the end of this line",
+                    ));
+                    notes.push("strings which span multiple lines should use `\"\"\"`".to_string());
+                }
+
+                ("string never closes".to_string(), labels, notes)
+            }
+            LexError::InvalidEscape { escapes } => {
+                let message = if escapes[0].unicode {
+                    "invalid unicode escape in string"
+                } else {
+                    "invalid string escape"
+                };
+                let labels = escapes
+                    .iter()
+                    .map(|escape| primary(&escape.loc, &escape.message))
+                    .collect();
+                let notes = escapes
+                    .iter()
+                    .filter_map(|escape| escape.suggestion.as_ref())
+                    .map(|suggestion| {
+                        format!(
+                            "help: replace `{}` with `{}`",
+                            suggestion.escape, suggestion.replacement
+                        )
+                    })
+                    .collect();
+
+                (message.to_string(), labels, notes)
+            }
+            LexError::UnterminatedChar { loc } => (
+                "character literal never closes".to_string(),
+                vec![primary(loc, "character literal never closes")],
+                vec![],
+            ),
+            LexError::EmptyChar { loc } => (
+                "empty character literal".to_string(),
+                vec![primary(loc, "expected a character here")],
+                vec![],
+            ),
+            LexError::MultipleChars { loc } => (
+                "character literal contains more than one character".to_string(),
+                vec![primary(
+                    loc,
+                    "character literals may only contain one character",
+                )],
+                vec![],
+            ),
+            LexError::UnclosedGroup { loc, close } => (
+                "group never ends".to_string(),
+                vec![
+                    primary(loc, &format!("group never closes with '{}'", close)),
+                    secondary(loc.start..loc.start, "group starts here"),
+                ],
+                vec![],
+            ),
+            LexError::MismatchedDelimiter {
+                loc,
+                open,
+                expected,
+                found,
+            } => (
+                "mismatched closing delimiter".to_string(),
+                vec![
+                    primary(loc, &format!("expected `{}`, found `{}`", expected, found)),
+                    secondary(open.clone(), "group starts here"),
+                ],
+                vec![],
+            ),
+            LexError::GroupTooDeep { loc, max_depth } => (
+                "groups are nested too deeply".to_string(),
+                vec![primary(
+                    loc,
+                    &format!("this group is more than {} groups deep", max_depth),
+                )],
+                vec!["deeply nested code should be split into smaller parts".to_string()],
+            ),
+            LexError::ConfusableIdentifier {
+                loc,
+                value,
+                skeleton,
+            } => (
+                format!("identifier `{}` looks like `{}`", value, skeleton),
+                vec![primary(
+                    loc,
+                    "this identifier contains letters which look like ASCII letters",
+                )],
+                confusable::lookalikes(value)
+                    .into_iter()
+                    .map(|(char, lookalike)| {
+                        format!(
+                            "`{}` (U+{:04X}) looks like `{}`",
+                            char, char as u32, lookalike
+                        )
+                    })
+                    .collect(),
+            ),
+            LexError::MixedScriptIdentifier { loc, scripts } => {
+                let scripts = match scripts.split_last() {
+                    Some((last, [])) => last.to_string(),
+                    Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+                    None => "different".to_string(),
+                };
+
+                (
+                    format!("identifier mixes {} letters", scripts),
+                    vec![primary(loc, "letters from different scripts")],
+                    vec![],
+                )
+            }
+        };
+
+        let diagnostic = if self.is_warning() {
+            Diagnostic::warning()
+        } else {
+            Diagnostic::error()
+        };
+        let diagnostic = match self.code() {
+            Some(code) => diagnostic.with_code(code),
+            None => diagnostic,
+        };
+
+        diagnostic
+            .with_message(message)
+            .with_labels(labels)
+            .with_notes(notes)
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_diagnostic(()).message)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Returns the name of the base of numbers of the given `kind`.
+fn radix(kind: &IntKind) -> &'static str {
+    match kind {
+        IntKind::Decimal => "decimal",
+        IntKind::Hexadecimal => "hexadecimal",
+        IntKind::Octal => "octal",
+        IntKind::Binary => "binary",
+    }
+}
+
+/// Returns the base of numbers of the given `kind`, such as `16`.
+fn base(kind: &IntKind) -> u32 {
+    match kind {
+        IntKind::Decimal => 10,
+        IntKind::Hexadecimal => 16,
+        IntKind::Octal => 8,
+        IntKind::Binary => 2,
+    }
+}
+
+/// Returns the article before [`radix`], "a" or "an".
+fn article(kind: &IntKind) -> &'static str {
+    match kind {
+        IntKind::Octal => "an",
+        _ => "a",
+    }
+}
+
+/// Returns the prefix of numbers of the given `kind`, such as `0x`.
+fn prefix(kind: &IntKind) -> &'static str {
+    match kind {
+        IntKind::Decimal => "",
+        IntKind::Hexadecimal => "0x",
+        IntKind::Octal => "0o",
+        IntKind::Binary => "0b",
+    }
+}
@@ -0,0 +1,222 @@
+//! Decoding of the escapes in string and character literals.
+
+/// An escape which couldn't be decoded.
+pub(crate) struct InvalidEscape {
+    /// The length in bytes of the invalid escape, after the `\`.
+    pub len: usize,
+
+    /// Whether or not this is an invalid `\u{...}` escape.
+    pub unicode: bool,
+
+    /// What is wrong with the escape.
+    pub message: String,
+
+    /// The valid escape which means what was meant, if there is one.
+    pub suggestion: Option<Suggestion>,
+}
+
+/// A valid escape suggested in place of an invalid one.
+pub(crate) struct Suggestion {
+    /// The length in bytes of the text to replace, after the `\`.  This may be
+    /// longer than the invalid escape, such as the digits after `\u` in
+    /// `\u00E9`.
+    pub len: usize,
+
+    /// The escape to replace it with, including the `\`.
+    pub replacement: String,
+}
+
+/// Decodes the escape at the start of `rest`, which comes right after a `\`.
+/// Returns the character it stands for, or `None` for a line continuation,
+/// along with the length of the escape in bytes, after the `\`.
+///
+/// The escapes are `\n`, `\r`, `\t`, `\0`, `\\`, `\'`, `\"` and `\$`, `\x` with
+/// two hexadecimal digits up to `\x7F`, `\u{...}` with one to six hexadecimal
+/// digits, and `\` at the end of a line, which skips the line break and the
+/// whitespace at the start of the next line.
+pub(crate) fn unescape_one(rest: &str) -> Result<(Option<char>, usize), InvalidEscape> {
+    let char = match rest.chars().next() {
+        Some(char) => char,
+        None => {
+            return Err(InvalidEscape {
+                len: 0,
+                unicode: false,
+                message: "expected an escape after this `\\`".to_string(),
+                suggestion: None,
+            })
+        }
+    };
+
+    let value = match char {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        '\\' | '\'' | '"' | '$' => char,
+        '\n' | '\r' => return Ok((None, rest.len() - rest.trim_start().len())),
+        'x' => return unescape_ascii(rest),
+        'u' => return unescape_unicode(rest),
+        _ => {
+            return Err(InvalidEscape {
+                len: char.len_utf8(),
+                unicode: false,
+                message: format!("unknown escape `\\{}`", char),
+                suggestion: suggest_unknown(rest, char),
+            })
+        }
+    };
+
+    Ok((Some(value), 1))
+}
+
+/// Decodes an `\x7F` escape, where `rest` starts with the `x`.
+fn unescape_ascii(rest: &str) -> Result<(Option<char>, usize), InvalidEscape> {
+    let digits = hex_digits(&rest[1..], 2);
+
+    if digits != 2 {
+        // a single digit is padded, so `\x7` becomes `\x07`.
+        return Err(InvalidEscape {
+            len: 1 + digits,
+            unicode: false,
+            message: "expected two hexadecimal digits after `\\x`".to_string(),
+            suggestion: (digits == 1).then(|| Suggestion {
+                len: 2,
+                replacement: format!("\\x0{}", &rest[1..2]),
+            }),
+        });
+    }
+
+    match u8::from_str_radix(&rest[1..3], 16) {
+        Ok(value) if value <= 0x7F => Ok((Some(value as char), 3)),
+        _ => Err(InvalidEscape {
+            len: 3,
+            unicode: false,
+            message: "`\\x` escapes may be at most `\\x7F`, use `\\u{...}` for other characters"
+                .to_string(),
+            suggestion: suggest_unicode(3, &rest[1..3]),
+        }),
+    }
+}
+
+/// Decodes a `\u{1F600}` escape, where `rest` starts with the `u`.
+fn unescape_unicode(rest: &str) -> Result<(Option<char>, usize), InvalidEscape> {
+    let invalid = |len, message: &str| InvalidEscape {
+        len,
+        unicode: true,
+        message: message.to_string(),
+        suggestion: None,
+    };
+
+    if !rest[1..].starts_with('{') {
+        // `\u00E9`, as written in many other languages.
+        let digits = hex_digits(&rest[1..], 4);
+        return Err(InvalidEscape {
+            suggestion: (digits == 4)
+                .then(|| suggest_unicode(5, &rest[1..5]))
+                .flatten(),
+            ..invalid(1, "expected `{` after `\\u`")
+        });
+    }
+
+    let digits = rest[2..]
+        .find(|char: char| !char.is_ascii_hexdigit())
+        .unwrap_or(rest.len() - 2);
+    let len = 2 + digits;
+
+    if !rest[len..].starts_with('}') {
+        return Err(InvalidEscape {
+            suggestion: suggest_unicode(len, &rest[2..len]),
+            ..invalid(len, "expected `}` after the digits of `\\u{...}`")
+        });
+    }
+
+    if digits == 0 || digits > 6 {
+        // leading zeros don't count, so `\u{0000041}` becomes `\u{41}`.
+        return Err(InvalidEscape {
+            suggestion: suggest_unicode(len + 1, &rest[2..len]),
+            ..invalid(len + 1, "unicode escapes must have between 1 and 6 digits")
+        });
+    }
+
+    match u32::from_str_radix(&rest[2..len], 16)
+        .ok()
+        .and_then(char::from_u32)
+    {
+        Some(value) => Ok((Some(value), len + 1)),
+        None => Err(invalid(len + 1, "not a valid unicode character")),
+    }
+}
+
+/// Returns the number of hexadecimal digits at the start of `text`, up to
+/// `max`.
+fn hex_digits(text: &str, max: usize) -> usize {
+    text.chars()
+        .take(max)
+        .take_while(char::is_ascii_hexdigit)
+        .count()
+}
+
+/// Suggests a `\u{...}` escape for the hexadecimal `digits`, replacing `len`
+/// bytes after the `\`, if they are a valid unicode character.
+fn suggest_unicode(len: usize, digits: &str) -> Option<Suggestion> {
+    let trimmed = digits.trim_start_matches('0');
+    let trimmed = if trimmed.is_empty() { "0" } else { trimmed };
+
+    if digits.is_empty() || trimmed.len() > 6 {
+        return None;
+    }
+
+    u32::from_str_radix(trimmed, 16)
+        .ok()
+        .and_then(char::from_u32)?;
+
+    Some(Suggestion {
+        len,
+        replacement: format!("\\u{{{}}}", if digits.len() > 6 { trimmed } else { digits }),
+    })
+}
+
+/// Suggests a valid escape for the unknown escape `char`, at the start of
+/// `rest`, which means what the escape means in the language it was most
+/// likely written for.  The first of these which applies is suggested:
+///
+/// - C's `\a`, `\b`, `\e`, `\f` and `\v` become `\x07`, `\x08`, `\x1B`,
+///   `\x0C` and `\x0B`, and `\U0001F600` becomes `\u{1F600}`.
+/// - C's octal escapes, such as `\7` or `\101`, become `\x07` or `\x41`, or
+///   `\u{...}` above `\x7F`.
+/// - `\N`, `\R` and `\T` become `\n`, `\r` and `\t`, unless the `\N` is
+///   Python's `\N{...}`, whose character can't be known without its name.
+/// - anything else, such as the `\d` in a regular expression, becomes `\\d`,
+///   which is what an unknown escape means in Python.
+fn suggest_unknown(rest: &str, char: char) -> Option<Suggestion> {
+    let suggestion = |len, replacement: String| Some(Suggestion { len, replacement });
+
+    match char {
+        'a' => suggestion(1, "\\x07".to_string()),
+        'b' => suggestion(1, "\\x08".to_string()),
+        'e' => suggestion(1, "\\x1B".to_string()),
+        'f' => suggestion(1, "\\x0C".to_string()),
+        'v' => suggestion(1, "\\x0B".to_string()),
+        'U' if hex_digits(&rest[1..], 8) >= 4 => {
+            let digits = hex_digits(&rest[1..], 8);
+            suggest_unicode(1 + digits, &rest[1..1 + digits])
+        }
+        '1'..='7' => {
+            let digits = rest
+                .chars()
+                .take(3)
+                .take_while(|char| matches!(char, '0'..='7'))
+                .count();
+            let value = u32::from_str_radix(&rest[..digits], 8).ok()?;
+
+            if value <= 0x7F {
+                suggestion(digits, format!("\\x{:02X}", value))
+            } else {
+                suggestion(digits, format!("\\u{{{:X}}}", value))
+            }
+        }
+        'N' if rest[1..].starts_with('{') => None,
+        'N' | 'R' | 'T' => suggestion(1, format!("\\{}", char.to_ascii_lowercase())),
+        char => suggestion(char.len_utf8(), format!("\\\\{}", char)),
+    }
+}
@@ -1,62 +1,569 @@
+mod arena;
+mod buffer;
+mod cache;
+mod comment_table;
+mod compact;
+mod config;
+mod confusable;
+mod cursor;
+mod error;
+mod escape;
+mod lint;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod printer;
+#[cfg(feature = "proc-macro2")]
+mod proc_macro;
+mod relex;
+mod source;
+mod strip;
 mod token;
 
+pub use arena::{TokenArena, TokenId};
+pub use buffer::{TokenBuffer, TokenOrigin};
+pub use cache::{decode_tokens, encode_tokens, TOKEN_CACHE_VERSION};
+pub use comment_table::CommentTable;
+pub use compact::Compact;
+pub use config::LexerConfig;
+pub use cursor::TokenCursor;
+pub use error::{EscapeError, EscapeSuggestion, LexError};
+pub use lint::{ambiguous_operators, apply_fixes, Fix, Lint};
+#[cfg(feature = "parallel")]
+pub use parallel::{lex_files, lex_files_with_config, LexResult};
+pub use printer::{DisplaySource, ToSource};
+pub use relex::TextEdit;
+pub use source::{TokenReplay, TokenSource};
+#[doc(hidden)]
+pub use strip::stripped_tokens;
 pub use token::{
-    Comment, CommentKind, Float, Group, Iden, Int, IntKind, Loc, Punct, Skipped, Spacing, Str,
-    TokenTree,
+    Char, Comment, CommentKind, Delimiter, Error, Float, FloatKind, Group, Iden, Int, IntKind,
+    Keyword, Loc, Punct, Skipped, Spacing, Str, StrKind, StrSegment, TokenKind, TokenTree, Trivia,
+    TriviaKind, TriviaToken,
 };
 
-use codespan_reporting::diagnostic::{Diagnostic, Label};
-use snailquote::{unescape, UnescapeError};
+use ccherry_span::FileId;
+use codespan_reporting::diagnostic::Diagnostic;
+use relex::shift_loc;
+use unicode_normalization::UnicodeNormalization;
 use unicode_xid::UnicodeXID;
 
 /// Cherry's lexer.
 ///
-/// At this phase in the parser, keywords are interpreted simply as identifiers.
-/// This means that, in theory, this lexer can be used for any programming
-/// language which uses usual characters and strings.
-pub struct Lexer {
-    /// The characters to tokenize.  This originates from the source string,
-    /// provided at the creation of this lexer.
-    chars: Vec<char>,
-
-    /// The index of the current token, in the `chars` list.  This should be the
-    /// index of the first character of the next token.
+/// At this phase in the parser, keywords are interpreted simply as identifiers,
+/// unless a keyword table is provided with [`LexerConfig::with_keywords`].
+/// This means that this lexer can be used for other programming languages,
+/// whose punctuators, comments and quotes can be set with a [`LexerConfig`].
+///
+/// All locations produced by the lexer are byte ranges into the source string,
+/// which is what `codespan-reporting` expects.
+pub struct Lexer<'a> {
+    /// The source string to tokenize, provided at the creation of this lexer.
+    source: &'a str,
+
+    /// The byte index of the current token in `source`.  This should be the
+    /// index of the first character of the next token, and is always on a
+    /// character boundary.
     idx: usize,
 
     /// List of comments.  The comments in this list will be added onto the next
     /// token found, and then this list will be cleared.
     comments: Vec<Comment>,
+
+    /// The comments after the last token on the same line, which will be added
+    /// onto that token as its trailing comments.
+    trailing_comments: Vec<Comment>,
+
+    /// Whether or not the lexer is in recovery mode.  In recovery mode, errors
+    /// are recorded in `errors` and replaced with [`TokenTree::Error`]
+    /// tokens, rather than being returned.
+    recovery: bool,
+
+    /// The errors and warnings recorded so far.
+    errors: Vec<LexError>,
+
+    /// The fixes suggested by the errors found so far.
+    fixes: Vec<Fix>,
+
+    /// The comment table, if comments are collected into a side table rather
+    /// than attached to tokens.
+    comment_table: Option<CommentTable>,
+
+    /// The configuration of this lexer.
+    config: LexerConfig,
+
+    /// The location of the shebang line, if the source starts with one.
+    shebang: Option<Loc>,
+
+    /// Whether or not the lexer is in lossless mode, where every token records
+    /// the exact source around it in its [`Trivia`].
+    lossless: bool,
+
+    /// The byte index of the end of the last token, or the start of the
+    /// current group, where the leading trivia of the next token starts.  This
+    /// is only used in lossless mode.
+    trivia_start: usize,
+
+    /// The closing delimiters of the groups around the current token, with the
+    /// innermost group last.  Interpolations start with an empty list, since a
+    /// delimiter in an interpolation can't close a group outside the string.
+    closers: Vec<char>,
+
+    /// The next token, if it has been lexed by [`TokenSource::peek_token`]
+    /// but not consumed yet.
+    peeked: Option<TokenTree>,
+
+    /// The spacing of the last token returned, for
+    /// [`TokenSource::last_spacing`].
+    last_spacing: Option<Spacing>,
+
+    /// The number of groups and interpolations around the current token.
+    depth: usize,
+
+    /// The whitespace, line breaks and comments skipped but not returned yet,
+    /// as [`TokenTree::Trivia`] tokens, if the lexer is in trivia mode.
+    trivia_tokens: Option<Vec<TokenTree>>,
+
+    /// The next top-level token, if it has been lexed but there is trivia
+    /// before it to return first.
+    queued: Option<TokenTree>,
+
+    /// The byte index of `source` in the source it is part of, which is added
+    /// to every location produced by this lexer.
+    offset: usize,
+
+    /// The comments after the last token of the source, once the lexer has
+    /// reached its end.
+    eof_comments: Vec<Comment>,
+
+    /// The file the source is in, which the diagnostics of
+    /// [`Lexer::tokenize_file`] have their labels in.
+    file: FileId,
+}
+
+/// A group whose tokens are being tokenized by [`Lexer::tokenize_tree`].
+struct OpenGroup {
+    /// The byte index of the opening delimiter.
+    start_index: usize,
+
+    /// The byte index where the leading trivia of the group starts.
+    trivia_start: usize,
+
+    /// The delimiter of the group.
+    delimiter: Delimiter,
+
+    /// The tokens found in the group so far.
+    tokens: Vec<TokenTree>,
+
+    /// The comments before the group.
+    comments: Box<[Comment]>,
+
+    /// The number of trivia tokens which were skipped before the group, and
+    /// aren't part of it.
+    trivia_len: usize,
+}
+
+/// What [`Lexer::tokenize_tree`] found.
+enum Lexed {
+    /// A top-level token.
+    Token(TokenTree),
+
+    /// The outermost group it was given, after it closed.
+    Group(OpenGroup),
+
+    /// The end of the source, outside of any group.
+    Eof,
+}
+
+/// A saved position of a [`Lexer`], created with [`Lexer::checkpoint`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexerState {
+    /// The byte index of the next token.
+    idx: usize,
+
+    /// The comments waiting to be attached to the next token.
+    comments: Vec<Comment>,
+
+    /// The number of errors recorded so far.
+    errors: usize,
+
+    /// The number of fixes suggested so far.
+    fixes: usize,
+
+    /// The number of comments in the comment table, if it is enabled.
+    comment_table: Option<usize>,
+
+    /// The byte index where the leading trivia of the next token starts.
+    trivia_start: usize,
+
+    /// The token which has been peeked, but not consumed yet.
+    peeked: Option<TokenTree>,
+
+    /// The spacing of the last token returned.
+    last_spacing: Option<Spacing>,
+
+    /// The trivia tokens skipped but not returned yet, in trivia mode.
+    trivia_tokens: Option<Vec<TokenTree>>,
+
+    /// The top-level token waiting for the trivia before it to be returned.
+    queued: Option<TokenTree>,
 }
 
-impl Lexer {
+impl<'a> Lexer<'a> {
     /// Initializes a new lexer from the provided `source` string.  This
     /// function initializes the lexer with a default index of `0`.
-    pub fn new(source: &str) -> Self {
+    pub fn new(source: &'a str) -> Self {
         Self {
-            chars: source.chars().collect(),
+            source,
             idx: 0,
             comments: vec![],
+            trailing_comments: vec![],
+            recovery: false,
+            errors: vec![],
+            fixes: vec![],
+            comment_table: None,
+            config: LexerConfig::new(),
+            shebang: None,
+            lossless: false,
+            trivia_start: 0,
+            closers: vec![],
+            peeked: None,
+            last_spacing: None,
+            depth: 0,
+            trivia_tokens: None,
+            queued: None,
+            offset: 0,
+            eof_comments: vec![],
+            file: FileId(0),
+        }
+    }
+
+    /// Returns this lexer after using the provided configuration.
+    pub fn with_config(mut self, config: LexerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns this lexer after enabling or disabling the comment table.
+    ///
+    /// With the comment table enabled, comments are not attached to tokens,
+    /// and are collected into a [`CommentTable`] instead, which can be
+    /// retrieved with [`Lexer::take_comment_table`].
+    pub fn with_comment_table(mut self, comment_table: bool) -> Self {
+        self.comment_table = comment_table.then(CommentTable::new);
+        self
+    }
+
+    /// Returns the comment table, if it is enabled.
+    pub fn comment_table(&self) -> Option<&CommentTable> {
+        self.comment_table.as_ref()
+    }
+
+    /// Returns the comment table, if it is enabled, and replaces it with an
+    /// empty one.
+    pub fn take_comment_table(&mut self) -> Option<CommentTable> {
+        self.comment_table.as_mut().map(std::mem::take)
+    }
+
+    /// Returns this lexer after enabling or disabling recovery mode.
+    ///
+    /// In recovery mode, the lexer never returns an `Err`.  Instead, the
+    /// error is recorded (see [`Lexer::errors`]), a
+    /// [`TokenTree::Error`] is returned in place of the invalid token, and the
+    /// lexer continues after it, so every error in a file can be reported at
+    /// once.
+    pub fn with_recovery(mut self, recovery: bool) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// Returns this lexer after enabling or disabling lossless mode.
+    ///
+    /// In lossless mode, every token records the exact whitespace, line breaks
+    /// and comments before it, and its own source, in its [`Trivia`].  This
+    /// way, [`ToSource`] gives back exactly the source which was lexed, byte
+    /// for byte, which is what formatters and refactoring tools need.  A source
+    /// with no tokens has nothing to record its trivia in.
+    pub fn with_lossless(mut self, lossless: bool) -> Self {
+        self.lossless = lossless;
+        self
+    }
+
+    /// Returns this lexer after enabling or disabling trivia mode.
+    ///
+    /// In trivia mode, the whitespace, line breaks and comments between tokens
+    /// are returned as [`TokenTree::Trivia`] tokens, in order with the other
+    /// tokens and in the groups they are in, for tools such as syntax
+    /// highlighters.  Comments are then only returned as trivia, rather than
+    /// also being attached to tokens, unless the comment table is enabled.
+    /// The spacing of the other tokens is the same as without trivia mode.
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia_tokens = trivia.then(Vec::new);
+        self
+    }
+
+    /// Returns this lexer after setting the byte index of its source in the
+    /// source it is part of, such as the body of a documentation test in the
+    /// file it was written in.  The offset is added to the location of every
+    /// token, comment, error and fix, so they line up with the outer source
+    /// in diagnostics.  The default is `0`.
+    ///
+    /// [`Lexer::relex`] doesn't support an offset, since it finds tokens by
+    /// their locations in the source of the lexer.
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Returns this lexer after setting the file its source is in, such as
+    /// its id in a `SourceMap`.  The diagnostics of [`Lexer::tokenize_file`]
+    /// have their labels in this file.  The default is `FileId(0)`.
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
+
+    /// Returns the file the source of this lexer is in (see
+    /// [`Lexer::with_file`]).
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    /// Lexes all of `source` in recovery mode, and returns every token along
+    /// with the comments after the last one (see [`Lexer::eof_comments`]) and
+    /// every error found, rather than stopping at the first one.
+    pub fn lex_all(source: &'a str) -> (Vec<TokenTree>, Vec<Comment>, Vec<LexError>) {
+        Lexer::new(source).lex_rest()
+    }
+
+    /// Lexes all of `source` like [`Lexer::lex_all`], as part of a larger
+    /// source which it starts at the byte index `offset` of, so every location
+    /// is in the larger source (see [`Lexer::with_offset`]).
+    pub fn lex_with_offset(
+        source: &'a str,
+        offset: usize,
+    ) -> (Vec<TokenTree>, Vec<Comment>, Vec<LexError>) {
+        Lexer::new(source).with_offset(offset).lex_rest()
+    }
+
+    /// Lexes the rest of the source in recovery mode, like
+    /// [`Lexer::lex_all`], but keeping the configuration of this lexer.
+    /// Returns every token along with the comments after the last one (see
+    /// [`Lexer::eof_comments`]) and the diagnostics of every error found, with
+    /// their labels in the file `file_id`.
+    pub fn tokenize_all<FileId: Clone>(
+        self,
+        file_id: FileId,
+    ) -> (Vec<TokenTree>, Vec<Comment>, Vec<Diagnostic<FileId>>) {
+        let (tokens, eof_comments, errors) = self.lex_rest();
+        let diagnostics = errors
+            .iter()
+            .map(|error| error.to_diagnostic(file_id.clone()))
+            .collect();
+
+        (tokens, eof_comments, diagnostics)
+    }
+
+    /// Lexes the rest of the source like [`Lexer::tokenize_all`], with the
+    /// labels of the diagnostics in the file of this lexer (see
+    /// [`Lexer::with_file`]), so diagnostics from several files can be
+    /// reported together.
+    pub fn tokenize_file(self) -> (Vec<TokenTree>, Vec<Comment>, Vec<Diagnostic<FileId>>) {
+        let file = self.file;
+        self.tokenize_all(file)
+    }
+
+    /// Lexes the rest of the source like [`Lexer::tokenize_all`], and returns
+    /// the tokens in a [`TokenArena`], which keeps every token in a single
+    /// list, rather than in a list for every group.
+    pub fn tokenize_all_arena<FileId: Clone>(
+        self,
+        file_id: FileId,
+    ) -> (TokenArena, Vec<Comment>, Vec<Diagnostic<FileId>>) {
+        let (tokens, eof_comments, diagnostics) = self.tokenize_all(file_id);
+        (TokenArena::new(tokens), eof_comments, diagnostics)
+    }
+
+    /// Lexes the rest of the source in recovery mode, and returns every token
+    /// along with the comments after the last one and every error found.
+    fn lex_rest(mut self) -> (Vec<TokenTree>, Vec<Comment>, Vec<LexError>) {
+        self.recovery = true;
+        let mut tokens = vec![];
+        let mut errors = vec![];
+
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        let mut recorded = self.take_errors();
+        recorded.append(&mut errors);
+
+        (tokens, std::mem::take(&mut self.eof_comments), recorded)
+    }
+
+    /// Returns the comments after the last token of the source, which aren't
+    /// attached to any token, such as the comments in a file with only
+    /// comments.  These are only known once the lexer has reached the end of
+    /// the source, and are always empty with the comment table enabled, since
+    /// they are in the comment table instead.
+    pub fn eof_comments(&self) -> &[Comment] {
+        if self.is_eof() {
+            &self.eof_comments
+        } else {
+            &[]
+        }
+    }
+
+    /// Returns the shebang line at the start of the source, such as
+    /// `#!/usr/bin/env cherry`, if there is one and it has been skipped.
+    pub fn shebang(&self) -> Option<&'a str> {
+        self.shebang.clone().map(|loc| &self.source[loc])
+    }
+
+    /// Returns the current position of this lexer, which it can later go back
+    /// to with [`Lexer::rewind`].  This is cheap, so a parser can use it to try
+    /// one interpretation of the tokens and back up if it doesn't work out,
+    /// such as deciding whether `<` starts generic arguments or is a
+    /// comparison.
+    pub fn checkpoint(&self) -> LexerState {
+        LexerState {
+            idx: self.idx,
+            comments: self.comments.clone(),
+            errors: self.errors.len(),
+            fixes: self.fixes.len(),
+            comment_table: self.comment_table.as_ref().map(CommentTable::len),
+            trivia_start: self.trivia_start,
+            peeked: self.peeked.clone(),
+            last_spacing: self.last_spacing.clone(),
+            trivia_tokens: self.trivia_tokens.clone(),
+            queued: self.queued.clone(),
+        }
+    }
+
+    /// Goes back to a position saved with [`Lexer::checkpoint`], so the tokens
+    /// after it are lexed again.  The errors recorded and the comments
+    /// collected into the comment table since the checkpoint are discarded.
+    pub fn rewind(&mut self, state: LexerState) {
+        self.idx = state.idx;
+        self.comments = state.comments;
+        self.errors.truncate(state.errors);
+        self.fixes.truncate(state.fixes);
+        self.trivia_start = state.trivia_start;
+        self.peeked = state.peeked;
+        self.last_spacing = state.last_spacing;
+        self.trivia_tokens = state.trivia_tokens;
+        self.queued = state.queued;
+
+        if let (Some(comment_table), Some(len)) = (&mut self.comment_table, state.comment_table) {
+            comment_table.truncate(len);
+        }
+    }
+
+    /// Returns the errors and warnings recorded so far.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// Returns the errors and warnings recorded so far, and clears them.
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Returns the fixes suggested by the errors found so far, such as the
+    /// valid escape for an escape from another language.  Each fix keeps the
+    /// meaning the source was written with, so they can be applied without
+    /// asking, like the fixes of lints.
+    pub fn fixes(&self) -> &[Fix] {
+        &self.fixes
+    }
+
+    /// Returns the character at the current index, if any.
+    fn peek(&self) -> Option<char> {
+        self.source[self.idx..].chars().next()
+    }
+
+    /// Returns the `n`th character after the current index, if any.  The
+    /// character at the current index is the `0`th character.
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.source[self.idx..].chars().nth(n)
+    }
+
+    /// Returns the character before the current index, if any.
+    fn prev(&self) -> Option<char> {
+        self.source[..self.idx].chars().next_back()
+    }
+
+    /// Moves the current index past the current character, and returns it.
+    fn bump(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.idx += char.len_utf8();
+        Some(char)
+    }
+
+    /// Returns whether or not the next token is a [`Punct`].  A `-` followed
+    /// by a digit starts a negative number, rather than a punctuator, unless
+    /// negative literals are disabled.
+    fn is_punct_next(&self) -> bool {
+        match self.peek() {
+            Some('-') if self.config.negative_literals() => {
+                self.config.is_punct('-') && !self.peek_nth(1).is_some_and(Lexer::is_digit)
+            }
+            Some(char) => self.config.is_punct(char),
+            None => false,
         }
     }
 
+    /// Returns whether or not the lexer has reached the end of the source.
+    fn is_eof(&self) -> bool {
+        self.idx >= self.source.len()
+    }
+
+    /// Returns whether or not the current index is at a `\n` or `\r\n` line
+    /// break.
+    fn is_crlf_or_lf(&self) -> bool {
+        self.source[self.idx..].starts_with('\n') || self.source[self.idx..].starts_with("\r\n")
+    }
+
     /// Returns whether or not `char` is a line breaking character.
     pub fn is_line_break(char: char) -> bool {
-        match char {
-            '\u{000A}' | '\u{000B}' | '\u{000C}' | '\u{000D}' | '\u{0085}' | '\u{2028}'
-            | '\u{2029}' => true,
-            _ => false,
-        }
+        matches!(
+            char,
+            '\u{000A}'
+                | '\u{000B}'
+                | '\u{000C}'
+                | '\u{000D}'
+                | '\u{0085}'
+                | '\u{2028}'
+                | '\u{2029}'
+        )
     }
 
     /// Returns whether or not `char` is a whitespace character, excluding any
     /// line breaking whitespace.
     pub fn is_whitespace(char: char) -> bool {
-        match char {
-            '\u{0009}' | '\u{0020}' | '\u{00A0}' | '\u{1680}' | '\u{2000}' | '\u{2001}'
-            | '\u{2002}' | '\u{2003}' | '\u{2004}' | '\u{2005}' | '\u{2006}' | '\u{2007}'
-            | '\u{2008}' | '\u{2009}' | '\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => true,
-            _ => false,
-        }
+        matches!(
+            char,
+            '\u{0009}'
+                | '\u{0020}'
+                | '\u{00A0}'
+                | '\u{1680}'
+                | '\u{2000}'
+                | '\u{2001}'
+                | '\u{2002}'
+                | '\u{2003}'
+                | '\u{2004}'
+                | '\u{2005}'
+                | '\u{2006}'
+                | '\u{2007}'
+                | '\u{2008}'
+                | '\u{2009}'
+                | '\u{200A}'
+                | '\u{202F}'
+                | '\u{205F}'
+                | '\u{3000}'
+        )
     }
 
     /// Returns whether or not `char` is an identifier starting character.
@@ -65,172 +572,252 @@ impl Lexer {
         UnicodeXID::is_xid_start(char) || char == '_'
     }
 
-    /// Returns whether or not `char` is a punctuator.
+    /// Returns whether or not `char` is one of Cherry's punctuators, which
+    /// can be changed with [`LexerConfig::with_puncts`].
     pub fn is_punct(char: char) -> bool {
-        match char {
-            '!' | '@' | '#' | '$' | '%' | '&' | '*' | ';' | ':' | ',' | '.' | '<' | '>' | '/'
-            | '|' | '-' | '=' | '+' | '?' | '~' => true,
-            _ => false,
-        }
+        matches!(
+            char,
+            '!' | '@'
+                | '#'
+                | '$'
+                | '%'
+                | '&'
+                | '*'
+                | ';'
+                | ':'
+                | ','
+                | '.'
+                | '<'
+                | '>'
+                | '/'
+                | '|'
+                | '-'
+                | '='
+                | '+'
+                | '?'
+                | '~'
+        )
     }
 
     /// Returns whether or not `char` is a digit.
     pub fn is_digit(char: char) -> bool {
-        match char {
-            '0'..='9' => true,
-            _ => false,
-        }
+        char.is_ascii_digit()
     }
 
     /// Returns whether or not `char` is a hexadecimal digit.
     pub fn is_hex_digit(char: char) -> bool {
-        match char {
-            '0'..='9' => true,
-            'a'..='f' => true,
-            'A'..='F' => true,
-            _ => false,
-        }
+        char.is_ascii_hexdigit()
+    }
+
+    /// Returns whether or not `char` is an octal digit.
+    pub fn is_oct_digit(char: char) -> bool {
+        matches!(char, '0'..='7')
     }
 
     /// Returns whether or not `char` is a binary digit.
     pub fn is_bin_digit(char: char) -> bool {
-        match char {
-            '0' | '1' => true,
-            _ => false,
-        }
+        matches!(char, '0' | '1')
     }
 
-    /// Skips a single line or documentation comment.
-    fn skip_line_comment(&mut self) -> Skipped {
-        let start_index = self.idx - 2; // the index of the first character of the comment.
-        let mut doc = false; // whether or not the comment is a doc comment.
+    /// Skips a single line comment, whose marker starts at `start_index`.
+    /// When `doc` is `true`, it may be a documentation comment.
+    fn skip_line_comment(&mut self, start_index: usize, doc: bool) -> Skipped {
+        let mut kind = CommentKind::Line; // what kind of comment this is.
         let mut value = String::new(); // the value of the comment.
 
-        if self.idx < self.chars.len() && self.chars[self.idx] == '/' {
-            doc = true;
-            self.idx += 1;
+        match self.peek() {
+            Some('/') if doc && self.peek_nth(1) != Some('/') => {
+                kind = CommentKind::Doc;
+                self.idx += 1;
+            }
+            Some('!') if doc => {
+                kind = CommentKind::InnerDoc;
+                self.idx += 1;
+            }
+            _ => {}
         }
 
-        while self.idx < self.chars.len() && self.chars[self.idx] != '\n' {
-            self.idx += 1;
-            value.push(self.chars[self.idx]);
+        while let Some(char) = self.peek() {
+            if self.is_crlf_or_lf() {
+                break;
+            }
+
+            value.push(char);
+            self.bump();
         }
 
         Skipped::Comment(Comment {
             loc: start_index..self.idx,
             value: value.trim().to_string(),
-            kind: match doc {
-                true => CommentKind::Line,
-                false => CommentKind::Doc,
-            },
+            kind,
         })
     }
 
-    /// Skips a single block comment.
-    fn skip_block_comment(&mut self) -> Result<Skipped, Diagnostic<()>> {
+    /// Reports a block comment which never ends.  In recovery mode, the
+    /// error is recorded and the comment is treated as if it ended at the
+    /// end of the file.
+    fn unclosed_block_comment(&mut self, start_index: usize) -> Result<(), LexError> {
+        let error = LexError::UnclosedBlockComment {
+            loc: start_index..self.idx,
+        };
+
+        if self.recovery {
+            self.push_error(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Skips a single block comment.  Block comments may be nested, so
+    /// `/* a /* b */ c */` is a single comment.
+    fn skip_block_comment(&mut self) -> Result<Skipped, LexError> {
         let start_index = self.idx - 2; // the index of the first character of this comment
+        let mut kind = CommentKind::Block; // what kind of comment this is.
         let mut value = String::new(); // the value of this comment.
 
-        loop {
-            if self.idx >= self.chars.len() {
-                return Err(Diagnostic::error()
-                    .with_code("E0001")
-                    .with_labels(vec![
-                        Label::primary((), self.idx..self.idx)
-                            .with_message("expected block comment to end here"),
-                        Label::secondary((), start_index..start_index + 2)
-                            .with_message("help: block comment started here"),
-                    ])
-                    .with_message("block comment never ends"));
-            }
-
-            if self.chars[self.idx] == '*' {
-                // could end the block comment?
+        match self.peek() {
+            Some('*') if !matches!(self.peek_nth(1), Some('*') | Some('/')) => {
+                kind = CommentKind::BlockDoc;
+                self.idx += 1;
+            }
+            Some('!') => {
+                kind = CommentKind::InnerBlockDoc;
                 self.idx += 1;
+            }
+            _ => {}
+        }
 
-                if self.idx >= self.chars.len() {
-                    return Err(Diagnostic::error()
-                        .with_code("E0001")
-                        .with_labels(vec![
-                            Label::primary((), self.idx..self.idx)
-                                .with_message("expected block comment to end here"),
-                            Label::secondary((), start_index..start_index + 2)
-                                .with_message("help: block comment started here"),
-                        ])
-                        .with_message("block comment never ends"));
-                }
+        // the start of each comment which is currently open, innermost last.
+        let mut open = vec![start_index];
 
-                if self.chars[self.idx] != '/' {
-                    value.push('*');
-                    value.push(self.chars[self.idx]);
-                    self.idx += 1;
-                    continue;
+        loop {
+            let char = match self.bump() {
+                Some(char) => char,
+                None => {
+                    self.unclosed_block_comment(open[open.len() - 1])?;
+                    break;
                 }
+            };
 
+            if char == '/' && self.peek() == Some('*') {
+                open.push(self.idx - 1);
+                value.push_str("/*");
                 self.idx += 1;
+                continue;
+            }
 
-                break;
+            if char == '*' && self.peek() == Some('/') {
+                self.idx += 1;
+                open.pop();
+
+                if open.is_empty() {
+                    break;
+                }
+
+                value.push_str("*/");
+                continue;
             }
 
-            value.push(self.chars[self.idx]);
-            self.idx += 1;
+            // `\r\n` is kept as `\n`, so comments read the same on every
+            // platform.
+            if char != '\r' || self.peek() != Some('\n') {
+                value.push(char);
+            }
         }
 
         Ok(Skipped::Comment(Comment {
             loc: start_index..self.idx,
             value: value.trim().to_string(),
-            kind: CommentKind::Block,
+            kind,
         }))
     }
 
+    /// Skips a shebang line, if the source starts with one.  `#!` followed by
+    /// `[` is an inner attribute, rather than a shebang.
+    fn skip_shebang(&mut self) -> Option<Skipped> {
+        if self.idx != 0 || !self.source.starts_with("#!") {
+            return None;
+        }
+
+        let end = self.source.find(['\r', '\n']).unwrap_or(self.source.len());
+        if self.source[2..end].trim_start().starts_with('[') {
+            return None;
+        }
+
+        self.idx = end;
+        self.shebang = Some(0..end);
+
+        Some(Skipped::Shebang(0..end))
+    }
+
     /// Skips a single skippable token, such as a whitespace, line break or
     /// comment.  Returns information about the skipped token, if any.
-    fn skip_token(&mut self) -> Result<Skipped, Diagnostic<()>> {
-        if self.idx >= self.chars.len() {
-            return Ok(Skipped::None);
+    fn skip_token(&mut self) -> Result<Skipped, LexError> {
+        if let Some(shebang) = self.skip_shebang() {
+            return Ok(shebang);
         }
 
-        let first_char = self.chars[self.idx];
+        let first_char = match self.peek() {
+            Some(char) => char,
+            None => return Ok(Skipped::None),
+        };
 
         if Lexer::is_whitespace(first_char) {
-            self.idx += 1;
+            self.bump();
             return Ok(Skipped::Whitespace);
         }
 
+        if first_char == '\r' {
+            if self.peek_nth(1) == Some('\n') {
+                self.idx += 2;
+            } else {
+                self.push_error(LexError::StrayCarriageReturn {
+                    loc: self.idx..self.idx + 1,
+                });
+                self.idx += 1;
+            }
+
+            return Ok(Skipped::LineBreak);
+        }
+
         if Lexer::is_line_break(first_char) {
-            self.idx += 1;
+            self.bump();
             return Ok(Skipped::LineBreak);
         }
 
-        if first_char == '/' {
-            if self.idx + 1 >= self.chars.len() {
-                return Ok(Skipped::None);
-            }
+        let rest = &self.source[self.idx..];
 
-            let second_char = self.chars[self.idx + 1];
+        if self.config.block_comments() && rest.starts_with("/*") {
             self.idx += 2;
+            return self.skip_block_comment();
+        }
 
-            if second_char == '/' {
-                // line comment
-
-                return Ok(self.skip_line_comment());
-            } else if second_char == '*' {
-                // block comment
-
-                return self.skip_block_comment();
-            }
+        let marker = self
+            .config
+            .line_comments()
+            .iter()
+            .find(|marker| rest.starts_with(marker.as_str()));
+
+        if let Some(marker) = marker {
+            // only `//` comments can be documentation comments.
+            let doc = marker == "//";
+            let start_index = self.idx;
+            self.idx += marker.len();
+            return Ok(self.skip_line_comment(start_index, doc));
         }
 
         Ok(Skipped::None)
     }
 
     /// Skips all skippable tokens until the next token is found.
-    fn skip(&mut self) -> Result<(), Diagnostic<()>> {
+    fn skip(&mut self) -> Result<(), LexError> {
         loop {
+            let start_index = self.idx;
             let result = self.skip_token();
 
             match result {
-                Ok(skipped) => match skipped {
+                Ok(skipped) => match self.record_skipped(start_index, skipped) {
                     Skipped::Comment(comment) => {
                         self.comments.push(comment);
                     }
@@ -242,22 +829,97 @@ impl Lexer {
         }
     }
 
+    /// Records `skipped`, which started at `start_index`, as a trivia token if
+    /// the lexer is in trivia mode, and returns it.
+    fn record_skipped(&mut self, start_index: usize, skipped: Skipped) -> Skipped {
+        let trivia_tokens = match &mut self.trivia_tokens {
+            Some(trivia_tokens) => trivia_tokens,
+            None => return skipped,
+        };
+
+        let kind = match &skipped {
+            Skipped::Comment(comment) => TriviaKind::Comment(comment.clone()),
+            Skipped::Whitespace => TriviaKind::Whitespace,
+            Skipped::LineBreak => TriviaKind::LineBreak,
+            Skipped::Shebang(_) => TriviaKind::Shebang,
+            Skipped::None => return skipped,
+        };
+
+        // whitespace characters next to each other are a single trivia token.
+        if let Some(TokenTree::Trivia(last)) = trivia_tokens.last_mut() {
+            if kind == TriviaKind::Whitespace
+                && last.kind == TriviaKind::Whitespace
+                && last.loc.end == start_index
+            {
+                last.loc.end = self.idx;
+                return skipped;
+            }
+        }
+
+        trivia_tokens.push(TokenTree::Trivia(TriviaToken {
+            loc: start_index..self.idx,
+            kind,
+        }));
+
+        skipped
+    }
+
+    /// Removes the trivia tokens which start before `end` from the ones
+    /// skipped, after the first `base` of them, and returns them.
+    fn take_trivia(&mut self, base: usize, end: usize) -> Vec<TokenTree> {
+        match &mut self.trivia_tokens {
+            Some(trivia_tokens) => {
+                let len = trivia_tokens[base..]
+                    .iter()
+                    .take_while(|trivia| trivia.loc().start < end)
+                    .count();
+                trivia_tokens.drain(base..base + len).collect()
+            }
+            None => vec![],
+        }
+    }
+
+    /// Removes the first trivia token skipped and returns it, if it starts
+    /// before `end`.
+    fn next_trivia(&mut self, end: usize) -> Option<TokenTree> {
+        let trivia_tokens = self.trivia_tokens.as_mut()?;
+        if trivia_tokens.first()?.loc().start < end {
+            Some(trivia_tokens.remove(0))
+        } else {
+            None
+        }
+    }
+
     /// Returns the spacing to the next token.
-    fn spacing(&mut self) -> Result<Spacing, Diagnostic<()>> {
+    ///
+    /// Comments on the same line as the last token are its trailing comments
+    /// if the line ends after them, and are before the next token otherwise.
+    fn spacing(&mut self) -> Result<Spacing, LexError> {
         let mut has_whitespace = false;
+        let mut comments = vec![];
 
         loop {
+            let start_index = self.idx;
             let result = self.skip_token();
 
             match result {
-                Ok(skipped) => match skipped {
+                Ok(skipped) => match self.record_skipped(start_index, skipped) {
                     Skipped::Comment(comment) => {
                         has_whitespace = true;
-                        self.comments.push(comment);
+                        comments.push(comment);
+                    }
+                    Skipped::Whitespace | Skipped::Shebang(_) => has_whitespace = true,
+                    Skipped::LineBreak => {
+                        self.trailing_comments = comments;
+                        return Ok(Spacing::LineBreak);
                     }
-                    Skipped::Whitespace => has_whitespace = true,
-                    Skipped::LineBreak => return Ok(Spacing::LineBreak),
                     Skipped::None => {
+                        if self.is_eof() {
+                            self.trailing_comments = comments;
+                        } else {
+                            self.comments.append(&mut comments);
+                        }
+
                         if has_whitespace {
                             return Ok(Spacing::Whitespace);
                         } else {
@@ -270,289 +932,515 @@ impl Lexer {
         }
     }
 
-    /// Gets all comments from the `comments` array and returns them, after
-    /// clearing the `comments` array.
-    fn get_comments(&mut self) -> Vec<Comment> {
-        let comments = self.comments.clone();
-        self.comments.clear();
-        comments
+    /// Takes all comments from the `comments` array and returns them, leaving
+    /// it empty.  Most tokens have no comments, and an empty boxed slice
+    /// doesn't allocate.
+    fn get_comments(&mut self) -> Box<[Comment]> {
+        let comments = std::mem::take(&mut self.comments);
+        self.collect_comments(comments)
     }
 
-    /// Tokenizes an identifier token.
-    fn tokenize_iden(&mut self) -> Result<TokenTree, Diagnostic<()>> {
-        let mut value = String::new();
-        let start_index = self.idx;
-
-        while self.idx < self.chars.len() && UnicodeXID::is_xid_continue(self.chars[self.idx]) {
-            value.push(self.chars[self.idx]);
-            self.idx += 1;
-        }
-
-        Ok(TokenTree::Iden(Iden {
-            loc: start_index..self.idx,
-            value,
-            comments: self.get_comments(),
-            spacing: match self.spacing() {
-                Ok(spacing) => spacing,
-                Err(err) => return Err(err),
-            },
-        }))
+    /// Takes the trailing comments of the last token, found by
+    /// [`Lexer::spacing`], and returns them.
+    fn get_trailing_comments(&mut self) -> Box<[Comment]> {
+        let comments = std::mem::take(&mut self.trailing_comments);
+        self.collect_comments(comments)
     }
 
-    /// Tokenizes a hexadecimal number.
-    fn tokenize_hexadecimal(&mut self) -> Result<TokenTree, Diagnostic<()>> {
-        let start_index = self.idx - 2;
+    /// Returns `comments` to attach to a token, or moves them into the comment
+    /// table and returns no comments, if the lexer has one.  In trivia mode,
+    /// no comments are returned either.
+    fn collect_comments(&mut self, comments: Vec<Comment>) -> Box<[Comment]> {
+        if let Some(comment_table) = &mut self.comment_table {
+            for mut comment in comments {
+                shift_loc(&mut comment.loc, self.offset as isize);
+                comment_table.push(comment);
+            }
 
-        if self.idx >= self.chars.len() {
-            return Err(Diagnostic::error()
-                .with_code("E0008")
-                .with_labels(vec![Label::primary((), start_index..self.idx)
-                    .with_message("expected a hexadecimal number here")])
-                .with_message("no hexadecimal number after `0x`"));
+            return Box::default();
         }
 
-        let mut first = true;
-        let mut number = String::new();
+        // in trivia mode, comments are returned as trivia tokens instead.
+        if self.trivia_tokens.is_some() {
+            return Box::default();
+        }
 
-        loop {
-            if self.idx < self.chars.len() {
-                if first {
-                    return Err(Diagnostic::error()
-                        .with_code("E0008")
-                        .with_labels(vec![Label::primary((), start_index..self.idx - 1)
-                            .with_message("expected a hexadecimal number here")])
-                        .with_message("no hexadecimal number after `0x`"));
-                } else {
-                    break;
-                }
-            }
+        comments.into_boxed_slice()
+    }
 
-            if !Lexer::is_hex_digit(self.chars[self.idx]) {
-                if first {
-                    return Err(Diagnostic::error()
-                        .with_code("E0008")
-                        .with_labels(vec![Label::primary((), start_index..self.idx - 1)
-                            .with_message("expected a hexadecimal number here")])
-                        .with_message("no hexadecimal number after `0x`"));
-                } else {
-                    break;
-                }
+    /// Tokenizes an identifier token.  If `raw` is `true`, the `r#` before the
+    /// identifier has already been skipped.
+    fn tokenize_iden(&mut self, raw: bool) -> Result<TokenTree, LexError> {
+        let value_start = self.idx;
+        let start_index = if raw { self.idx - 2 } else { self.idx };
+
+        while let Some(char) = self.peek() {
+            if !self.config.is_iden_continue(char, self.peek_nth(1)) {
+                break;
             }
 
-            self.idx += 1;
-            number.push(self.chars[self.idx]);
-            first = false;
+            self.bump();
         }
 
-        match i64::from_str_radix(&number, 16) {
-            Ok(value) => Ok(TokenTree::Int(Int {
+        // identifiers are normalized to NFC, so an identifier has the same
+        // value however its accented letters were written.
+        let value = &self.source[value_start..self.idx];
+        let value = if value.is_ascii() {
+            value.to_string()
+        } else {
+            let value: String = value.nfc().collect();
+            if self.config.confusable_warnings() {
+                let loc = start_index..self.idx;
+                if let Some(warning) = confusable::check_identifier(&value, &loc) {
+                    self.push_error(warning);
+                }
+            }
+            value
+        };
+
+        if !raw && self.config.is_keyword(&value) {
+            return Ok(TokenTree::Keyword(Keyword {
                 loc: start_index..self.idx,
-                kind: IntKind::Hexadecimal,
                 value,
                 comments: self.get_comments(),
-                spacing: match self.spacing() {
-                    Ok(spacing) => spacing,
-                    Err(err) => return Err(err),
-                },
-            })),
-            Err(_) => Err(Diagnostic::error()
-                .with_code("E0009")
-                .with_labels(vec![Label::primary((), start_index..self.idx)
-                    .with_message("hexadecimal number is too large")])
-                .with_message("hexadecimal number is too large.")),
+                spacing: self.spacing()?,
+                trailing_comments: self.get_trailing_comments(),
+                trivia: None,
+            }));
         }
+
+        Ok(TokenTree::Iden(Iden {
+            loc: start_index..self.idx,
+            value,
+            raw,
+            comments: self.get_comments(),
+            spacing: self.spacing()?,
+            trailing_comments: self.get_trailing_comments(),
+            trivia: None,
+        }))
     }
 
-    /// Tokenizes a binary number.
-    fn tokenize_binary(&mut self) -> Result<TokenTree, Diagnostic<()>> {
-        let start_index = self.idx - 2;
+    /// Tokenizes a hexadecimal number.
+    fn tokenize_hexadecimal(&mut self, negative: bool) -> Result<TokenTree, LexError> {
+        let start_index = self.idx - 2 - negative as usize;
+        let mut number = String::new();
+        self.eat_digits(Lexer::is_hex_digit, &mut number)?;
 
-        if self.idx >= self.chars.len() {
-            return Err(Diagnostic::error()
-                .with_code("E0008")
-                .with_labels(vec![Label::primary((), start_index..self.idx)
-                    .with_message("expected a binary number here")])
-                .with_message("no binary number after `0b`"));
+        if number.is_empty() {
+            self.check_invalid_digit(IntKind::Hexadecimal)?;
+            return Err(LexError::MissingDigits {
+                loc: start_index..self.idx,
+                kind: IntKind::Hexadecimal,
+            });
         }
 
-        let mut first = true;
-        let mut number = String::new();
+        // a `.` is only part of the number if the fraction is followed by an
+        // exponent, so that `0x1.max(2)` is still a method call.
+        let mut fraction = String::new();
+        if self.is_hex_fraction_next() {
+            self.idx += 1;
+            self.eat_digits(Lexer::is_hex_digit, &mut fraction)?;
+        }
 
-        loop {
-            if self.idx < self.chars.len() {
-                if first {
-                    return Err(Diagnostic::error()
-                        .with_code("E0008")
-                        .with_labels(vec![Label::primary((), start_index..self.idx - 1)
-                            .with_message("expected a binary number here")])
-                        .with_message("no binary number after `0b`"));
-                } else {
-                    break;
-                }
-            }
+        if matches!(self.peek(), Some('p' | 'P')) {
+            return self.tokenize_hex_float(start_index, number, fraction, negative);
+        }
 
-            if !Lexer::is_bin_digit(self.chars[self.idx]) {
-                if first {
-                    return Err(Diagnostic::error()
-                        .with_code("E0008")
-                        .with_labels(vec![Label::primary((), start_index..self.idx - 1)
-                            .with_message("expected a binary number here")])
-                        .with_message("no binary number after `0b`"));
-                } else {
-                    break;
-                }
+        self.check_invalid_digit(IntKind::Hexadecimal)?;
+
+        match u128::from_str_radix(&number, 16) {
+            Ok(value) => Ok(TokenTree::Int(Int {
+                loc: start_index..self.idx,
+                kind: IntKind::Hexadecimal,
+                value,
+                negative,
+                digits: number,
+                comments: self.get_comments(),
+                spacing: self.spacing()?,
+                trailing_comments: self.get_trailing_comments(),
+                trivia: None,
+            })),
+            Err(_) => Err(LexError::IntOverflow {
+                loc: start_index..self.idx,
+                kind: IntKind::Hexadecimal,
+            }),
+        }
+    }
+
+    /// Returns whether or not the fraction of a hexadecimal float, such as the
+    /// `.8` in `0x1.8p3`, is next.
+    fn is_hex_fraction_next(&self) -> bool {
+        let rest = match self.source[self.idx..].strip_prefix('.') {
+            Some(rest) => rest,
+            None => return false,
+        };
+        let fraction = rest
+            .find(|char| !Lexer::is_hex_digit(char) && char != '_')
+            .unwrap_or(rest.len());
+
+        fraction > 0 && rest[fraction..].starts_with(['p', 'P'])
+    }
+
+    /// Tokenizes the exponent of a hexadecimal float, such as `0x1.8p3`, whose
+    /// digits have already been eaten.  The value is exact, rounded only once
+    /// to the nearest `f64`.
+    fn tokenize_hex_float(
+        &mut self,
+        start_index: usize,
+        number: String,
+        fraction: String,
+        negative: bool,
+    ) -> Result<TokenTree, LexError> {
+        self.idx += 1; // `p`
+
+        let exponent_negative = match self.peek() {
+            Some(char @ ('+' | '-')) => {
+                self.idx += 1;
+                char == '-'
             }
+            _ => false,
+        };
 
-            self.idx += 1;
-            number.push(self.chars[self.idx]);
-            first = false;
+        let mut exponent = String::new();
+        self.eat_digits(Lexer::is_digit, &mut exponent)?;
+
+        if exponent.is_empty() {
+            let loc = start_index..self.idx;
+            return Err(match self.peek() {
+                None => LexError::MissingExponent { loc, sign: false },
+                Some(_) => LexError::InvalidExponent { loc },
+            });
         }
 
-        match i64::from_str_radix(&number, 2) {
+        // exponents this large overflow or underflow no matter the digits, so
+        // they're clamped to keep the arithmetic below from overflowing.
+        let exponent = exponent.parse::<i64>().unwrap_or(i64::MAX).min(1 << 20);
+        let exponent = if exponent_negative {
+            -exponent
+        } else {
+            exponent
+        };
+
+        let value = Lexer::hex_float_value(
+            &format!("{}{}", number, fraction),
+            exponent - 4 * fraction.len() as i64,
+        );
+
+        if value.is_infinite() {
+            return Err(LexError::FloatOverflow {
+                loc: start_index..self.idx,
+            });
+        }
+
+        Ok(TokenTree::Float(Float {
+            loc: start_index..self.idx,
+            value: if negative { -value } else { value },
+            kind: FloatKind::Hexadecimal,
+            comments: self.get_comments(),
+            spacing: self.spacing()?,
+            trailing_comments: self.get_trailing_comments(),
+            trivia: None,
+        }))
+    }
+
+    /// Returns the hexadecimal digits `digits` multiplied by two to the power
+    /// of `exponent`, rounded to the nearest `f64` (ties to even).
+    fn hex_float_value(digits: &str, mut exponent: i64) -> f64 {
+        let digits = digits.trim_start_matches('0');
+
+        // only the first 32 digits fit in a `u128`.  The rest only matter for
+        // rounding, so a nonzero one is kept as a "sticky" lowest bit.
+        let kept = digits.len().min(32);
+        let mut mantissa = u128::from_str_radix(&digits[..kept], 16).unwrap_or(0);
+        exponent += 4 * (digits.len() - kept) as i64;
+        if digits[kept..].chars().any(|char| char != '0') {
+            mantissa |= 1;
+        }
+
+        if mantissa == 0 {
+            return 0.0;
+        }
+
+        // round to the precision of the result: 53 bits for normal floats,
+        // fewer for subnormal ones.  Afterwards, the mantissa and the scaling
+        // below are both exact.
+        let bits = 128 - mantissa.leading_zeros() as i64;
+        let precision = (bits - 1 + exponent + 1075).min(53);
+        let shift = (bits - precision).clamp(0, 129) as u32;
+
+        if shift > 0 {
+            let half = 1u128 << (shift.min(128) - 1);
+            let (kept, rest) = match shift {
+                129 => (0, 0),
+                128 => (0, mantissa),
+                _ => (mantissa >> shift, mantissa & ((1 << shift) - 1)),
+            };
+
+            mantissa = if rest > half || (rest == half && kept & 1 == 1) {
+                kept + 1
+            } else {
+                kept
+            };
+            exponent += shift as i64;
+        }
+
+        let mut value = mantissa as f64;
+        while exponent != 0 {
+            let step = exponent.clamp(-1000, 1000);
+            value *= f64::from_bits(((step + 1023) as u64) << 52);
+            exponent -= step;
+        }
+
+        value
+    }
+
+    /// Tokenizes a binary number.
+    fn tokenize_binary(&mut self, negative: bool) -> Result<TokenTree, LexError> {
+        let start_index = self.idx - 2 - negative as usize;
+        let mut number = String::new();
+        self.eat_digits(Lexer::is_bin_digit, &mut number)?;
+        self.check_invalid_digit(IntKind::Binary)?;
+
+        if number.is_empty() {
+            return Err(LexError::MissingDigits {
+                loc: start_index..self.idx,
+                kind: IntKind::Binary,
+            });
+        }
+
+        match u128::from_str_radix(&number, 2) {
             Ok(value) => Ok(TokenTree::Int(Int {
                 loc: start_index..self.idx,
                 kind: IntKind::Binary,
                 value,
+                negative,
+                digits: number,
                 comments: self.get_comments(),
-                spacing: match self.spacing() {
-                    Ok(spacing) => spacing,
-                    Err(err) => return Err(err),
-                },
+                spacing: self.spacing()?,
+                trailing_comments: self.get_trailing_comments(),
+                trivia: None,
             })),
-            Err(_) => Err(Diagnostic::error()
-                .with_code("E0009")
-                .with_labels(vec![Label::primary((), start_index..self.idx)
-                    .with_message("hexadecimal number is too large")])
-                .with_message("hexadecimal number is too large.")),
+            Err(_) => Err(LexError::IntOverflow {
+                loc: start_index..self.idx,
+                kind: IntKind::Binary,
+            }),
         }
     }
 
-    /// Tokenizes a single number token.
-    fn tokenize_number(&mut self, negative: bool) -> Result<TokenTree, Diagnostic<()>> {
-        let mut number = match negative {
-            true => "-".to_string(),
-            false => String::new(),
+    /// Tokenizes an octal number.
+    fn tokenize_octal(&mut self, negative: bool) -> Result<TokenTree, LexError> {
+        let start_index = self.idx - 2 - negative as usize;
+        let mut number = String::new();
+        self.eat_digits(Lexer::is_oct_digit, &mut number)?;
+        self.check_invalid_digit(IntKind::Octal)?;
+
+        if number.is_empty() {
+            return Err(LexError::MissingDigits {
+                loc: start_index..self.idx,
+                kind: IntKind::Octal,
+            });
+        }
+
+        match u128::from_str_radix(&number, 8) {
+            Ok(value) => Ok(TokenTree::Int(Int {
+                loc: start_index..self.idx,
+                kind: IntKind::Octal,
+                value,
+                negative,
+                digits: number,
+                comments: self.get_comments(),
+                spacing: self.spacing()?,
+                trailing_comments: self.get_trailing_comments(),
+                trivia: None,
+            })),
+            Err(_) => Err(LexError::IntOverflow {
+                loc: start_index..self.idx,
+                kind: IntKind::Octal,
+            }),
+        }
+    }
+
+    /// Returns an error if the digits of a number with a prefix are followed
+    /// by a letter or another digit, such as the `8` in `0o78`, which isn't a
+    /// digit of numbers of the given `kind`.  The rest of the number is
+    /// skipped, so it isn't lexed as another token.
+    fn check_invalid_digit(&mut self, kind: IntKind) -> Result<(), LexError> {
+        let char = match self.peek() {
+            Some(char) if char.is_alphanumeric() => char,
+            _ => return Ok(()),
         };
-        let first_char = self.chars[self.idx];
-        let start_index = self.idx;
 
-        if first_char == '0' {
-            if self.idx + 1 >= self.chars.len() {
-                number.push('0');
-                return Ok(TokenTree::Int(Int {
-                    loc: start_index..self.idx,
-                    kind: IntKind::Decimal,
-                    value: if negative { -0 } else { 0 },
-                    comments: self.get_comments(),
-                    spacing: match self.spacing() {
-                        Ok(spacing) => spacing,
-                        Err(err) => return Err(err),
-                    },
-                }));
-            }
+        let loc = self.idx..self.idx + char.len_utf8();
+        while self
+            .peek()
+            .is_some_and(|char| char.is_alphanumeric() || char == '_')
+        {
+            self.bump();
+        }
 
-            if self.chars[self.idx + 1] == 'x' {
-                self.idx += 2;
-                return self.tokenize_hexadecimal();
-            } else if self.chars[self.idx + 1] == 'b' {
-                self.idx += 2;
-                return self.tokenize_binary();
-            } else {
-                number.push('0');
+        Err(LexError::InvalidDigit { loc, kind })
+    }
+
+    /// Consumes a run of digits, which may be separated by `_`s, and pushes
+    /// the digits to `number`.  A separator must be between two digits.
+    fn eat_digits(
+        &mut self,
+        is_digit: fn(char) -> bool,
+        number: &mut String,
+    ) -> Result<(), LexError> {
+        while let Some(char) = self.peek() {
+            if is_digit(char) {
+                number.push(char);
+            } else if char == '_' {
+                let reason = if self.peek_nth(1) == Some('_') {
+                    "digit separators may not be doubled"
+                } else if !self.prev().is_some_and(is_digit) {
+                    "digit separators may not start a number"
+                } else if !self.peek_nth(1).is_some_and(is_digit) {
+                    "digit separators may not end a number"
+                } else {
+                    self.idx += 1;
+                    continue;
+                };
+
+                // the separator is part of the invalid number, so that
+                // recovery skips the rest of it, such as the `5` in `1._5`.
                 self.idx += 1;
+                return Err(LexError::MisplacedDigitSeparator {
+                    loc: self.idx - 1..self.idx,
+                    reason,
+                });
+            } else {
+                break;
             }
+
+            self.idx += 1;
         }
 
-        let mut is_float = false;
+        Ok(())
+    }
 
-        'main_number_loop: loop {
-            if self.idx >= self.chars.len() {
-                break;
+    /// Returns whether or not a float starting with a `.`, such as `.5`, is at
+    /// `idx`.  A `.` directly after another `.` isn't, so that `1..2` is still
+    /// a range.
+    fn is_leading_dot_float_at(&self, idx: usize) -> bool {
+        self.config.leading_dot_floats()
+            && !self.source[..idx].ends_with('.')
+            && self.source[idx..].starts_with('.')
+            && self.source[idx + 1..]
+                .chars()
+                .next()
+                .is_some_and(Lexer::is_digit)
+    }
+
+    /// Tokenizes a single number token.
+    fn tokenize_number(&mut self, negative: bool) -> Result<TokenTree, LexError> {
+        let mut number = String::new();
+        let start_index = self.idx - negative as usize;
+
+        if self.peek() == Some('0') {
+            match self.peek_nth(1) {
+                Some('x') => {
+                    self.idx += 2;
+                    return self.tokenize_hexadecimal(negative);
+                }
+                Some('b') => {
+                    self.idx += 2;
+                    return self.tokenize_binary(negative);
+                }
+                Some('o') => {
+                    self.idx += 2;
+                    return self.tokenize_octal(negative);
+                }
+                _ => {
+                    number.push('0');
+                    self.idx += 1;
+                }
             }
+        }
 
-            let current_char = self.chars[self.idx];
+        let mut is_float = false;
+        let mut trailing_dot = false;
 
-            if Lexer::is_digit(current_char) {
-                number.push(current_char);
+        while let Some(current_char) = self.peek() {
+            if Lexer::is_digit(current_char) || current_char == '_' {
+                self.eat_digits(Lexer::is_digit, &mut number)?;
+                continue;
             } else if current_char == '.' {
                 if is_float {
                     break; // second `.` in a number literal
-                } else {
+                }
+
+                let next = self.peek_nth(1);
+                let exponent_next = matches!(next, Some('e' | 'E'))
+                    && self
+                        .peek_nth(2)
+                        .is_some_and(|char| Lexer::is_digit(char) || char == '+' || char == '-');
+
+                // a `_` is included so that `1._5` reports a misplaced digit
+                // separator.
+                if next.is_some_and(|char| Lexer::is_digit(char) || char == '_') || exponent_next {
                     is_float = true;
                     number.push('.');
+                } else if self.config.trailing_dot_floats()
+                    && next != Some('.')
+                    && !next.is_some_and(Lexer::is_iden)
+                {
+                    is_float = true;
+                    trailing_dot = true;
+                    number.push('.');
+                    self.idx += 1;
+                    break;
+                } else {
+                    // the `.` starts a method call or a range, such as
+                    // `1.max(2)` or `1..2`.
+                    break;
                 }
             } else if current_char == 'e' || current_char == 'E' {
-                if !is_float {
-                    return Err(Diagnostic::error()
-                        .with_code("E0003")
-                        .with_labels(vec![Label::primary((), start_index..self.idx)
-                            .with_message("integers may not have an exponent")])
-                        .with_message("exponent after `.`"));
-                }
+                // a number with an exponent is a float, even without a `.`,
+                // such as `1e10`.
+                is_float = true;
 
-                if self.chars[self.idx - 1] == '.' {
+                if self.prev() == Some('.') {
                     // an exponent may not immediately follow a `.`
                     self.idx += 1;
 
-                    return Err(Diagnostic::error()
-                        .with_code("E0002")
-                        .with_labels(vec![
-                            Label::primary((), start_index..self.idx)
-                                .with_message("exponent cannot immediately follow `.`"),
-                            Label::secondary((), self.idx - 2..self.idx - 2)
-                                .with_message("try inserting a `0` after this `.`"),
-                        ])
-                        .with_message("exponent after `.`"));
+                    return Err(LexError::ExponentAfterDot {
+                        loc: start_index..self.idx,
+                    });
                 }
 
                 number.push(current_char);
                 self.idx += 1;
 
-                if self.idx >= self.chars.len() {
-                    return Err(Diagnostic::error()
-                        .with_code("E0004")
-                        .with_labels(vec![Label::primary((), start_index..self.idx)
-                            .with_message("expected an exponent value or `+`/`-`")])
-                        .with_message("expected an exponent value"));
-                }
+                let current_char = match self.peek() {
+                    Some(char) => char,
+                    None => {
+                        return Err(LexError::MissingExponent {
+                            loc: start_index..self.idx,
+                            sign: true,
+                        })
+                    }
+                };
 
-                let current_char = self.chars[self.idx];
                 if current_char == '+' || current_char == '-' {
                     number.push(current_char);
 
                     self.idx += 1;
                 }
 
-                let mut first = false;
-                loop {
-                    if self.idx >= self.chars.len() {
-                        if first {
-                            return Err(Diagnostic::error()
-                                .with_code("E0004")
-                                .with_labels(vec![Label::primary((), start_index..self.idx)
-                                    .with_message("expected an exponent value")])
-                                .with_message("expected an exponent value"));
-                        } else {
-                            break 'main_number_loop;
-                        }
-                    }
-
-                    if !Lexer::is_digit(self.chars[self.idx]) {
-                        if first {
-                            return Err(Diagnostic::error()
-                                .with_code("E0005")
-                                .with_labels(vec![Label::primary((), start_index..self.idx)
-                                    .with_message("expected a valid exponent value (a number)")])
-                                .with_message("expected a valid exponent value"));
-                        } else {
-                            break 'main_number_loop;
-                        }
-                    }
+                let exponent_start = self.idx;
+                self.eat_digits(Lexer::is_digit, &mut number)?;
 
-                    number.push(self.chars[self.idx]);
-                    self.idx += 1;
-                    first = false;
+                if self.idx == exponent_start {
+                    let loc = start_index..self.idx;
+                    return Err(match self.peek() {
+                        None => LexError::MissingExponent { loc, sign: false },
+                        Some(_) => LexError::InvalidExponent { loc },
+                    });
                 }
+
+                break;
             } else {
                 break;
             }
@@ -561,24 +1449,33 @@ impl Lexer {
         }
 
         let comments = self.get_comments();
-        let number = number.replace("_", "");
 
         if is_float {
-            match number.parse() {
-                Ok(value) => Ok(TokenTree::Float(Float {
+            let kind = if number.starts_with('.') {
+                FloatKind::LeadingDot
+            } else if trailing_dot {
+                FloatKind::TrailingDot
+            } else if number.contains('.') {
+                FloatKind::Decimal
+            } else {
+                FloatKind::Exponent
+            };
+
+            // parsing rounds values which are too large to infinity, rather
+            // than failing.
+            match number.parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(TokenTree::Float(Float {
                     loc: start_index..self.idx,
-                    value,
+                    value: if negative { -value } else { value },
+                    kind,
                     comments,
-                    spacing: match self.spacing() {
-                        Ok(spacing) => spacing,
-                        Err(err) => return Err(err),
-                    },
+                    spacing: self.spacing()?,
+                    trailing_comments: self.get_trailing_comments(),
+                    trivia: None,
                 })),
-                Err(_) => Err(Diagnostic::error()
-                    .with_code("E0006")
-                    .with_labels(vec![Label::primary((), start_index..self.idx)
-                        .with_message("float number is too large")])
-                    .with_message("float is too large")),
+                _ => Err(LexError::FloatOverflow {
+                    loc: start_index..self.idx,
+                }),
             }
         } else {
             match number.parse() {
@@ -586,201 +1483,890 @@ impl Lexer {
                     loc: start_index..self.idx,
                     kind: IntKind::Decimal,
                     value,
+                    negative,
+                    digits: number,
                     comments,
-                    spacing: match self.spacing() {
-                        Ok(spacing) => spacing,
-                        Err(err) => return Err(err),
-                    },
+                    spacing: self.spacing()?,
+                    trailing_comments: self.get_trailing_comments(),
+                    trivia: None,
                 })),
-                Err(_) => Err(Diagnostic::error()
-                    .with_code("E0007")
-                    .with_labels(vec![Label::primary((), start_index..self.idx)
-                        .with_message("integer number is too large")])
-                    .with_message("integer is too large")),
+                Err(_) => Err(LexError::IntOverflow {
+                    loc: start_index..self.idx,
+                    kind: IntKind::Decimal,
+                }),
             }
         }
     }
 
-    // Tokenizes a single string token.
-    fn tokenize_string(&mut self) -> Result<TokenTree, Diagnostic<()>> {
+    /// Tokenizes a string token, either `"..."` or a triple-quoted
+    /// `"""..."""` string.  Interpolations in the string, such as `${name}`,
+    /// are tokenized as groups.
+    fn tokenize_string(&mut self) -> Result<TokenTree, LexError> {
         let start_index = self.idx;
-        let quote = self.chars[start_index];
+        let kind = if self.source[self.idx..].starts_with("\"\"\"") {
+            StrKind::Triple
+        } else {
+            StrKind::Normal
+        };
 
-        let mut string = quote.to_string();
-        self.idx += 1;
+        // the comments before the string are taken first, so they aren't mixed
+        // up with the comments inside of its interpolations.
+        let comments = self.get_comments();
 
-        loop {
-            if self.idx >= self.chars.len() {
-                return Err(Diagnostic::error()
-                    .with_code("E0010")
-                    .with_labels(vec![Label::primary((), start_index..self.idx)
-                        .with_message("string never closes")])
-                    .with_message("string never closes"));
+        match self.tokenize_string_segments(start_index, &kind) {
+            Ok(value) => Ok(TokenTree::Str(Str {
+                loc: start_index..self.idx,
+                value,
+                kind,
+                comments,
+                spacing: self.spacing()?,
+                trailing_comments: self.get_trailing_comments(),
+                trivia: None,
+            })),
+            Err(error) => {
+                self.comments.splice(0..0, comments.into_vec());
+                Err(error)
             }
+        }
+    }
 
-            if self.chars[self.idx] == quote {
-                self.idx += 1;
-                string.push(quote);
+    /// Tokenizes the segments of the string starting at `start_index`, up to
+    /// and including its closing quote.
+    fn tokenize_string_segments(
+        &mut self,
+        start_index: usize,
+        kind: &StrKind,
+    ) -> Result<Vec<StrSegment>, LexError> {
+        let quote = if *kind == StrKind::Triple {
+            "\"\"\""
+        } else if self.source[start_index..].starts_with('\'') {
+            "'"
+        } else {
+            "\""
+        };
+
+        // the text before, between and after the interpolations, so there is
+        // always one more text than there are interpolations.  escapes are
+        // only processed once the whole string has been skipped, so recovery
+        // continues after the string, and the indentation of a triple-quoted
+        // string is known.
+        let mut texts = vec![];
+        let mut interpolations = vec![];
+
+        self.idx += quote.len();
+        let mut text_start = self.idx;
+
+        loop {
+            if self.source[self.idx..].starts_with(quote) {
+                texts.push(text_start..self.idx);
+                self.idx += quote.len();
                 break;
-            } else if self.chars[self.idx] == '\\' {
-                string.push('\\');
+            }
 
-                self.idx += 1;
-                if self.idx >= self.chars.len() {
-                    return Err(Diagnostic::error()
-                        .with_code("E0010")
-                        .with_labels(vec![Label::primary((), start_index..self.idx)
-                            .with_message("string never closes")])
-                        .with_message("string never closes"));
+            match self.peek() {
+                Some('\\') => {
+                    self.idx += 1;
+                    if self.bump().is_none() {
+                        return Err(self.unclosed_string(start_index, quote));
+                    }
                 }
+                Some('$') if self.peek_nth(1) == Some('{') => {
+                    texts.push(text_start..self.idx);
+                    self.idx += 1;
 
-                string.push(self.chars[self.idx]);
-                self.idx += 1;
-            } else {
-                string.push(self.chars[self.idx]);
-                self.idx += 1;
+                    let group_start = self.idx;
+                    let closers = std::mem::take(&mut self.closers);
+                    let tokens = self
+                        .open_group(Delimiter::Brace)
+                        .and_then(|group| self.tokenize_tree(vec![group]));
+                    self.closers = closers;
+                    let group = match tokens? {
+                        Lexed::Group(group) => group,
+                        _ => unreachable!("the interpolation is still open"),
+                    };
+
+                    interpolations.push(Group {
+                        loc: group_start..self.idx,
+                        delimiter: Delimiter::Brace,
+                        tokens: group.tokens,
+                        inner_comments: self.get_comments(),
+                        comments: group.comments,
+                        spacing: Spacing::None,
+                        trailing_comments: Box::default(),
+                        trivia: None,
+                    });
+                    text_start = self.idx;
+                }
+                Some(_) => {
+                    self.bump();
+                }
+                None => return Err(self.unclosed_string(start_index, quote)),
             }
         }
 
-        match unescape(&string) {
-            Ok(value) => {
-                return Ok(TokenTree::Str(Str {
-                    loc: start_index..self.idx,
-                    value,
-                    comments: self.get_comments(),
-                    spacing: match self.spacing() {
-                        Ok(spacing) => spacing,
-                        Err(err) => return Err(err),
-                    },
-                }))
-            }
-            Err(err) => match err {
-                UnescapeError::InvalidEscape { index, .. } => {
-                    let index = start_index + index;
-
-                    return Err(Diagnostic::error()
-                        .with_code("E0011")
-                        .with_labels(vec![Label::primary((), index..index)
-                            .with_message("invalid string escape here")])
-                        .with_message("invalid string escape"));
+        let indent = if *kind == StrKind::Triple {
+            self.trim_triple_quoted(&mut texts)
+        } else {
+            ""
+        };
+
+        let mut segments = vec![];
+        let mut interpolations = interpolations.into_iter();
+        let mut invalid = vec![];
+
+        for text in texts {
+            let text = self.unescape(text, indent, &mut invalid);
+            if !text.is_empty() {
+                segments.push(StrSegment::Text(text));
+            }
+
+            if let Some(group) = interpolations.next() {
+                segments.push(StrSegment::Interpolation(group));
+            }
+        }
+
+        if !invalid.is_empty() {
+            return Err(self.invalid_escapes(invalid));
+        }
+
+        Ok(segments)
+    }
+
+    /// Removes the line break after the opening `"""` of a triple-quoted
+    /// string, and the line break and indentation before its closing `"""`,
+    /// from the first and last of `texts`.
+    ///
+    /// Returns the indentation before the closing `"""`, which is stripped
+    /// from the start of every line of the string, unless indentation
+    /// stripping is disabled in the configuration.
+    fn trim_triple_quoted(&self, texts: &mut [Loc]) -> &'a str {
+        let first = &mut texts[0];
+        if self.source[first.clone()].starts_with('\n') {
+            first.start += 1;
+        } else if self.source[first.clone()].starts_with("\r\n") {
+            first.start += 2;
+        }
+
+        let last = texts.last_mut().unwrap();
+        let text = &self.source[last.clone()];
+
+        let line_start = match text.rfind('\n') {
+            Some(idx) => idx,
+            None => return "",
+        };
+
+        let indent = &self.source[last.start + line_start + 1..last.end];
+        if !indent.chars().all(|char| char == ' ' || char == '\t') {
+            return "";
+        }
+
+        last.end = last.start + line_start;
+        if text[..line_start].ends_with('\r') {
+            last.end -= 1;
+        }
+
+        if self.config.strips_indent() {
+            indent
+        } else {
+            ""
+        }
+    }
+
+    /// Returns the error for a string starting at `start_index` which never
+    /// closes with `quote`.
+    fn unclosed_string(&self, start_index: usize, quote: &str) -> LexError {
+        // the missing quote usually belongs on the line the string starts on,
+        // rather than at the end of the file.
+        let line_end = match quote {
+            "\"" | "'" => self.source[start_index..self.idx]
+                .find(Lexer::is_line_break)
+                .map(|len| start_index + len),
+            _ => None,
+        };
+
+        LexError::UnterminatedString {
+            loc: start_index..self.idx,
+            terminator: quote.to_string(),
+            raw: false,
+            line_end,
+        }
+    }
+
+    /// Unescapes the contents of a string or character literal in `range`,
+    /// removing `indent` from the start of each line.  `\r\n` is kept as
+    /// `\n`, so strings have the same value on every platform.  Invalid escapes
+    /// are skipped, and added to `invalid` along with the index of their `\`,
+    /// for [`Lexer::invalid_escapes`].
+    fn unescape(
+        &self,
+        range: Loc,
+        indent: &str,
+        invalid: &mut Vec<(usize, escape::InvalidEscape)>,
+    ) -> String {
+        let inner = &self.source[range.clone()];
+        let mut unindented = if self.source[..range.start].ends_with('\n') {
+            indent
+        } else {
+            ""
+        };
+
+        let mut value = String::with_capacity(inner.len());
+        let mut idx = 0;
+
+        while let Some(char) = inner[idx..].chars().next() {
+            let start = idx;
+            idx += char.len_utf8();
+
+            if let Some(rest) = unindented.strip_prefix(char) {
+                unindented = rest;
+                continue;
+            }
+
+            unindented = if char == '\n' { indent } else { "" };
+
+            if char == '\r' && inner[idx..].starts_with('\n') {
+                continue;
+            } else if char != '\\' {
+                value.push(char);
+                continue;
+            }
+
+            match escape::unescape_one(&inner[idx..]) {
+                Ok((char, len)) => {
+                    value.extend(char);
+                    idx += len;
                 }
-                UnescapeError::InvalidUnicode { index, .. } => {
-                    let index = start_index + index;
-                    return Err(Diagnostic::error()
-                        .with_code("E0012")
-                        .with_labels(vec![Label::primary((), index..index)
-                            .with_message("invalid unicode escape here")])
-                        .with_message("invalid unicode escape in string"));
+                Err(escape) => {
+                    idx += escape.len;
+                    invalid.push((range.start + start, escape));
                 }
-            },
+            }
         }
+
+        value
     }
 
-    /// Tokenizes a group token.
-    fn tokenize_group(&mut self, close: char) -> Result<TokenTree, Diagnostic<()>> {
-        let start_index = self.idx;
-        let mut tokens = vec![];
+    /// Returns the error for the invalid escapes in a literal, found by
+    /// [`Lexer::unescape`].  Every invalid escape is labeled, so they can all
+    /// be fixed at once, and the suggested escapes are recorded as fixes.
+    fn invalid_escapes(&mut self, invalid: Vec<(usize, escape::InvalidEscape)>) -> LexError {
+        let mut escapes = vec![];
+
+        for (start, escape) in invalid {
+            let suggestion = escape.suggestion.map(|suggestion| {
+                let loc = start..start + 1 + suggestion.len;
+                let mut fix_loc = loc.clone();
+                shift_loc(&mut fix_loc, self.offset as isize);
+                self.fixes.push(Fix {
+                    loc: fix_loc,
+                    replacement: suggestion.replacement.clone(),
+                });
+
+                EscapeSuggestion {
+                    escape: self.source[loc.clone()].to_string(),
+                    loc,
+                    replacement: suggestion.replacement,
+                }
+            });
+
+            escapes.push(EscapeError {
+                loc: start..start + 1 + escape.len,
+                message: escape.message,
+                unicode: escape.unicode,
+                suggestion,
+            });
+        }
+
+        LexError::InvalidEscape { escapes }
+    }
 
+    /// Tokenizes a character literal token, which must contain exactly one
+    /// character, or one escape.
+    fn tokenize_char(&mut self) -> Result<TokenTree, LexError> {
+        let start_index = self.idx;
         self.idx += 1;
 
         loop {
-            if self.idx >= self.chars.len() {
-                return Err(Diagnostic::error()
-                    .with_code("E0014")
-                    .with_labels(vec![
-                        Label::primary((), start_index..self.idx)
-                            .with_message(format!("group never closes with '{}'", close)),
-                        Label::secondary((), start_index..start_index)
-                            .with_message("group starts here"),
-                    ])
-                    .with_message("group never ends"));
-            }
-
-            if self.chars[self.idx] == close {
-                self.idx += 1;
-                break;
+            match self.peek() {
+                Some('\'') => break,
+                Some('\\') => {
+                    self.idx += 1;
+                    self.bump();
+                }
+                Some(char) if !Lexer::is_line_break(char) => {
+                    self.bump();
+                }
+                _ => {
+                    return Err(LexError::UnterminatedChar {
+                        loc: start_index..self.idx,
+                    });
+                }
             }
+        }
 
-            if let Some(result) = self.tokenize() {
-                match result {
-                    Ok(token) => tokens.push(token),
-                    Err(e) => return Err(e),
+        let inner = start_index + 1..self.idx;
+        self.idx += 1;
+
+        let mut invalid = vec![];
+        let value = self.unescape(inner, "", &mut invalid);
+
+        if !invalid.is_empty() {
+            // an escaped `\` followed by the unknown escape is two characters,
+            // which can't be in a character literal.
+            for (_, escape) in &mut invalid {
+                let two_chars = escape
+                    .suggestion
+                    .as_ref()
+                    .is_some_and(|suggestion| suggestion.replacement.starts_with("\\\\"));
+                if two_chars {
+                    escape.suggestion = None;
                 }
             }
+
+            return Err(self.invalid_escapes(invalid));
         }
 
-        Ok(TokenTree::Group(Group {
+        let mut chars = value.chars();
+        let value = match (chars.next(), chars.next()) {
+            (Some(value), None) => value,
+            (None, _) => {
+                return Err(LexError::EmptyChar {
+                    loc: start_index..self.idx,
+                });
+            }
+            (Some(_), Some(_)) => {
+                return Err(LexError::MultipleChars {
+                    loc: start_index..self.idx,
+                });
+            }
+        };
+
+        Ok(TokenTree::Char(Char {
             loc: start_index..self.idx,
-            tokens,
+            value,
             comments: self.get_comments(),
-            spacing: match self.spacing() {
-                Ok(spacing) => spacing,
-                Err(err) => return Err(err),
-            },
+            spacing: self.spacing()?,
+            trailing_comments: self.get_trailing_comments(),
+            trivia: None,
         }))
     }
 
-    /// Tokenizes a single token from the `chars` list, then returns it, if
-    /// there was another token and it was valid.
-    fn tokenize(&mut self) -> Option<Result<TokenTree, Diagnostic<()>>> {
-        if let Err(err) = self.skip() {
-            return Some(Err(err));
+    /// Returns whether or not a raw string starts at the current index: an
+    /// `r`, followed by any number of `#`s and a `"`.
+    fn is_raw_string_next(&self) -> bool {
+        let mut chars = self.source[self.idx..].chars();
+
+        chars.next() == Some('r') && chars.find(|&char| char != '#') == Some('"')
+    }
+
+    /// Tokenizes a raw string token, such as `r"..."` or `r#"..."#`.
+    fn tokenize_raw_string(&mut self) -> Result<TokenTree, LexError> {
+        let start_index = self.idx;
+        self.idx += 1; // `r`
+
+        let mut hashes = 0;
+        while self.peek() == Some('#') {
+            hashes += 1;
+            self.idx += 1;
         }
 
-        if self.idx >= self.chars.len() {
-            return None;
+        self.idx += 1; // `"`
+        let value_start = self.idx;
+
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let value_end = match self.source[value_start..].find(&terminator) {
+            Some(len) => value_start + len,
+            None => {
+                self.idx = self.source.len();
+
+                return Err(LexError::UnterminatedString {
+                    loc: start_index..self.idx,
+                    terminator,
+                    raw: true,
+                    line_end: None,
+                });
+            }
+        };
+
+        self.idx = value_end + terminator.len();
+
+        Ok(TokenTree::Str(Str {
+            loc: start_index..self.idx,
+            value: vec![StrSegment::Text(
+                self.source[value_start..value_end].replace("\r\n", "\n"),
+            )],
+            kind: StrKind::Raw(hashes),
+            comments: self.get_comments(),
+            spacing: self.spacing()?,
+            trailing_comments: self.get_trailing_comments(),
+            trivia: None,
+        }))
+    }
+
+    /// Returns whether or not the group starting at `start_index` closes at
+    /// the current index, after skipping its `close` delimiter.  In recovery
+    /// mode, a group which isn't closed before the end of the source or a
+    /// different closing delimiter is closed there, after recording the
+    /// error.
+    fn group_closes(&mut self, start_index: usize, close: char) -> Result<bool, LexError> {
+        if self.is_eof() {
+            let error = LexError::UnclosedGroup {
+                loc: start_index..self.idx,
+                close,
+            };
+
+            if self.recovery {
+                // close the group at the end of the file, keeping the tokens
+                // found so far.
+                self.push_error(error);
+                return Ok(true);
+            }
+
+            return Err(error);
         }
 
-        let first_char = self.chars[self.idx];
+        if self.peek() == Some(close) {
+            self.idx += 1;
+            return Ok(true);
+        }
+
+        if let Some(found) = self.peek().filter(|char| matches!(char, ')' | ']' | '}')) {
+            let error = LexError::MismatchedDelimiter {
+                loc: self.idx..self.idx + 1,
+                open: start_index..start_index + 1,
+                expected: close,
+                found,
+            };
+
+            if self.recovery {
+                // close the group here, so the tokens after it are still
+                // lexed.  The delimiter is kept for the enclosing group if it
+                // closes that one, such as the `}` in `{ ( }`.
+                self.push_error(error);
+                if !self.closers.contains(&found) {
+                    self.idx += 1;
+                }
+                return Ok(true);
+            }
+
+            return Err(error);
+        }
+
+        Ok(false)
+    }
+
+    /// Opens the group starting at the current index, whose tokens are lexed
+    /// next, or returns an error if it is nested too deeply.
+    fn open_group(&mut self, delimiter: Delimiter) -> Result<OpenGroup, LexError> {
         let start_index = self.idx;
+        let trivia_start = self.trivia_start;
 
-        if Lexer::is_iden(first_char) {
-            Some(self.tokenize_iden())
-        } else if Lexer::is_punct(first_char) {
-            self.idx += 1;
+        if self.depth >= self.config.max_depth() {
+            if self.recovery {
+                self.skip_nested_group();
+            }
+
+            return Err(LexError::GroupTooDeep {
+                loc: start_index..start_index + 1,
+                max_depth: self.config.max_depth(),
+            });
+        }
+
+        // the comments before the group are taken now, so they aren't
+        // attached to the first token in it.
+        let comments = self.get_comments();
+
+        self.closers.push(delimiter.close());
+        self.depth += 1;
+        self.idx += 1;
+        self.trivia_start = self.idx;
+
+        Ok(OpenGroup {
+            start_index,
+            trivia_start,
+            delimiter,
+            tokens: vec![],
+            comments,
+            trivia_len: self.trivia_tokens.as_ref().map_or(0, Vec::len),
+        })
+    }
+
+    /// Closes the innermost open group in `groups`, and returns it.
+    fn close_group(&mut self, groups: &mut Vec<OpenGroup>) -> OpenGroup {
+        self.closers.pop();
+        self.depth -= 1;
+        groups.pop().expect("a group is open")
+    }
+
+    /// Skips past the group starting at the current index, which is nested too
+    /// deeply to be lexed, so only one error is reported for it.  Delimiters
+    /// are matched without lexing the tokens between them, so delimiters in
+    /// strings and comments are counted too.
+    fn skip_nested_group(&mut self) {
+        let mut depth = 0;
+
+        while let Some(char) = self.bump() {
+            if Delimiter::from_open(char).is_some() {
+                depth += 1;
+            } else if matches!(char, ')' | ']' | '}') {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Tokenizes token trees, keeping the groups which are open in `groups`
+    /// rather than recursing into them, so deeply nested groups can't overflow
+    /// the stack.  With no open groups, a single top-level token is tokenized.
+    /// Otherwise, tokens are tokenized until the outermost group in `groups`
+    /// closes, and it is returned.
+    fn tokenize_tree(&mut self, mut groups: Vec<OpenGroup>) -> Result<Lexed, LexError> {
+        let outermost = groups.len();
+        let result = self.tokenize_tree_in(&mut groups, outermost);
+
+        // the groups which are still open after an error are abandoned.
+        for _ in 0..groups.len() {
+            self.closers.pop();
+            self.depth -= 1;
+        }
 
-            if first_char == '-' {
-                if self.idx < self.chars.len() {
-                    if Lexer::is_digit(self.chars[self.idx]) {
-                        return Some(self.tokenize_number(true));
+        result
+    }
+
+    /// Tokenizes token trees for [`Lexer::tokenize_tree`], which returns once
+    /// fewer than `outermost` groups are open.
+    fn tokenize_tree_in(
+        &mut self,
+        groups: &mut Vec<OpenGroup>,
+        outermost: usize,
+    ) -> Result<Lexed, LexError> {
+        loop {
+            // the token finished by this step, with where it and its leading
+            // trivia start, unless a token is started instead.
+            let mut finished = None;
+
+            if let Some(group) = groups.last() {
+                let (start_index, close) = (group.start_index, group.delimiter.close());
+
+                match self
+                    .skip()
+                    .and_then(|()| self.group_closes(start_index, close))
+                {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        let mut group = self.close_group(groups);
+                        let trivia = self.take_trivia(group.trivia_len, self.idx);
+                        group.tokens.extend(trivia);
+
+                        if groups.len() < outermost {
+                            return Ok(Lexed::Group(group));
+                        }
+
+                        let (start_index, trivia_start) = (group.start_index, group.trivia_start);
+                        finished = Some((self.finish_group(group), start_index, trivia_start));
                     }
+                    Err(error) => {
+                        let group = self.close_group(groups);
+                        if let Some(trivia_tokens) = &mut self.trivia_tokens {
+                            trivia_tokens.truncate(group.trivia_len);
+                        }
+
+                        if groups.len() < outermost {
+                            return Err(error);
+                        }
+
+                        finished = Some((Err(error), group.start_index, group.trivia_start));
+                    }
+                }
+            } else {
+                self.skip()?;
+
+                if self.is_eof() {
+                    return Ok(Lexed::Eof);
                 }
             }
 
-            Some(Ok(TokenTree::Punct(Punct {
-                loc: start_index..self.idx,
+            let (result, start_index, trivia_start) = match finished {
+                Some(finished) => finished,
+                None => {
+                    let start_index = self.idx;
+                    let trivia_start = self.trivia_start;
+
+                    match self.peek().and_then(Delimiter::from_open) {
+                        Some(delimiter) => match self.open_group(delimiter) {
+                            Ok(group) => {
+                                groups.push(group);
+                                continue;
+                            }
+                            Err(error) => (Err(error), start_index, trivia_start),
+                        },
+                        None => (self.tokenize_token(), start_index, trivia_start),
+                    }
+                }
+            };
+
+            let mut token = match result {
+                Err(error) if self.recovery => self.recover(start_index, error),
+                result => result?,
+            };
+
+            if self.lossless {
+                self.record_trivia(&mut token, trivia_start);
+            }
+
+            match groups.last_mut() {
+                Some(group) => {
+                    let trivia = self.take_trivia(group.trivia_len, token.loc().start);
+                    group.tokens.extend(trivia);
+                    group.tokens.push(token);
+                }
+                None => return Ok(Lexed::Token(token)),
+            }
+        }
+    }
+
+    /// Returns the group token of `group`, after its closing delimiter.
+    fn finish_group(&mut self, group: OpenGroup) -> Result<TokenTree, LexError> {
+        Ok(TokenTree::Group(Group {
+            loc: group.start_index..self.idx,
+            delimiter: group.delimiter,
+            tokens: group.tokens,
+            inner_comments: self.get_comments(),
+            comments: group.comments,
+            spacing: self.spacing()?,
+            trailing_comments: self.get_trailing_comments(),
+            trivia: None,
+        }))
+    }
+
+    /// Tokenizes the token starting at the current character.  There must be
+    /// at least one character left, which doesn't open a group, since groups
+    /// are tokenized by [`Lexer::tokenize_tree`].
+    fn tokenize_token(&mut self) -> Result<TokenTree, LexError> {
+        let first_char = self.peek().unwrap_or_default();
+        let start_index = self.idx;
+
+        if self.is_raw_string_next() {
+            self.tokenize_raw_string()
+        } else if self.source[self.idx..].starts_with("r#")
+            && self.peek_nth(2).is_some_and(Lexer::is_iden)
+        {
+            self.idx += 2;
+            self.tokenize_iden(true)
+        } else if Lexer::is_iden(first_char) {
+            self.tokenize_iden(false)
+        } else if first_char == '.' && self.is_leading_dot_float_at(self.idx) {
+            self.tokenize_number(false)
+        } else if self.config.is_punct(first_char) {
+            self.idx += 1;
+
+            if first_char == '-'
+                && self.config.negative_literals()
+                && (self.peek().is_some_and(Lexer::is_digit)
+                    || self.is_leading_dot_float_at(self.idx))
+            {
+                return self.tokenize_number(true);
+            }
+
+            let loc = start_index..self.idx;
+            let comments = self.get_comments();
+            let mut spacing = self.spacing()?;
+
+            // a punctuator immediately followed by another one may be part of
+            // a multi-character operator, such as `==` or `->`.
+            if spacing == Spacing::None && self.is_punct_next() {
+                spacing = Spacing::Joint;
+            }
+
+            Ok(TokenTree::Punct(Punct {
+                loc,
                 value: first_char,
-                comments: self.get_comments(),
-                spacing: match self.spacing() {
-                    Ok(spacing) => spacing,
-                    Err(err) => return Some(Err(err)),
-                },
-            })))
-        } else if Lexer::is_digit(first_char) {
-            Some(self.tokenize_number(false))
-        } else if first_char == '"' || first_char == '\'' {
-            Some(self.tokenize_string())
-        } else if first_char == '{' || first_char == '[' || first_char == '(' {
-            Some(self.tokenize_group(match first_char {
-                '{' => '}',
-                '[' => ']',
-                '(' => ')',
-                _ => unreachable!(),
+                comments,
+                spacing,
+                trailing_comments: self.get_trailing_comments(),
+                trivia: None,
             }))
+        } else if Lexer::is_digit(first_char) {
+            self.tokenize_number(false)
+        } else if first_char == '"' || (first_char == '\'' && self.config.single_quote_strings()) {
+            self.tokenize_string()
+        } else if first_char == '\'' {
+            self.tokenize_char()
         } else {
-            Some(Err(Diagnostic::error()
-                .with_code("E0013")
-                .with_labels(vec![Label::primary((), start_index..start_index)
-                    .with_message("invalid character here")])
-                .with_message("invalid character")))
+            Err(LexError::InvalidCharacter {
+                loc: start_index..start_index + first_char.len_utf8(),
+            })
+        }
+    }
+
+    /// Recovers from an error in the token starting at `start_index`, by
+    /// recording the error and skipping the rest of the invalid token.
+    /// Returns a [`TokenTree::Error`] in place of the invalid token.
+    fn recover(&mut self, start_index: usize, error: LexError) -> TokenTree {
+        self.push_error(error);
+
+        // always make progress, even if the error was found before consuming
+        // anything, such as with an invalid character.
+        if self.idx <= start_index {
+            self.idx = start_index;
+            self.bump();
+        }
+
+        // if the error was found in the middle of a word, such as an invalid
+        // digit in a number literal, skip the rest of the word.
+        if self.prev().is_some_and(UnicodeXID::is_xid_continue) {
+            while self.peek().is_some_and(UnicodeXID::is_xid_continue) {
+                self.bump();
+            }
+        }
+
+        let loc = start_index..self.idx;
+        let comments = self.get_comments();
+        let spacing = match self.spacing() {
+            Ok(spacing) => spacing,
+            Err(error) => {
+                self.push_error(error);
+                Spacing::None
+            }
+        };
+
+        TokenTree::Error(Error {
+            loc,
+            comments,
+            spacing,
+            trailing_comments: self.get_trailing_comments(),
+            trivia: None,
+        })
+    }
+
+    /// Tokenizes a single token from the source string, then returns it, if
+    /// there was another token, with its locations in the source this lexer's
+    /// source is part of.  In recovery mode, errors are replaced with
+    /// [`TokenTree::Error`] tokens.
+    fn tokenize(&mut self) -> Option<Result<TokenTree, LexError>> {
+        let mut result = self.tokenize_next();
+
+        if self.offset != 0 {
+            match &mut result {
+                Some(Ok(token)) => relex::shift(token, self.offset as isize),
+                Some(Err(error)) => error.shift(self.offset as isize),
+                None => {}
+            }
+        }
+
+        result
+    }
+
+    /// Records an error found while lexing, with its locations in the source
+    /// this lexer's source is part of.
+    fn push_error(&mut self, mut error: LexError) {
+        error.shift(self.offset as isize);
+        self.errors.push(error);
+    }
+
+    /// Tokenizes a single token from the source string, like
+    /// [`Lexer::tokenize`], with its locations in this lexer's source.
+    fn tokenize_next(&mut self) -> Option<Result<TokenTree, LexError>> {
+        if let Some(token) = self.queued.take() {
+            return Some(Ok(self.trivia_before(token)));
+        }
+
+        match self.tokenize_tree(vec![]) {
+            Ok(Lexed::Token(token)) => Some(Ok(self.trivia_before(token))),
+            Ok(Lexed::Eof) => {
+                if let Some(trivia) = self.next_trivia(usize::MAX) {
+                    return Some(Ok(trivia));
+                }
+
+                // comments after the last token aren't attached to anything,
+                // so they are kept for `Lexer::eof_comments`, but still belong
+                // in the comment table.
+                if self.comment_table.is_some() {
+                    self.get_comments();
+                } else if !self.comments.is_empty() {
+                    self.eof_comments = std::mem::take(&mut self.comments);
+                    for comment in &mut self.eof_comments {
+                        shift_loc(&mut comment.loc, self.offset as isize);
+                    }
+                }
+
+                None
+            }
+            Ok(Lexed::Group(_)) => unreachable!("no group was open"),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// Returns the first trivia token before the top-level `token`, and keeps
+    /// `token` to be returned after the rest of them, or returns `token` if
+    /// there is no trivia before it.
+    fn trivia_before(&mut self, token: TokenTree) -> TokenTree {
+        match self.next_trivia(token.loc().start) {
+            Some(trivia) => {
+                self.queued = Some(token);
+                trivia
+            }
+            None => token,
+        }
+    }
+
+    /// Records the exact source around `token`, whose leading trivia starts at
+    /// `trivia_start`, for lossless mode.
+    fn record_trivia(&mut self, token: &mut TokenTree, trivia_start: usize) {
+        let loc = token.loc().clone();
+
+        // the tokens of a group have already been lexed, so the trivia after
+        // the last of them starts at `self.trivia_start`.
+        let (source, mut trailing) = match token {
+            TokenTree::Group(_) => (
+                self.source[loc.start..loc.start + 1].to_string(),
+                self.source[self.trivia_start.min(loc.end)..loc.end].to_string(),
+            ),
+            _ => (self.source[loc.clone()].to_string(), String::new()),
+        };
+
+        self.trivia_start = loc.end;
+
+        // the trivia after the last token of the file isn't before any other
+        // token, so it is kept by the last token.  If skipping it fails, the
+        // error is found again when lexing the next token.
+        let state = self.checkpoint();
+        match self.skip() {
+            Ok(()) if self.is_eof() => {
+                trailing.push_str(&self.source[loc.end..]);
+                self.trivia_start = self.source.len();
+            }
+            Ok(()) => {}
+            Err(_) => self.rewind(state),
+        }
+
+        if let Some(trivia) = token.trivia_mut() {
+            *trivia = Some(Trivia {
+                leading: self.source[trivia_start..loc.start].to_string(),
+                source,
+                trailing,
+            });
         }
     }
 }
 
-impl Iterator for Lexer {
-    type Item = Result<TokenTree, Diagnostic<()>>;
+impl Iterator for Lexer<'_> {
+    type Item = Result<TokenTree, LexError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.tokenize()
+        let result = match self.peeked.take() {
+            Some(token) => Some(Ok(token)),
+            None => self.tokenize(),
+        };
+
+        if let Some(Ok(token)) = &result {
+            if !token.is_trivia() {
+                self.last_spacing = Some(token.spacing().clone());
+            }
+        }
+
+        result
     }
 }
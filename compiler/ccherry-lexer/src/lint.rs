@@ -0,0 +1,160 @@
+//! Lints which only need tokens, run after lexing.
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+use crate::token::{Loc, Punct, Spacing, StrSegment, TokenTree};
+
+/// A warning found by a lint.
+#[derive(Clone, Debug)]
+pub struct Lint {
+    /// The name of the lint which found this warning, such as
+    /// `ambiguous_operators`.
+    pub name: &'static str,
+
+    /// The warning.
+    pub diagnostic: Diagnostic<()>,
+
+    /// An edit which fixes the warning without changing the meaning of the
+    /// source, if there is one.
+    pub fix: Option<Fix>,
+}
+
+/// An edit to a source string, which replaces the text at `loc`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    /// The location of the text to replace.
+    pub loc: Loc,
+
+    /// The text to replace it with.
+    pub replacement: String,
+}
+
+/// Returns `source` after applying the fixes of `lints`.  A fix which
+/// overlaps with an earlier one is skipped, and can be applied by linting the
+/// fixed source again.
+pub fn apply_fixes(source: &str, lints: &[Lint]) -> String {
+    let mut fixes: Vec<&Fix> = lints.iter().filter_map(|lint| lint.fix.as_ref()).collect();
+    fixes.sort_by_key(|fix| (fix.loc.start, fix.loc.end));
+
+    let mut fixed = String::with_capacity(source.len());
+    let mut idx = 0;
+
+    for fix in fixes {
+        if fix.loc.start < idx {
+            continue;
+        }
+
+        fixed.push_str(&source[idx..fix.loc.start]);
+        fixed.push_str(&fix.replacement);
+        idx = fix.loc.end;
+    }
+
+    fixed.push_str(&source[idx..]);
+    fixed
+}
+
+/// Returns an `ambiguous_operators` warning for each visually ambiguous use of
+/// `-` in `tokens`, including the tokens inside of groups and string
+/// interpolations.
+///
+/// Because a `-` directly before a digit is part of a negative number literal,
+/// `a-1` is `a` followed by `-1`, rather than a subtraction, and `x <-1` is
+/// `x < -1`.  These are fixed by adding a space before the `-`.  An operator
+/// which ends in `-`, such as `=-` in `x =- 1`, is also reported, since it
+/// could have been meant as `x = -1`, but can't be fixed automatically.
+pub fn ambiguous_operators(tokens: &[TokenTree]) -> Vec<Lint> {
+    let mut lints = vec![];
+    lint_tokens(tokens, &mut lints);
+    lints
+}
+
+/// Adds the warnings for `tokens` to `lints`.
+fn lint_tokens(tokens: &[TokenTree], lints: &mut Vec<Lint>) {
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Group(group) => lint_tokens(&group.tokens, lints),
+            TokenTree::Str(str) => {
+                for segment in &str.value {
+                    if let StrSegment::Interpolation(group) = segment {
+                        lint_tokens(&group.tokens, lints);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let prev = match idx.checked_sub(1) {
+            Some(prev) => &tokens[prev],
+            None => continue,
+        };
+
+        let negative = match token {
+            TokenTree::Int(int) => int.negative,
+            TokenTree::Float(float) => float.value.is_sign_negative(),
+            _ => false,
+        };
+
+        if negative && *prev.spacing() == Spacing::None {
+            let minus = token.loc().start;
+
+            lints.push(Lint {
+                name: "ambiguous_operators",
+                diagnostic: Diagnostic::warning()
+                    .with_labels(vec![
+                        Label::primary((), minus..minus + 1)
+                            .with_message("this `-` is part of a negative number literal"),
+                        Label::secondary((), prev.loc().clone())
+                            .with_message("so this isn't followed by an operator"),
+                    ])
+                    .with_message("ambiguous negative number literal")
+                    .with_notes(vec![
+                        "add a space before the `-` to keep the negative literal, or after it to make it an operator".to_string(),
+                    ]),
+                fix: Some(Fix {
+                    loc: minus..minus,
+                    replacement: " ".to_string(),
+                }),
+            });
+        }
+
+        if matches!(
+            token,
+            TokenTree::Punct(Punct {
+                value: '-',
+                spacing: Spacing::Whitespace | Spacing::LineBreak,
+                ..
+            })
+        ) && *prev.spacing() == Spacing::Joint
+        {
+            // find the start of the multi-character operator ending in `-`.
+            let mut start = idx;
+            while start > 0 && *tokens[start - 1].spacing() == Spacing::Joint {
+                start -= 1;
+            }
+
+            let operator: String = tokens[start..=idx]
+                .iter()
+                .filter_map(|token| match token {
+                    TokenTree::Punct(punct) => Some(punct.value),
+                    _ => None,
+                })
+                .collect();
+
+            lints.push(Lint {
+                name: "ambiguous_operators",
+                diagnostic: Diagnostic::warning()
+                    .with_labels(vec![Label::primary(
+                        (),
+                        tokens[start].loc().start..token.loc().end,
+                    )
+                    .with_message(format!("this is read as the operator `{}`", operator))])
+                    .with_message(format!("ambiguous operator `{}`", operator))
+                    .with_notes(vec![format!(
+                        "if the `-` negates what comes after it, write `{} -` instead",
+                        &operator[..operator.len() - 1]
+                    )]),
+                fix: None,
+            });
+        }
+    }
+}
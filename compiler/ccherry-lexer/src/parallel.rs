@@ -0,0 +1,63 @@
+//! Lexing many independent files at once on a thread pool, for build tools
+//! which lex whole source trees.
+
+use codespan_reporting::diagnostic::Diagnostic;
+use rayon::prelude::*;
+
+use crate::token::{Comment, TokenTree};
+use crate::{Lexer, LexerConfig};
+
+/// The result of lexing one of the files passed to [`lex_files`].
+#[derive(Clone, Debug)]
+pub struct LexResult<FileId> {
+    /// The file these tokens were lexed from.
+    pub file_id: FileId,
+
+    /// Every token in the file, with errors replaced by
+    /// [`TokenTree::Error`] tokens.
+    pub tokens: Vec<TokenTree>,
+
+    /// The comments after the last token in the file, such as every comment
+    /// in a file with only comments.
+    pub eof_comments: Vec<Comment>,
+
+    /// The diagnostics of every error found in the file, with their labels in
+    /// `file_id`.
+    pub diagnostics: Vec<Diagnostic<FileId>>,
+}
+
+/// Lexes every file in `files` with the default configuration, in parallel,
+/// like [`Lexer::tokenize_all`].  Each file is a file id along with its
+/// source, and the results are in the same order as `files`.
+pub fn lex_files<FileId>(files: &[(FileId, &str)]) -> Vec<LexResult<FileId>>
+where
+    FileId: Clone + Send + Sync,
+{
+    lex_files_with_config(files, &LexerConfig::new())
+}
+
+/// Lexes every file in `files` like [`lex_files`], with the provided
+/// configuration.
+pub fn lex_files_with_config<FileId>(
+    files: &[(FileId, &str)],
+    config: &LexerConfig,
+) -> Vec<LexResult<FileId>>
+where
+    FileId: Clone + Send + Sync,
+{
+    files
+        .par_iter()
+        .map(|(file_id, source)| {
+            let (tokens, eof_comments, diagnostics) = Lexer::new(source)
+                .with_config(config.clone())
+                .tokenize_all(file_id.clone());
+
+            LexResult {
+                file_id: file_id.clone(),
+                tokens,
+                eof_comments,
+                diagnostics,
+            }
+        })
+        .collect()
+}
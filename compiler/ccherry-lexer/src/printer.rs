@@ -0,0 +1,433 @@
+//! Rendering tokens back into Cherry source code.
+
+use std::fmt;
+
+use crate::token::{
+    Comment, CommentKind, Float, FloatKind, Group, IntKind, Spacing, Str, StrKind, StrSegment,
+    TokenTree, TriviaKind,
+};
+use crate::Lexer;
+
+/// Something which can be rendered back into Cherry source code, such as the
+/// output of a macro expansion.
+///
+/// The source is valid Cherry which lexes to the same tokens, except for their
+/// locations, and is not formatted beyond honoring each token's [`Spacing`]:
+/// [`Spacing::Whitespace`] is a single space and [`Spacing::LineBreak`] is a
+/// single line break.  A space is also added between tokens with
+/// [`Spacing::None`] if they would otherwise run together, such as two
+/// identifiers created by a macro.
+///
+/// [`TokenTree::Error`] tokens have no source, and are rendered as nothing.
+/// [`TokenTree::Trivia`] tokens are rendered like spacing, as a single space or
+/// line break, or as their comment, and a shebang line as nothing.  No spacing
+/// is added next to them, since they are what was between the other tokens.
+///
+/// Tokens lexed in lossless mode (see [`Lexer::with_lossless`]) are rendered
+/// from their [`Trivia`](crate::Trivia) instead, which gives back exactly the
+/// source they were lexed from.
+pub trait ToSource {
+    /// Appends the source code of `self` to `out`.
+    fn write_source(&self, out: &mut String);
+
+    /// Returns the source code of `self`.
+    fn to_source(&self) -> String {
+        let mut out = String::new();
+        self.write_source(&mut out);
+        out
+    }
+
+    /// Returns an adapter which displays the source code of `self`, such as
+    /// for quoting a list of tokens in a diagnostic.
+    fn display(&self) -> DisplaySource<'_, Self> {
+        DisplaySource(self)
+    }
+}
+
+/// Displays the source code of tokens, created by [`ToSource::display`].
+#[derive(Clone, Copy, Debug)]
+pub struct DisplaySource<'a, T: ?Sized>(&'a T);
+
+impl<T: ToSource + ?Sized> fmt::Display for DisplaySource<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_source())
+    }
+}
+
+/// Displays the source of a single token, such as `"foo"`, `123` or `;`, for
+/// diagnostics such as "expected `;`, found `{}`".
+///
+/// A group is displayed as its opening delimiter, where it starts, and a
+/// [`TokenTree::Error`] as `<error>`, since it has no source.  The comments
+/// and trivia around a token aren't displayed, but a [`TokenTree::Trivia`] is
+/// displayed like its source, as a space, a line break or its comment.
+/// [`ToSource::display`] displays a token with the tokens in it instead.
+impl fmt::Display for TokenTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenTree::Group(token) => write!(f, "{}", token.delimiter.open()),
+            TokenTree::Error(_) => f.write_str("<error>"),
+            token => {
+                let mut out = String::new();
+                write_token(token, &mut out);
+                f.write_str(&out)
+            }
+        }
+    }
+}
+
+impl ToSource for TokenTree {
+    fn write_source(&self, out: &mut String) {
+        if let Some(trivia) = self.trivia() {
+            out.push_str(&trivia.leading);
+            out.push_str(&trivia.source);
+            if let TokenTree::Group(token) = self {
+                token.tokens.write_source(out);
+            }
+            out.push_str(&trivia.trailing);
+            return;
+        }
+
+        for comment in self.comments() {
+            write_comment(comment, out);
+        }
+
+        write_token(self, out);
+
+        for comment in self.trailing_comments() {
+            out.push(' ');
+            write_trailing_comment(comment, out);
+        }
+    }
+}
+
+impl ToSource for [TokenTree] {
+    fn write_source(&self, out: &mut String) {
+        let at_start = out.is_empty();
+
+        for (idx, token) in self.iter().enumerate() {
+            let start = out.len();
+            token.write_source(out);
+
+            let next = match self.get(idx + 1) {
+                Some(next) => next,
+                None => break,
+            };
+
+            // the leading trivia of the next token already includes
+            // everything between them, and so do trivia tokens.
+            if next.trivia().is_some() || token.is_trivia() || next.is_trivia() {
+                continue;
+            }
+
+            match token.spacing() {
+                Spacing::Whitespace => out.push(' '),
+                Spacing::LineBreak => out.push('\n'),
+                Spacing::Joint => {}
+                Spacing::None => {
+                    let last = out[start..].chars().next_back();
+                    let first = first_char(next);
+
+                    if let (Some(last), Some(first)) = (last, first) {
+                        if runs_together(last, first) {
+                            out.push(' ');
+                        }
+                    }
+                }
+            }
+        }
+
+        // `#!` at the start of a source is a shebang line, rather than a `#`
+        // and a `!`.
+        let lossless = self.first().is_some_and(|token| token.trivia().is_some());
+        if at_start && !lossless && out.starts_with("#!") {
+            out.insert(0, ' ');
+        }
+    }
+}
+
+/// Appends the source code of `token` to `out`, without its comments or
+/// trivia.
+pub(crate) fn write_token(token: &TokenTree, out: &mut String) {
+    match token {
+        TokenTree::Iden(token) => {
+            if token.raw {
+                out.push_str("r#");
+            }
+            out.push_str(&token.value);
+        }
+        TokenTree::Keyword(token) => out.push_str(&token.value),
+        TokenTree::Punct(token) => out.push(token.value),
+        TokenTree::Int(token) => {
+            if token.negative {
+                out.push('-');
+            }
+            out.push_str(match token.kind {
+                IntKind::Decimal => "",
+                IntKind::Hexadecimal => "0x",
+                IntKind::Octal => "0o",
+                IntKind::Binary => "0b",
+            });
+            out.push_str(&token.digits);
+        }
+        TokenTree::Float(token) => write_float(token, out),
+        TokenTree::Str(token) => write_str(token, out),
+        TokenTree::Char(token) => {
+            out.push('\'');
+            write_escaped(token.value, '\'', out);
+            out.push('\'');
+        }
+        TokenTree::Group(token) => {
+            out.push(token.delimiter.open());
+            write_group_contents(token, out);
+            out.push(token.delimiter.close());
+        }
+        TokenTree::Error(_) => {}
+        TokenTree::Trivia(token) => match &token.kind {
+            TriviaKind::Whitespace => out.push(' '),
+            TriviaKind::LineBreak => out.push('\n'),
+            TriviaKind::Comment(comment) => write_trailing_comment(comment, out),
+            TriviaKind::Shebang => {}
+        },
+    }
+}
+
+/// Appends the tokens of `group` and the comments at its end to `out`, without
+/// its delimiters.
+pub(crate) fn write_group_contents(group: &Group, out: &mut String) {
+    group.tokens.write_source(out);
+
+    let last = match group.tokens.last() {
+        Some(last) => last,
+        None => {
+            for comment in group.inner_comments.iter() {
+                write_comment(comment, out);
+            }
+            return;
+        }
+    };
+
+    // a line comment after the last token would swallow the closing
+    // delimiter, and the comments at the end of the group go after the
+    // spacing of its last token, so they don't end up in its trailing comment.
+    let line_comment = last.trailing_comments().last().is_some_and(|comment| {
+        matches!(
+            comment.kind,
+            CommentKind::Line | CommentKind::Doc | CommentKind::InnerDoc
+        )
+    });
+    if line_comment || (!group.inner_comments.is_empty() && *last.spacing() == Spacing::LineBreak) {
+        out.push('\n');
+    } else if !group.inner_comments.is_empty() {
+        out.push(' ');
+    }
+
+    for comment in group.inner_comments.iter() {
+        write_comment(comment, out);
+    }
+}
+
+/// Appends the source of a float literal to `out`, in the form it was written
+/// in, so it lexes as the same kind of float again.  A value which can't be
+/// written in that form, such as `1.5` with [`FloatKind::TrailingDot`], is
+/// written like a [`FloatKind::Decimal`] float.
+fn write_float(token: &Float, out: &mut String) {
+    if token.value.is_sign_negative() {
+        out.push('-');
+    }
+
+    let value = token.value.abs();
+
+    // `{:e}` is the shortest exponent form which parses to the same value,
+    // such as `1.5e3`, and is split into its digits and exponent.
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let fraction = mantissa
+        .split_once('.')
+        .map_or("", |(_, fraction)| fraction);
+    let digits = mantissa.replace('.', "");
+
+    match token.kind {
+        FloatKind::Exponent => {
+            out.push_str(&format!("{}e{}", digits, exponent - fraction.len() as i32));
+        }
+        FloatKind::LeadingDot if (-6..0).contains(&exponent) => {
+            out.push('.');
+            out.push_str(&"0".repeat((-exponent - 1) as usize));
+            out.push_str(&digits);
+        }
+        FloatKind::LeadingDot => {
+            out.push_str(&format!(".{}e{}", digits, exponent + 1));
+        }
+        FloatKind::TrailingDot if value.fract() == 0.0 => {
+            out.push_str(&format!("{}.", value));
+        }
+        FloatKind::Hexadecimal => {
+            let bits = value.to_bits();
+            let biased = (bits >> 52) as i32;
+            let fraction = format!("{:013x}", bits & ((1 << 52) - 1));
+            let fraction = fraction.trim_end_matches('0');
+
+            // subnormals have no implicit leading `1`.
+            let (leading, exponent) = match (value == 0.0, biased) {
+                (true, _) => (0, 0),
+                (false, 0) => (0, -1022),
+                (false, biased) => (1, biased - 1023),
+            };
+
+            out.push_str(&format!("0x{}", leading));
+            if !fraction.is_empty() {
+                out.push('.');
+                out.push_str(fraction);
+            }
+            out.push_str(&format!("p{}", exponent));
+        }
+        FloatKind::Decimal | FloatKind::TrailingDot => {
+            // `Debug` writes large and small values with an exponent, such as
+            // `1e20`, which needs a `.` to be a decimal float.
+            let debug = format!("{:?}", value);
+            match debug.split_once('e') {
+                Some((mantissa, exponent)) if !mantissa.contains('.') => {
+                    out.push_str(&format!("{}.0e{}", mantissa, exponent));
+                }
+                _ => out.push_str(&debug),
+            }
+        }
+    }
+}
+
+/// Returns the first character of the source of `token`.
+fn first_char(token: &TokenTree) -> Option<char> {
+    if !token.comments().is_empty() {
+        return Some('/');
+    }
+
+    match token {
+        TokenTree::Iden(token) if token.raw => Some('r'),
+        TokenTree::Iden(token) => token.value.chars().next(),
+        TokenTree::Keyword(token) => token.value.chars().next(),
+        TokenTree::Punct(token) => Some(token.value),
+        TokenTree::Int(token) if token.negative => Some('-'),
+        TokenTree::Int(token) if token.kind != IntKind::Decimal => Some('0'),
+        TokenTree::Int(token) => token.digits.chars().next(),
+        TokenTree::Float(token) if token.value.is_sign_negative() => Some('-'),
+        TokenTree::Float(token) if token.kind == FloatKind::LeadingDot => Some('.'),
+        TokenTree::Float(_) => Some('0'),
+        TokenTree::Str(token) if matches!(token.kind, StrKind::Raw(_)) => Some('r'),
+        TokenTree::Str(_) => Some('"'),
+        TokenTree::Char(_) => Some('\''),
+        TokenTree::Group(token) => Some(token.delimiter.open()),
+        TokenTree::Error(_) | TokenTree::Trivia(_) => None,
+    }
+}
+
+/// Returns whether or not a token ending with `last` and a token starting with
+/// `first` would be lexed as something else if there was nothing between
+/// them, such as `a` and `b` becoming `ab`, `-` and `1` becoming `-1`, or `1`,
+/// `.` and `5` becoming `1.5`.
+fn runs_together(last: char, first: char) -> bool {
+    let is_word = |char: char| char.is_alphanumeric() || char == '_';
+
+    (is_word(last) && (is_word(first) || first == '"' || first == '\''))
+        || (Lexer::is_punct(last) && Lexer::is_punct(first))
+        || ((last == '-' || last == '.') && Lexer::is_digit(first))
+        || (Lexer::is_digit(last) && first == '.')
+        || (last == '/' && first == '*')
+}
+
+/// Appends the source of a comment to `out`.  Line comments end with a line
+/// break, and block comments with a space.
+fn write_comment(comment: &Comment, out: &mut String) {
+    match comment.kind {
+        CommentKind::Line => out.push_str("//"),
+        CommentKind::Doc => out.push_str("///"),
+        CommentKind::InnerDoc => out.push_str("//!"),
+        CommentKind::Block => out.push_str("/*"),
+        CommentKind::BlockDoc => out.push_str("/**"),
+        CommentKind::InnerBlockDoc => out.push_str("/*!"),
+    }
+
+    if !comment.value.is_empty() {
+        out.push(' ');
+        out.push_str(&comment.value);
+    }
+
+    match comment.kind {
+        CommentKind::Line | CommentKind::Doc | CommentKind::InnerDoc => out.push('\n'),
+        CommentKind::Block | CommentKind::BlockDoc | CommentKind::InnerBlockDoc => {
+            out.push_str(" */ ")
+        }
+    }
+}
+
+/// Appends the source of a comment after a token to `out`.  The line break
+/// after it is the spacing of the token.
+fn write_trailing_comment(comment: &Comment, out: &mut String) {
+    write_comment(comment, out);
+    out.truncate(out.trim_end().len());
+}
+
+/// Appends the source of a string literal to `out`, escaping its text.
+fn write_str(token: &Str, out: &mut String) {
+    if let StrKind::Raw(hashes) = token.kind {
+        let hashes = "#".repeat(hashes);
+        out.push('r');
+        out.push_str(&hashes);
+        out.push('"');
+        out.push_str(token.text().unwrap_or_default());
+        out.push('"');
+        out.push_str(&hashes);
+        return;
+    }
+
+    // the line breaks after the opening quotes and before the closing quotes
+    // of a triple-quoted string aren't part of its value.
+    let quote = match token.kind {
+        StrKind::Triple => "\"\"\"\n",
+        _ => "\"",
+    };
+    out.push_str(quote);
+
+    for segment in &token.value {
+        match segment {
+            StrSegment::Text(text) => {
+                for char in text.chars() {
+                    if char == '\n' && token.kind == StrKind::Triple {
+                        out.push('\n');
+                    } else {
+                        write_escaped(char, '"', out);
+                    }
+                }
+            }
+            StrSegment::Interpolation(group) => {
+                out.push_str("${");
+                write_group_contents(group, out);
+                out.push('}');
+            }
+        }
+    }
+
+    out.push_str(&quote.chars().rev().collect::<String>());
+}
+
+/// Appends `char` to `out`, escaped if it has to be inside of a literal
+/// delimited by `quote`.
+fn write_escaped(char: char, quote: char, out: &mut String) {
+    match char {
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        '\0' => out.push_str("\\0"),
+        '\\' | '$' => {
+            out.push('\\');
+            out.push(char);
+        }
+        char if char == quote => {
+            out.push('\\');
+            out.push(char);
+        }
+        char if char.is_control() => out.push_str(&format!("\\u{{{:x}}}", char as u32)),
+        char => out.push(char),
+    }
+}
@@ -0,0 +1,234 @@
+//! Converting tokens to and from [`proc_macro2`] tokens, so Rust procedural
+//! macros and code generators built with `quote` can produce and consume
+//! Cherry tokens.
+//!
+//! Rust tokens have no comments, and their spans can't be turned into byte
+//! ranges, so converted tokens lose their comments and trivia in one
+//! direction, and their locations in the other.
+
+use proc_macro2 as rust;
+use unicode_xid::UnicodeXID;
+
+use crate::printer::{write_group_contents, ToSource};
+use crate::token::{
+    Delimiter, Error, Group, Iden, Keyword, Punct, Spacing, Str, StrSegment, TokenTree,
+};
+use crate::Lexer;
+
+/// The identifiers which can't be raw identifiers in Rust.
+const NOT_RAW: [&str; 5] = ["_", "crate", "self", "Self", "super"];
+
+/// Converts a Cherry token into a Rust token, at
+/// [`Span::call_site`](proc_macro2::Span::call_site).
+///
+/// Keywords become identifiers, numbers and characters become literals, and
+/// strings become string literals with the same value.  Rust splits a
+/// negative number into a `-` and the number when it is added to a token
+/// stream.  Rust strings have no interpolations, so an interpolation is
+/// written into the string as `${...}`, which is an interpolation again when
+/// converted back, and a `$` in the text of the string is written as
+/// `\u{24}`, so it isn't.  A [`TokenTree::Error`] has no source, and becomes
+/// an empty group with no delimiters, and so does a [`TokenTree::Trivia`],
+/// since Rust tokens have no trivia, and an identifier which isn't a Rust
+/// identifier, such as `foo-bar` lexed with
+/// [`LexerConfig::with_iden_continue`](crate::LexerConfig::with_iden_continue).
+///
+/// # Panics
+///
+/// Panics if the token is a punctuator which Rust doesn't have, which can only
+/// be lexed with [`LexerConfig::with_puncts`](crate::LexerConfig::with_puncts).
+impl From<TokenTree> for rust::TokenTree {
+    fn from(token: TokenTree) -> Self {
+        match token {
+            TokenTree::Iden(Iden { ref value, .. })
+            | TokenTree::Keyword(Keyword { ref value, .. })
+                if !is_rust_ident(value) =>
+            {
+                rust::Group::new(rust::Delimiter::None, rust::TokenStream::new()).into()
+            }
+            TokenTree::Iden(token) if token.raw && !NOT_RAW.contains(&&*token.value) => {
+                rust::Ident::new_raw(&token.value, rust::Span::call_site()).into()
+            }
+            TokenTree::Iden(token) => {
+                rust::Ident::new(&token.value, rust::Span::call_site()).into()
+            }
+            TokenTree::Keyword(token) => {
+                rust::Ident::new(&token.value, rust::Span::call_site()).into()
+            }
+            TokenTree::Punct(token) => {
+                let spacing = match token.spacing {
+                    Spacing::Joint => rust::Spacing::Joint,
+                    _ => rust::Spacing::Alone,
+                };
+                rust::Punct::new(token.value, spacing).into()
+            }
+            TokenTree::Int(_) => {
+                // Cherry's integers are valid Rust integers, negative or not.
+                let source = token.stripped().to_source();
+                let literal: rust::Literal = source
+                    .parse()
+                    .expect("a Cherry integer is a valid Rust literal");
+                literal.into()
+            }
+            // Rust has no hexadecimal floats or floats starting with a `.`,
+            // so floats are written as decimal floats.
+            TokenTree::Float(token) => rust::Literal::f64_unsuffixed(token.value).into(),
+            TokenTree::Str(token) => {
+                let literal: rust::Literal = str_source(&token)
+                    .parse()
+                    .expect("an escaped string is a valid Rust literal");
+                literal.into()
+            }
+            TokenTree::Char(token) => rust::Literal::character(token.value).into(),
+            TokenTree::Group(group) => rust::Group::new(
+                match group.delimiter {
+                    Delimiter::Paren => rust::Delimiter::Parenthesis,
+                    Delimiter::Bracket => rust::Delimiter::Bracket,
+                    Delimiter::Brace => rust::Delimiter::Brace,
+                },
+                group.tokens.into_iter().collect(),
+            )
+            .into(),
+            TokenTree::Error(_) | TokenTree::Trivia(_) => {
+                rust::Group::new(rust::Delimiter::None, rust::TokenStream::new()).into()
+            }
+        }
+    }
+}
+
+/// Converts a Rust token into a Cherry token, with the location `0..0` and
+/// [`Spacing::None`], like a [stripped](TokenTree::stripped) token, unless it
+/// is a joint punctuator.
+///
+/// Identifiers, including Rust's keywords, become [`TokenTree::Iden`]s, and
+/// literals are lexed as Cherry tokens.  A literal which isn't a single Cherry
+/// token, such as `1u8` or `b"bytes"`, becomes a [`TokenTree::Error`].  Cherry
+/// has no groups without delimiters, so they become parentheses.
+impl From<rust::TokenTree> for TokenTree {
+    fn from(token: rust::TokenTree) -> Self {
+        match token {
+            rust::TokenTree::Ident(ident) => {
+                let value = ident.to_string();
+                let (value, raw) = match value.strip_prefix("r#") {
+                    Some(value) => (value.to_string(), true),
+                    None => (value, false),
+                };
+
+                TokenTree::Iden(Iden {
+                    loc: 0..0,
+                    value,
+                    raw,
+                    comments: Box::default(),
+                    spacing: Spacing::None,
+                    trailing_comments: Box::default(),
+                    trivia: None,
+                })
+            }
+            rust::TokenTree::Punct(punct) => TokenTree::Punct(Punct {
+                loc: 0..0,
+                value: punct.as_char(),
+                comments: Box::default(),
+                spacing: match punct.spacing() {
+                    rust::Spacing::Joint => Spacing::Joint,
+                    rust::Spacing::Alone => Spacing::None,
+                },
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+            rust::TokenTree::Literal(literal) => {
+                let source = literal.to_string();
+                let mut lexer = Lexer::new(&source);
+
+                match (lexer.next(), lexer.next()) {
+                    (Some(Ok(token)), None) if !matches!(token, TokenTree::Group(_)) => {
+                        token.stripped()
+                    }
+                    _ => TokenTree::Error(Error {
+                        loc: 0..0,
+                        comments: Box::default(),
+                        spacing: Spacing::None,
+                        trailing_comments: Box::default(),
+                        trivia: None,
+                    }),
+                }
+            }
+            rust::TokenTree::Group(group) => TokenTree::Group(Group {
+                loc: 0..0,
+                delimiter: match group.delimiter() {
+                    rust::Delimiter::Parenthesis | rust::Delimiter::None => Delimiter::Paren,
+                    rust::Delimiter::Bracket => Delimiter::Bracket,
+                    rust::Delimiter::Brace => Delimiter::Brace,
+                },
+                tokens: group.stream().into_iter().map(TokenTree::from).collect(),
+                inner_comments: Box::default(),
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            }),
+        }
+    }
+}
+
+/// Collects Cherry tokens into a Rust token stream, converting each of them
+/// into a Rust token, and leaving out [`TokenTree::Trivia`].  A Rust token
+/// stream is converted back by converting each of its tokens.
+impl FromIterator<TokenTree> for rust::TokenStream {
+    fn from_iter<I: IntoIterator<Item = TokenTree>>(tokens: I) -> Self {
+        tokens
+            .into_iter()
+            .filter(|token| !token.is_trivia())
+            .map(rust::TokenTree::from)
+            .collect()
+    }
+}
+
+/// Returns whether or not `value` is a Rust identifier, which
+/// [`rust::Ident::new`] would accept.
+fn is_rust_ident(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|char| char == '_' || char.is_xid_start())
+        && chars.all(UnicodeXID::is_xid_continue)
+}
+
+/// Returns the source of a Rust string literal with the value of `token`,
+/// with its interpolations written as `${...}`, and the `$`s in its text
+/// escaped, so they don't start an interpolation when lexed by Cherry.
+fn str_source(token: &Str) -> String {
+    let mut source = String::from('"');
+
+    for segment in &token.value {
+        match segment {
+            StrSegment::Text(text) => {
+                for char in text.chars() {
+                    match char {
+                        '$' => source.push_str("\\u{24}"),
+                        char => escape(char, &mut source),
+                    }
+                }
+            }
+            StrSegment::Interpolation(group) => {
+                let mut tokens = String::new();
+                write_group_contents(group, &mut tokens);
+
+                source.push_str("${");
+                tokens.chars().for_each(|char| escape(char, &mut source));
+                source.push('}');
+            }
+        }
+    }
+
+    source.push('"');
+    source
+}
+
+/// Appends `char` to the source of a Rust string literal, escaped like
+/// [`rust::Literal::string`] does.
+fn escape(char: char, source: &mut String) {
+    match char {
+        '\'' => source.push(char),
+        char => source.extend(char.escape_debug()),
+    }
+}
@@ -0,0 +1,183 @@
+//! Lexing a source again after an edit, reusing the tokens the edit didn't
+//! touch, for editors which relex on every keystroke.
+
+use crate::token::{Comment, Group, Loc, StrSegment, TokenTree, TriviaKind};
+use crate::Lexer;
+
+/// An edit to a source string, such as a keystroke in an editor, which
+/// replaces the text at `loc` with `text`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    /// The location of the replaced text, in the source before the edit.
+    pub loc: Loc,
+
+    /// The text it is replaced with.
+    pub text: String,
+}
+
+impl TextEdit {
+    /// Returns `source` after applying this edit.
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.text.len());
+        edited.push_str(&source[..self.loc.start]);
+        edited.push_str(&self.text);
+        edited.push_str(&source[self.loc.end..]);
+        edited
+    }
+
+    /// Returns how many bytes longer the source is after this edit.
+    fn delta(&self) -> isize {
+        self.text.len() as isize - self.loc.len() as isize
+    }
+}
+
+impl Lexer<'_> {
+    /// Returns the tokens of this lexer's source, which is the source
+    /// `old_tokens` were lexed from after applying `edit`.
+    ///
+    /// Only the tokens around the edit are lexed again, in recovery mode: the
+    /// lexer starts at the top-level token before the line the edit starts on,
+    /// since the edit may change how anything on that line lexes, such as
+    /// joining a raw string prefix like `r##` with a new quote, and the
+    /// spacing of the token before it.  It stops as soon as it produces a
+    /// token which is the same as an old one after the edit.  The tokens
+    /// before that are reused as they are, and the tokens after it have their
+    /// locations shifted by the length of the edit.
+    ///
+    /// The errors in the relexed part are recorded in this lexer, and the
+    /// errors in the rest of the source are unchanged.  The comment table is
+    /// not supported, since comments before the edit would not be collected.
+    pub fn relex(&mut self, old_tokens: &[TokenTree], edit: &TextEdit) -> Vec<TokenTree> {
+        let delta = edit.delta();
+
+        // the first token which touches the line the edit starts on, and the
+        // one before it.  Nothing before that line can lex differently, since
+        // a token which spans a line break into it is lexed again whole.
+        let line_start = self.source[..edit.loc.start]
+            .rfind('\n')
+            .map_or(0, |idx| idx + 1);
+        let damaged = old_tokens
+            .iter()
+            .position(|token| token.loc().end >= line_start)
+            .unwrap_or(old_tokens.len());
+        let first = damaged.saturating_sub(1);
+
+        let mut tokens = old_tokens[..first].to_vec();
+        self.idx = match old_tokens.get(first) {
+            Some(token) if first > 0 => leading_start(token),
+            _ => 0,
+        };
+        self.trivia_start = tokens.last().map_or(0, |token| token.loc().end);
+        self.comments.clear();
+        self.peeked = None;
+        self.recovery = true;
+
+        // the old tokens after the edit, any of which the lexer may get back
+        // in sync with.
+        let mut next_old = damaged;
+
+        while let Some(result) = self.tokenize() {
+            let token = match result {
+                Ok(token) => token,
+                Err(error) => {
+                    self.errors.push(error);
+                    continue;
+                }
+            };
+
+            while let Some(old) = old_tokens.get(next_old) {
+                let start = old.loc().start as isize + delta;
+                if old.loc().start < edit.loc.end || start < token.loc().start as isize {
+                    next_old += 1;
+                    continue;
+                }
+
+                if start == token.loc().start as isize {
+                    let mut shifted = old.clone();
+                    shift(&mut shifted, delta);
+
+                    if shifted == token {
+                        tokens.push(token);
+                        tokens.extend(old_tokens[next_old + 1..].iter().map(|old| {
+                            let mut old = old.clone();
+                            shift(&mut old, delta);
+                            old
+                        }));
+
+                        return tokens;
+                    }
+                }
+
+                break;
+            }
+
+            tokens.push(token);
+        }
+
+        tokens
+    }
+}
+
+/// Returns where the source of `token` starts, including the comments before
+/// it.
+fn leading_start(token: &TokenTree) -> usize {
+    token
+        .comments()
+        .first()
+        .map_or(token.loc().start, |comment| comment.loc.start)
+}
+
+/// Moves `loc` by `delta` bytes.
+pub(crate) fn shift_loc(loc: &mut Loc, delta: isize) {
+    loc.start = (loc.start as isize + delta) as usize;
+    loc.end = (loc.end as isize + delta) as usize;
+}
+
+/// Moves the comments in `comments` by `delta` bytes.
+fn shift_comments(comments: &mut [Comment], delta: isize) {
+    for comment in comments {
+        shift_loc(&mut comment.loc, delta);
+    }
+}
+
+/// Moves every location in `group`, including the tokens in it, by `delta`
+/// bytes.
+fn shift_group(group: &mut Group, delta: isize) {
+    shift_loc(&mut group.loc, delta);
+    shift_comments(&mut group.comments, delta);
+    shift_comments(&mut group.inner_comments, delta);
+    shift_comments(&mut group.trailing_comments, delta);
+
+    for token in &mut group.tokens {
+        shift(token, delta);
+    }
+}
+
+/// Moves every location in `token`, including the tokens nested in it, by
+/// `delta` bytes.
+pub(crate) fn shift(token: &mut TokenTree, delta: isize) {
+    match token {
+        TokenTree::Group(group) => return shift_group(group, delta),
+        TokenTree::Str(str) => {
+            for segment in &mut str.value {
+                if let StrSegment::Interpolation(group) = segment {
+                    shift_group(group, delta);
+                }
+            }
+        }
+        TokenTree::Trivia(trivia) => {
+            if let TriviaKind::Comment(comment) = &mut trivia.kind {
+                shift_loc(&mut comment.loc, delta);
+            }
+        }
+        _ => {}
+    }
+
+    shift_loc(token.loc_mut(), delta);
+    if let Some(comments) = token.comments_mut() {
+        shift_comments(comments, delta);
+    }
+    if let Some(comments) = token.trailing_comments_mut() {
+        shift_comments(comments, delta);
+    }
+}
@@ -0,0 +1,151 @@
+//! A common interface for everything which provides tokens to the parser, so
+//! it doesn't depend on where they come from: the lexer, tokens lexed earlier,
+//! or the output of a macro expansion.
+
+use std::iter::Peekable;
+use std::mem;
+use std::vec::IntoIter;
+
+use crate::cursor::TokenCursor;
+use crate::token::{Loc, Spacing, TokenTree};
+use crate::Lexer;
+
+/// A stream of top-level token trees, which can be looked ahead into by one
+/// token.
+pub trait TokenSource {
+    /// Consumes the next token, and returns it, or `None` once every token
+    /// has been consumed.
+    fn next_token(&mut self) -> Option<TokenTree>;
+
+    /// Returns the next token, without consuming it.
+    fn peek_token(&mut self) -> Option<&TokenTree>;
+
+    /// Returns the location of the next token, or an empty location at the
+    /// end of the input once every token has been consumed.
+    fn span(&mut self) -> Loc;
+
+    /// Returns the spacing after the last token consumed, if any token has
+    /// been consumed yet.
+    fn last_spacing(&self) -> Option<&Spacing>;
+}
+
+/// A lexer provides the tokens of its source.  The tokens are lexed in
+/// recovery mode, whether or not it is enabled, so errors are recorded in the
+/// lexer and replaced with [`TokenTree::Error`] tokens, and can be reported
+/// with [`Lexer::take_errors`] afterwards.
+impl TokenSource for Lexer<'_> {
+    fn next_token(&mut self) -> Option<TokenTree> {
+        self.peek_token()?;
+        self.next().and_then(Result::ok)
+    }
+
+    fn peek_token(&mut self) -> Option<&TokenTree> {
+        if self.peeked.is_none() {
+            let recovery = mem::replace(&mut self.recovery, true);
+            self.peeked = match self.tokenize() {
+                Some(Ok(token)) => Some(token),
+                Some(Err(error)) => {
+                    self.errors.push(error);
+                    None
+                }
+                None => None,
+            };
+            self.recovery = recovery;
+        }
+
+        self.peeked.as_ref()
+    }
+
+    fn span(&mut self) -> Loc {
+        let end = self.offset + self.source.len();
+        self.peek_token()
+            .map_or(end..end, |token| token.loc().clone())
+    }
+
+    fn last_spacing(&self) -> Option<&Spacing> {
+        self.last_spacing.as_ref()
+    }
+}
+
+/// A cursor replays the tokens it is over, such as tokens lexed earlier or
+/// the tokens of a group.  The tokens are cloned as they are consumed.
+impl TokenSource for TokenCursor<'_> {
+    fn next_token(&mut self) -> Option<TokenTree> {
+        self.next().cloned()
+    }
+
+    fn peek_token(&mut self) -> Option<&TokenTree> {
+        self.peek()
+    }
+
+    fn span(&mut self) -> Loc {
+        self.peek().map_or_else(
+            || {
+                let end = self.end();
+                end..end
+            },
+            |token| token.loc().clone(),
+        )
+    }
+
+    fn last_spacing(&self) -> Option<&Spacing> {
+        let idx = self.position().checked_sub(1)?;
+        Some(self.tokens()[idx].spacing())
+    }
+}
+
+/// Replays a list of tokens which is owned, such as the output of a macro
+/// expansion, or tokens built by a test.
+#[derive(Clone, Debug)]
+pub struct TokenReplay {
+    /// The tokens which haven't been consumed yet.
+    tokens: Peekable<IntoIter<TokenTree>>,
+
+    /// The spacing of the last token consumed.
+    last_spacing: Option<Spacing>,
+
+    /// The end of the last token consumed, which is where the input ends
+    /// once every token has been consumed.
+    end: usize,
+}
+
+impl TokenReplay {
+    /// Creates a source which replays `tokens`.
+    pub fn new(tokens: Vec<TokenTree>) -> Self {
+        Self {
+            tokens: tokens.into_iter().peekable(),
+            last_spacing: None,
+            end: 0,
+        }
+    }
+}
+
+impl From<Vec<TokenTree>> for TokenReplay {
+    fn from(tokens: Vec<TokenTree>) -> Self {
+        Self::new(tokens)
+    }
+}
+
+impl TokenSource for TokenReplay {
+    fn next_token(&mut self) -> Option<TokenTree> {
+        let token = self.tokens.next()?;
+        self.last_spacing = Some(token.spacing().clone());
+        self.end = token.loc().end;
+        Some(token)
+    }
+
+    fn peek_token(&mut self) -> Option<&TokenTree> {
+        self.tokens.peek()
+    }
+
+    fn span(&mut self) -> Loc {
+        let end = self.end;
+        self.tokens
+            .peek()
+            .map_or(end..end, |token| token.loc().clone())
+    }
+
+    fn last_spacing(&self) -> Option<&Spacing> {
+        self.last_spacing.as_ref()
+    }
+}
@@ -0,0 +1,93 @@
+//! Comparing tokens without their locations, for tests which only care about
+//! which tokens were lexed.
+//!
+//! Writing out every location by hand makes tests long, and breaks them
+//! whenever the source they lex changes.  Instead, the lexed tokens can be
+//! [stripped](TokenTree::stripped) and compared against tokens built with
+//! [`tokens!`](crate::tokens!), which are stripped too.
+
+use crate::token::{Group, Spacing, StrSegment, TokenTree};
+use crate::Lexer;
+
+impl TokenTree {
+    /// Returns a copy of this token, and the tokens nested in it, without
+    /// anything but what was lexed: every location is `0..0`, the spacing is
+    /// [`Spacing::None`], and there are no comments or trivia.
+    pub fn stripped(&self) -> TokenTree {
+        let mut token = self.clone();
+        strip(&mut token);
+        token
+    }
+}
+
+/// Strips the location, spacing, comments and trivia of `group` and the tokens
+/// in it.
+fn strip_group(group: &mut Group) {
+    group.loc = 0..0;
+    group.spacing = Spacing::None;
+    group.comments = Box::default();
+    group.inner_comments = Box::default();
+    group.trailing_comments = Box::default();
+    group.trivia = None;
+
+    for token in &mut group.tokens {
+        strip(token);
+    }
+}
+
+/// Strips the location, spacing, comments and trivia of `token` and the tokens
+/// nested in it.
+fn strip(token: &mut TokenTree) {
+    match token {
+        TokenTree::Group(group) => return strip_group(group),
+        TokenTree::Str(str) => {
+            for segment in &mut str.value {
+                if let StrSegment::Interpolation(group) = segment {
+                    strip_group(group);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    *token.loc_mut() = 0..0;
+    if let Some(spacing) = token.spacing_mut() {
+        *spacing = Spacing::None;
+    }
+    if let Some(comments) = token.comments_mut() {
+        *comments = Box::default();
+    }
+    if let Some(comments) = token.trailing_comments_mut() {
+        *comments = Box::default();
+    }
+    if let Some(trivia) = token.trivia_mut() {
+        *trivia = None;
+    }
+}
+
+/// Lexes `source`, for [`tokens!`](crate::tokens!).
+#[doc(hidden)]
+pub fn stripped_tokens(source: &str) -> Vec<TokenTree> {
+    Lexer::new(source)
+        .map(|token| match token {
+            Ok(token) => token.stripped(),
+            Err(error) => panic!("invalid tokens in `tokens!`: {}", error),
+        })
+        .collect()
+}
+
+/// Builds [stripped](TokenTree::stripped) tokens from Cherry source in a
+/// string literal, such as `tokens!(r#"foo(1, "x")"#)`, to compare against
+/// stripped lexer output.
+///
+/// The source is lexed exactly as written, rather than from Rust tokens, since
+/// the spacing `stringify!` puts between tokens changes between versions of
+/// Rust, and decides whether `-1` is a negative integer or a `-` before one.
+/// It is lexed with the default configuration, so identifiers are never
+/// keywords.  Panics if the source doesn't lex.
+#[macro_export]
+macro_rules! tokens {
+    ($source:literal) => {
+        $crate::stripped_tokens($source)
+    };
+}
@@ -1,11 +1,22 @@
 //! Tokens for the Cherry lexer.
 
+use std::fmt;
 use std::ops::Range;
 
+use ccherry_span::{FileId, Span};
+
+/// A location in the source a token was lexed from, as a range of bytes.
+///
+/// A location doesn't know which file it is in, so it is only meaningful
+/// alongside the source it was lexed from.  Anything which deals with more
+/// than one file should use a [`Span`] instead, such as [`TokenTree::span`],
+/// or the diagnostics of [`Lexer::tokenize_file`](crate::Lexer::tokenize_file),
+/// which have their labels in the file of the lexer.
 pub type Loc = Range<usize>;
 
 /// The spacing between this token and the next token.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Spacing {
     /// Either there is no token after this one, or there is no whitespace
     /// between this token and the next token.
@@ -16,26 +27,64 @@ pub enum Spacing {
 
     /// There is a line break between this token and the next.
     LineBreak,
+
+    /// This token is a [`Punct`], and the next token is a [`Punct`] directly
+    /// after it, with no whitespace or comments in between.  This is how
+    /// multi-character operators, such as `==`, `->` or `::`, are represented.
+    Joint,
 }
 
 /// What comment syntax was used.
+///
+/// Outer doc comments document the item after them, and inner doc comments
+/// document the item they're inside of, such as a `//!` at the top of a file
+/// documenting its module.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CommentKind {
     /// The comment started with `//`.
     Line,
 
-    /// The comment started with `///`.
+    /// An outer doc comment, which starts with `///`.  A comment starting with
+    /// `////` is a regular line comment.
     Doc,
 
+    /// An inner doc comment, which starts with `//!`.
+    InnerDoc,
+
     /// A block comment, which starts with `/*` and ends with `*/`.
     Block,
+
+    /// An outer block doc comment, which starts with `/**` and ends with `*/`.
+    /// A comment starting with `/***`, and the empty comment `/**/`, are
+    /// regular block comments.
+    BlockDoc,
+
+    /// An inner block doc comment, which starts with `/*!` and ends with `*/`.
+    InnerBlockDoc,
+}
+
+impl CommentKind {
+    /// Returns whether or not this is a doc comment.
+    pub fn is_doc(&self) -> bool {
+        !matches!(self, CommentKind::Line | CommentKind::Block)
+    }
+
+    /// Returns whether or not this is an inner doc comment, which documents the
+    /// item it's inside of rather than the item after it.
+    pub fn is_inner(&self) -> bool {
+        matches!(self, CommentKind::InnerDoc | CommentKind::InnerBlockDoc)
+    }
 }
 
 /// A comment token.
 ///
-/// This will never be outputted directly by the lexer.  Comments may be found
-/// in tokens that have comments before them.
+/// Comments are attached to the tokens around them, in their comments or
+/// trailing comments.  In trivia mode (see
+/// [`Lexer::with_trivia`](crate::Lexer::with_trivia)) they are also outputted
+/// directly, as [`TriviaKind::Comment`] tokens.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Comment {
     /// The location of this comment.
     pub loc: Loc,
@@ -48,8 +97,39 @@ pub struct Comment {
     pub kind: CommentKind,
 }
 
+impl Comment {
+    /// Returns the span of this comment, if it was lexed from `file`.
+    pub fn span(&self, file: FileId) -> Span {
+        Span::at(file, self.loc.clone())
+    }
+}
+
+/// The exact source around a token, recorded by lexers in lossless mode (see
+/// [`Lexer::with_lossless`](crate::Lexer::with_lossless)).
+///
+/// Rendering the leading trivia, source and trailing trivia of each token in
+/// order gives back the exact source which was lexed.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trivia {
+    /// The whitespace, line breaks and comments between the previous token, or
+    /// the start of the file or group, and this token, exactly as written.
+    pub leading: String,
+
+    /// The source of this token, exactly as written.  For a group, this is only
+    /// its opening delimiter.
+    pub source: String,
+
+    /// For a group, the whitespace, line breaks and comments after its last
+    /// token, followed by its closing delimiter.  For the last token of the
+    /// file, the whitespace, line breaks and comments after it.  Empty for
+    /// every other token.
+    pub trailing: String,
+}
+
 /// Information about a token which was skipped.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Skipped {
     /// A comment token was skipped.
     Comment(Comment),
@@ -60,12 +140,17 @@ pub enum Skipped {
     /// A line breaking token.
     LineBreak,
 
+    /// A shebang line, such as `#!/usr/bin/env cherry`, at the very start of
+    /// the source.
+    Shebang(Loc),
+
     /// Nothing was skipped, the current character is not skippable.
     None,
 }
 
 /// An identifier literal token.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Iden {
     /// The location of this identifier.
     pub loc: Loc,
@@ -73,15 +158,52 @@ pub struct Iden {
     /// The value of this identifier.
     pub value: String,
 
+    /// Whether or not this is a raw identifier, such as `r#type`.  The value of
+    /// a raw identifier doesn't include the `r#`.
+    pub raw: bool,
+
     /// The comments before this identifier.
-    pub comments: Vec<Comment>,
+    pub comments: Box<[Comment]>,
 
     /// The spacing of this identifier.
     pub spacing: Spacing,
+
+    /// The comments after this identifier on the same line, if nothing else
+    /// comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this identifier, only recorded in lossless mode.
+    pub trivia: Option<Trivia>,
+}
+
+/// A keyword token, which is an identifier in the keyword table of the
+/// [`LexerConfig`](crate::LexerConfig).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyword {
+    /// The location of this keyword.
+    pub loc: Loc,
+
+    /// The value of this keyword.
+    pub value: String,
+
+    /// The comments before this keyword.
+    pub comments: Box<[Comment]>,
+
+    /// The spacing of this keyword.
+    pub spacing: Spacing,
+
+    /// The comments after this keyword on the same line, if nothing else comes
+    /// after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this keyword, only recorded in lossless mode.
+    pub trivia: Option<Trivia>,
 }
 
 /// A punctuation token.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Punct {
     /// The location of this punctuator.
     pub loc: Loc,
@@ -90,14 +212,22 @@ pub struct Punct {
     pub value: char,
 
     /// The comments before this punctuator.
-    pub comments: Vec<Comment>,
+    pub comments: Box<[Comment]>,
 
     /// The spacing of this punctuator.
     pub spacing: Spacing,
+
+    /// The comments after this punctuator on the same line, if nothing else
+    /// comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this punctuator, only recorded in lossless mode.
+    pub trivia: Option<Trivia>,
 }
 
-/// Whether an integer is a decimal, hexadecimal or binary literal.
+/// Whether an integer is a decimal, hexadecimal, octal or binary literal.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IntKind {
     /// A decimal literal.
     Decimal,
@@ -105,6 +235,9 @@ pub enum IntKind {
     /// A hexadecimal literal.
     Hexadecimal,
 
+    /// An octal literal.
+    Octal,
+
     /// A binary literal.
     Binary,
 }
@@ -112,27 +245,70 @@ pub enum IntKind {
 /// An integer literal token.
 ///
 /// By this point, the lexer has already converted this token to a usable
-/// integer value, rather than keeping it as a string.
+/// integer value, rather than keeping it as a string.  The lexer only checks
+/// that the value fits in a `u128`; whether it fits in the type of the literal
+/// is checked later.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Int {
-    /// The location of this integer literal.
+    /// The location of this integer literal, including its sign.
     pub loc: Loc,
 
     /// The kind of this integer literal.
     pub kind: IntKind,
 
-    /// The value of this integer literal.
-    pub value: i64,
+    /// The magnitude of this integer literal.
+    pub value: u128,
+
+    /// Whether or not this integer literal is negative.
+    pub negative: bool,
+
+    /// The digits of this integer literal, without its sign, prefix or digit
+    /// separators.
+    pub digits: String,
 
     /// The comments before this integer literal.
-    pub comments: Vec<Comment>,
+    pub comments: Box<[Comment]>,
 
     /// The spacing of this integer literal.
     pub spacing: Spacing,
+
+    /// The comments after this integer literal on the same line, if nothing
+    /// else comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this integer literal, only recorded in lossless
+    /// mode.
+    pub trivia: Option<Trivia>,
+}
+
+/// How a float literal was written, so a formatter can keep or normalize it.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FloatKind {
+    /// Digits on both sides of the `.`, such as `1.5` or `1.5e3`.
+    Decimal,
+
+    /// An exponent without a `.`, such as `1e3`.
+    Exponent,
+
+    /// A hexadecimal float with a binary exponent, such as `0x1.8p3`.
+    Hexadecimal,
+
+    /// No digits before the `.`, such as `.5`.  These are only lexed if
+    /// enabled with
+    /// [`LexerConfig::with_leading_dot_floats`](crate::LexerConfig::with_leading_dot_floats).
+    LeadingDot,
+
+    /// No digits after the `.`, such as `1.`.  These are only lexed if enabled
+    /// with
+    /// [`LexerConfig::with_trailing_dot_floats`](crate::LexerConfig::with_trailing_dot_floats).
+    TrailingDot,
 }
 
 /// A float literal token.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Float {
     /// The location of this float literal.
     pub loc: Loc,
@@ -140,51 +316,268 @@ pub struct Float {
     /// The value of this float literal.
     pub value: f64,
 
+    /// How this float literal was written.
+    pub kind: FloatKind,
+
     /// The comments before this float literal.
-    pub comments: Vec<Comment>,
+    pub comments: Box<[Comment]>,
 
     /// The spacing of this float literal.
     pub spacing: Spacing,
+
+    /// The comments after this float literal on the same line, if nothing else
+    /// comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this float literal, only recorded in lossless
+    /// mode.
+    pub trivia: Option<Trivia>,
+}
+
+/// What string syntax was used.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrKind {
+    /// A string in `"` or `'` quotes, which may contain escapes.
+    Normal,
+
+    /// A raw string, such as `r"..."` or `r#"..."#`, which can't contain
+    /// escapes.  The value is the number of `#`s around the string.
+    Raw(usize),
+
+    /// A triple-quoted string, such as `"""..."""`, which may span multiple
+    /// lines.  A line break directly after the opening quotes is ignored, and
+    /// so is the last line break and the indentation before the closing
+    /// quotes, which is also removed from the start of every other line.
+    Triple,
+}
+
+/// A part of a string literal.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrSegment {
+    /// Literal text, with its escapes processed.
+    Text(String),
+
+    /// An interpolation, such as `${name}`.  The group is the `{name}` part,
+    /// and has no comments before it and no spacing.
+    Interpolation(Group),
 }
 
 /// A string token.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Str {
     /// The location of this string literal.
     pub loc: Loc,
 
-    /// The (unescaped) value of this string literal.
-    pub value: String,
+    /// The segments of this string literal, in order.  Empty text isn't
+    /// included, so the empty string has no segments.
+    pub value: Vec<StrSegment>,
+
+    /// What string syntax was used.
+    pub kind: StrKind,
 
     /// The comments before this string literal.
-    pub comments: Vec<Comment>,
+    pub comments: Box<[Comment]>,
 
     /// The spacing of this string literal.
     pub spacing: Spacing,
+
+    /// The comments after this string literal on the same line, if nothing else
+    /// comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this string literal, only recorded in lossless
+    /// mode.
+    pub trivia: Option<Trivia>,
+}
+
+impl Str {
+    /// Returns the value of this string literal, if it has no
+    /// interpolations.
+    pub fn text(&self) -> Option<&str> {
+        match self.value.as_slice() {
+            [] => Some(""),
+            [StrSegment::Text(text)] => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// A character literal token, such as `'a'` or `'\n'`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Char {
+    /// The location of this character literal.
+    pub loc: Loc,
+
+    /// The (unescaped) value of this character literal.
+    pub value: char,
+
+    /// The comments before this character literal.
+    pub comments: Box<[Comment]>,
+
+    /// The spacing of this character literal.
+    pub spacing: Spacing,
+
+    /// The comments after this character literal on the same line, if nothing
+    /// else comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this character literal, only recorded in
+    /// lossless mode.
+    pub trivia: Option<Trivia>,
+}
+
+/// The delimiters around a group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Delimiter {
+    /// `( ... )`
+    Paren,
+
+    /// `[ ... ]`
+    Bracket,
+
+    /// `{ ... }`
+    Brace,
+}
+
+impl Delimiter {
+    /// Returns the delimiter which opens with `char`, if there is one.
+    pub fn from_open(char: char) -> Option<Delimiter> {
+        match char {
+            '(' => Some(Delimiter::Paren),
+            '[' => Some(Delimiter::Bracket),
+            '{' => Some(Delimiter::Brace),
+            _ => None,
+        }
+    }
+
+    /// Returns the character which opens this delimiter.
+    pub fn open(self) -> char {
+        match self {
+            Delimiter::Paren => '(',
+            Delimiter::Bracket => '[',
+            Delimiter::Brace => '{',
+        }
+    }
+
+    /// Returns the character which closes this delimiter.
+    pub fn close(self) -> char {
+        match self {
+            Delimiter::Paren => ')',
+            Delimiter::Bracket => ']',
+            Delimiter::Brace => '}',
+        }
+    }
 }
 
 /// A group token.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Group {
-    /// The location of this group.
+    /// The location of this group, including its delimiters.
     pub loc: Loc,
 
+    /// The delimiters around this group.  Interpolations in strings are always
+    /// [`Delimiter::Brace`].
+    pub delimiter: Delimiter,
+
     /// The (unescaped) value of this group.
     pub tokens: Vec<TokenTree>,
 
+    /// The comments at the end of this group, before its closing delimiter,
+    /// which aren't before any token in it.
+    pub inner_comments: Box<[Comment]>,
+
     /// The comments before this group.
-    pub comments: Vec<Comment>,
+    pub comments: Box<[Comment]>,
 
     /// The spacing of this group.
     pub spacing: Spacing,
+
+    /// The comments after this group on the same line, if nothing else comes
+    /// after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this group, only recorded in lossless mode.
+    pub trivia: Option<Trivia>,
+}
+
+/// A placeholder for a token which could not be tokenized.
+///
+/// These are only produced by lexers in recovery mode, in place of the token
+/// which caused an error.  The diagnostic for the error is recorded by the
+/// lexer.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Error {
+    /// The location of the invalid token.
+    pub loc: Loc,
+
+    /// The comments before the invalid token.
+    pub comments: Box<[Comment]>,
+
+    /// The spacing of the invalid token.
+    pub spacing: Spacing,
+
+    /// The comments after this invalid token on the same line, if nothing else
+    /// comes after them on that line.
+    pub trailing_comments: Box<[Comment]>,
+
+    /// The exact source around this invalid token, only recorded in lossless
+    /// mode.
+    pub trivia: Option<Trivia>,
+}
+
+/// What a [`TriviaToken`] is.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriviaKind {
+    /// One or more whitespace characters, other than line breaks.
+    Whitespace,
+
+    /// A single line break, `\n` or `\r\n`.
+    LineBreak,
+
+    /// A comment.
+    Comment(Comment),
+
+    /// A shebang line, such as `#!/usr/bin/env cherry`, at the very start of
+    /// the source.
+    Shebang,
+}
+
+/// Whitespace, a line break or a comment between tokens.
+///
+/// These are only produced by lexers in trivia mode (see
+/// [`Lexer::with_trivia`](crate::Lexer::with_trivia)), in order between the
+/// other tokens, for tools such as syntax highlighters which color every byte
+/// of the source.  They have no comments, spacing or trivia of their own, so
+/// [`TokenTree::comments`] and the other accessors return empty values for
+/// them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriviaToken {
+    /// The location of this trivia.
+    pub loc: Loc,
+
+    /// What this trivia is.
+    pub kind: TriviaKind,
 }
 
 /// A tree of tokens.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenTree {
     /// An identifier token.
     Iden(Iden),
 
+    /// A keyword token.
+    Keyword(Keyword),
+
     /// An punctuation token.
     Punct(Punct),
 
@@ -197,6 +590,285 @@ pub enum TokenTree {
     /// A string literal token.
     Str(Str),
 
+    /// A character literal token.
+    Char(Char),
+
     /// A group token.
     Group(Group),
+
+    /// A token which could not be tokenized, only produced in recovery mode.
+    Error(Error),
+
+    /// Whitespace, a line break or a comment, only produced in trivia mode.
+    Trivia(TriviaToken),
+}
+
+/// The kind of a [`TokenTree`], without its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// An identifier token.
+    Iden,
+
+    /// A keyword token.
+    Keyword,
+
+    /// A punctuation token.
+    Punct,
+
+    /// An integer literal token.
+    Int,
+
+    /// A float literal token.
+    Float,
+
+    /// A string literal token.
+    Str,
+
+    /// A character literal token.
+    Char,
+
+    /// A group token.
+    Group,
+
+    /// A token which could not be tokenized.
+    Error,
+
+    /// Whitespace, a line break or a comment.
+    Trivia,
+}
+
+/// Displays the name of a kind of token, such as `identifier`, for
+/// diagnostics such as "expected identifier, found `1`".
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TokenKind::Iden => "identifier",
+            TokenKind::Keyword => "keyword",
+            TokenKind::Punct => "punctuator",
+            TokenKind::Int => "integer literal",
+            TokenKind::Float => "float literal",
+            TokenKind::Str => "string literal",
+            TokenKind::Char => "character literal",
+            TokenKind::Group => "group",
+            TokenKind::Error => "invalid token",
+            TokenKind::Trivia => "whitespace or comment",
+        })
+    }
+}
+
+impl TokenTree {
+    /// Returns the kind of this token.
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            TokenTree::Iden(_) => TokenKind::Iden,
+            TokenTree::Keyword(_) => TokenKind::Keyword,
+            TokenTree::Punct(_) => TokenKind::Punct,
+            TokenTree::Int(_) => TokenKind::Int,
+            TokenTree::Float(_) => TokenKind::Float,
+            TokenTree::Str(_) => TokenKind::Str,
+            TokenTree::Char(_) => TokenKind::Char,
+            TokenTree::Group(_) => TokenKind::Group,
+            TokenTree::Error(_) => TokenKind::Error,
+            TokenTree::Trivia(_) => TokenKind::Trivia,
+        }
+    }
+
+    /// Returns whether or not this token is the punctuator `value`.
+    pub fn is_punct(&self, value: char) -> bool {
+        matches!(self, TokenTree::Punct(token) if token.value == value)
+    }
+
+    /// Returns whether or not this token is the identifier `value`.  Raw
+    /// identifiers match their name without the `r#`.
+    pub fn is_iden(&self, value: &str) -> bool {
+        matches!(self, TokenTree::Iden(token) if token.value == value)
+    }
+
+    /// Returns whether or not this token is the keyword `value`.
+    pub fn is_keyword(&self, value: &str) -> bool {
+        matches!(self, TokenTree::Keyword(token) if token.value == value)
+    }
+
+    /// Returns whether or not this token is whitespace, a line break or a
+    /// comment, which are only produced in trivia mode.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenTree::Trivia(_))
+    }
+
+    /// Returns whether or not this token is a group delimited by `delimiter`.
+    pub fn is_group(&self, delimiter: Delimiter) -> bool {
+        matches!(self, TokenTree::Group(token) if token.delimiter == delimiter)
+    }
+
+    /// Returns the span of this token, including the delimiters of a group, if
+    /// it was lexed from `file`.
+    pub fn span(&self, file: FileId) -> Span {
+        Span::at(file, self.loc().clone())
+    }
+
+    /// Returns the location of this token, including the delimiters of a
+    /// group.
+    pub fn loc(&self) -> &Loc {
+        match self {
+            TokenTree::Iden(token) => &token.loc,
+            TokenTree::Keyword(token) => &token.loc,
+            TokenTree::Punct(token) => &token.loc,
+            TokenTree::Int(token) => &token.loc,
+            TokenTree::Float(token) => &token.loc,
+            TokenTree::Str(token) => &token.loc,
+            TokenTree::Char(token) => &token.loc,
+            TokenTree::Group(token) => &token.loc,
+            TokenTree::Error(token) => &token.loc,
+            TokenTree::Trivia(token) => &token.loc,
+        }
+    }
+
+    /// Returns a mutable reference to the location of this token.
+    pub(crate) fn loc_mut(&mut self) -> &mut Loc {
+        match self {
+            TokenTree::Iden(token) => &mut token.loc,
+            TokenTree::Keyword(token) => &mut token.loc,
+            TokenTree::Punct(token) => &mut token.loc,
+            TokenTree::Int(token) => &mut token.loc,
+            TokenTree::Float(token) => &mut token.loc,
+            TokenTree::Str(token) => &mut token.loc,
+            TokenTree::Char(token) => &mut token.loc,
+            TokenTree::Group(token) => &mut token.loc,
+            TokenTree::Error(token) => &mut token.loc,
+            TokenTree::Trivia(token) => &mut token.loc,
+        }
+    }
+
+    /// Returns the comments before this token.
+    pub fn comments(&self) -> &[Comment] {
+        match self {
+            TokenTree::Iden(token) => &token.comments,
+            TokenTree::Keyword(token) => &token.comments,
+            TokenTree::Punct(token) => &token.comments,
+            TokenTree::Int(token) => &token.comments,
+            TokenTree::Float(token) => &token.comments,
+            TokenTree::Str(token) => &token.comments,
+            TokenTree::Char(token) => &token.comments,
+            TokenTree::Group(token) => &token.comments,
+            TokenTree::Error(token) => &token.comments,
+            TokenTree::Trivia(_) => &[],
+        }
+    }
+
+    /// Returns a mutable reference to the comments before this token, or
+    /// `None` for trivia tokens, which have none.
+    pub(crate) fn comments_mut(&mut self) -> Option<&mut Box<[Comment]>> {
+        Some(match self {
+            TokenTree::Iden(token) => &mut token.comments,
+            TokenTree::Keyword(token) => &mut token.comments,
+            TokenTree::Punct(token) => &mut token.comments,
+            TokenTree::Int(token) => &mut token.comments,
+            TokenTree::Float(token) => &mut token.comments,
+            TokenTree::Str(token) => &mut token.comments,
+            TokenTree::Char(token) => &mut token.comments,
+            TokenTree::Group(token) => &mut token.comments,
+            TokenTree::Error(token) => &mut token.comments,
+            TokenTree::Trivia(_) => return None,
+        })
+    }
+
+    /// Returns the comments after this token on the same line.
+    pub fn trailing_comments(&self) -> &[Comment] {
+        match self {
+            TokenTree::Iden(token) => &token.trailing_comments,
+            TokenTree::Keyword(token) => &token.trailing_comments,
+            TokenTree::Punct(token) => &token.trailing_comments,
+            TokenTree::Int(token) => &token.trailing_comments,
+            TokenTree::Float(token) => &token.trailing_comments,
+            TokenTree::Str(token) => &token.trailing_comments,
+            TokenTree::Char(token) => &token.trailing_comments,
+            TokenTree::Group(token) => &token.trailing_comments,
+            TokenTree::Error(token) => &token.trailing_comments,
+            TokenTree::Trivia(_) => &[],
+        }
+    }
+
+    /// Returns a mutable reference to the comments after this token on the
+    /// same line, or `None` for trivia tokens, which have none.
+    pub(crate) fn trailing_comments_mut(&mut self) -> Option<&mut Box<[Comment]>> {
+        Some(match self {
+            TokenTree::Iden(token) => &mut token.trailing_comments,
+            TokenTree::Keyword(token) => &mut token.trailing_comments,
+            TokenTree::Punct(token) => &mut token.trailing_comments,
+            TokenTree::Int(token) => &mut token.trailing_comments,
+            TokenTree::Float(token) => &mut token.trailing_comments,
+            TokenTree::Str(token) => &mut token.trailing_comments,
+            TokenTree::Char(token) => &mut token.trailing_comments,
+            TokenTree::Group(token) => &mut token.trailing_comments,
+            TokenTree::Error(token) => &mut token.trailing_comments,
+            TokenTree::Trivia(_) => return None,
+        })
+    }
+
+    /// Returns the spacing of this token.
+    pub fn spacing(&self) -> &Spacing {
+        match self {
+            TokenTree::Iden(token) => &token.spacing,
+            TokenTree::Keyword(token) => &token.spacing,
+            TokenTree::Punct(token) => &token.spacing,
+            TokenTree::Int(token) => &token.spacing,
+            TokenTree::Float(token) => &token.spacing,
+            TokenTree::Str(token) => &token.spacing,
+            TokenTree::Char(token) => &token.spacing,
+            TokenTree::Group(token) => &token.spacing,
+            TokenTree::Error(token) => &token.spacing,
+            TokenTree::Trivia(_) => &Spacing::None,
+        }
+    }
+
+    /// Returns a mutable reference to the spacing of this token, or `None` for
+    /// trivia tokens, which have none.
+    pub(crate) fn spacing_mut(&mut self) -> Option<&mut Spacing> {
+        Some(match self {
+            TokenTree::Iden(token) => &mut token.spacing,
+            TokenTree::Keyword(token) => &mut token.spacing,
+            TokenTree::Punct(token) => &mut token.spacing,
+            TokenTree::Int(token) => &mut token.spacing,
+            TokenTree::Float(token) => &mut token.spacing,
+            TokenTree::Str(token) => &mut token.spacing,
+            TokenTree::Char(token) => &mut token.spacing,
+            TokenTree::Group(token) => &mut token.spacing,
+            TokenTree::Error(token) => &mut token.spacing,
+            TokenTree::Trivia(_) => return None,
+        })
+    }
+
+    /// Returns the exact source around this token, if it was lexed in
+    /// lossless mode.
+    pub fn trivia(&self) -> Option<&Trivia> {
+        match self {
+            TokenTree::Iden(token) => token.trivia.as_ref(),
+            TokenTree::Keyword(token) => token.trivia.as_ref(),
+            TokenTree::Punct(token) => token.trivia.as_ref(),
+            TokenTree::Int(token) => token.trivia.as_ref(),
+            TokenTree::Float(token) => token.trivia.as_ref(),
+            TokenTree::Str(token) => token.trivia.as_ref(),
+            TokenTree::Char(token) => token.trivia.as_ref(),
+            TokenTree::Group(token) => token.trivia.as_ref(),
+            TokenTree::Error(token) => token.trivia.as_ref(),
+            TokenTree::Trivia(_) => None,
+        }
+    }
+
+    /// Returns a mutable reference to the exact source around this token, or
+    /// `None` for trivia tokens, which have none.
+    pub(crate) fn trivia_mut(&mut self) -> Option<&mut Option<Trivia>> {
+        Some(match self {
+            TokenTree::Iden(token) => &mut token.trivia,
+            TokenTree::Keyword(token) => &mut token.trivia,
+            TokenTree::Punct(token) => &mut token.trivia,
+            TokenTree::Int(token) => &mut token.trivia,
+            TokenTree::Float(token) => &mut token.trivia,
+            TokenTree::Str(token) => &mut token.trivia,
+            TokenTree::Char(token) => &mut token.trivia,
+            TokenTree::Group(token) => &mut token.trivia,
+            TokenTree::Error(token) => &mut token.trivia,
+            TokenTree::Trivia(_) => return None,
+        })
+    }
 }
@@ -1,8 +1,14 @@
 extern crate ccherry_lexer;
 
 use ccherry_lexer::{
-    Comment, CommentKind, Float, Group, Iden, Int, IntKind, Lexer, Spacing, TokenTree,
+    ambiguous_operators, apply_fixes, assert_tokens, decode_tokens, encode_tokens, tokens, Char,
+    Comment, CommentKind, CommentTable, Delimiter, Error, Float, FloatKind, Group, Iden, Int,
+    IntKind, Keyword, LexError, Lexer, LexerConfig, Loc, Punct, Spacing, Str, StrKind, StrSegment,
+    TextEdit, ToSource, TokenArena, TokenBuffer, TokenCursor, TokenKind, TokenOrigin, TokenReplay,
+    TokenSource, TokenTree, Trivia,
 };
+use ccherry_span::{FileId, Span};
+use codespan_reporting::diagnostic::Severity;
 
 #[test]
 fn iden() {
@@ -13,8 +19,11 @@ fn iden() {
         Some(Ok(TokenTree::Iden(Iden {
             loc: 0..4,
             value: "test".to_string(),
-            comments: vec![],
+            raw: false,
+            comments: Box::default(),
             spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 
@@ -23,8 +32,11 @@ fn iden() {
         Some(Ok(TokenTree::Iden(Iden {
             loc: 5..15,
             value: "identifier".to_string(),
-            comments: vec![],
+            raw: false,
+            comments: Box::default(),
             spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 }
@@ -33,7 +45,45 @@ fn iden() {
 fn only_comment() {
     let mut lexer = Lexer::new("/* test comment */");
 
+    assert_eq!(lexer.eof_comments(), &[]);
     assert_eq!(lexer.next(), None);
+    assert_eq!(
+        lexer.eof_comments(),
+        &[Comment {
+            loc: 0..18,
+            value: "test comment".to_string(),
+            kind: CommentKind::Block,
+        }]
+    );
+
+    // the one-shot functions return the comments after the last token too.
+    let (tokens, eof_comments, diagnostics) = Lexer::new("/* test comment */").tokenize_all(());
+    assert_eq!(tokens, []);
+    assert_eq!(eof_comments, lexer.eof_comments());
+    assert!(diagnostics.is_empty());
+    assert_eq!(Lexer::lex_all("/* test comment */").1, eof_comments);
+}
+
+#[test]
+fn eof_comments() {
+    let mut lexer = Lexer::new("a // b\n// c\n");
+
+    assert!(lexer.next().is_some());
+    assert_eq!(lexer.eof_comments(), &[]);
+    assert_eq!(lexer.next(), None);
+    assert_eq!(
+        lexer.eof_comments(),
+        &[Comment {
+            loc: 7..11,
+            value: "c".to_string(),
+            kind: CommentKind::Line,
+        }]
+    );
+
+    let mut lexer = Lexer::new("// a").with_comment_table(true);
+    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.eof_comments(), &[]);
+    assert_eq!(lexer.comment_table().map(CommentTable::len), Some(1));
 }
 
 #[test]
@@ -45,12 +95,15 @@ fn comment_before_iden() {
         Some(Ok(TokenTree::Iden(Iden {
             loc: 19..27,
             value: "function".to_string(),
-            comments: vec![Comment {
+            raw: false,
+            comments: Box::new([Comment {
                 loc: 0..18,
                 value: "test comment".to_string(),
                 kind: CommentKind::Block,
-            }],
+            }]),
             spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 }
@@ -65,8 +118,12 @@ fn integer() {
             loc: 0..4,
             kind: IntKind::Decimal,
             value: 1234,
-            comments: vec![],
+            negative: false,
+            digits: "1234".to_string(),
+            comments: Box::default(),
             spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 
@@ -76,8 +133,12 @@ fn integer() {
             loc: 5..9,
             kind: IntKind::Decimal,
             value: 4321,
-            comments: vec![],
+            negative: false,
+            digits: "4321".to_string(),
+            comments: Box::default(),
             spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 }
@@ -91,8 +152,11 @@ fn float() {
         Some(Ok(TokenTree::Float(Float {
             loc: 0..9,
             value: 1234.0213,
-            comments: vec![],
+            kind: FloatKind::Decimal,
+            comments: Box::default(),
             spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 
@@ -101,12 +165,142 @@ fn float() {
         Some(Ok(TokenTree::Float(Float {
             loc: 10..18,
             value: 4321.432,
-            comments: vec![],
+            kind: FloatKind::Decimal,
+            comments: Box::default(),
             spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 }
 
+#[test]
+fn exponent_without_dot() {
+    assert_tokens!(
+        "1e10 2E-3 -1_0e+2",
+        [
+            "Float(10000000000.0)@0..4 ws",
+            "Float(0.002)@5..9 ws",
+            "Float(-1000.0)@10..17",
+        ]
+    );
+
+    for (source, code) in [
+        ("1e", "E0004"),
+        ("1ex", "E0005"),
+        ("1.e5", "E0002"),
+        ("1e309", "E0006"),
+        ("-1.5e1000", "E0006"),
+    ] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert_eq!(error.code.as_deref(), Some(code), "{}", source);
+    }
+}
+
+#[test]
+fn dot_floats() {
+    // without configuration, a `.` is only part of a number with digits on
+    // both sides.
+    assert_tokens!(
+        "1.max .5 1..2",
+        [
+            "Int(1)@0..1",
+            "Punct('.')@1..2",
+            "Iden(\"max\")@2..5 ws",
+            "Punct('.')@6..7",
+            "Int(5)@7..8 ws",
+            "Int(1)@9..10",
+            "Punct('.')@10..11 joint",
+            "Punct('.')@11..12",
+            "Int(2)@12..13",
+        ]
+    );
+
+    let config = LexerConfig::new()
+        .with_leading_dot_floats(true)
+        .with_trailing_dot_floats(true);
+    assert_tokens!(
+        Lexer::new("1. -.5 1.max 1..2").with_config(config.clone()) => [
+            "Float(1.0)@0..2 ws",
+            "Float(-0.5)@3..6 ws",
+            "Int(1)@7..8",
+            "Punct('.')@8..9",
+            "Iden(\"max\")@9..12 ws",
+            "Int(1)@13..14",
+            "Punct('.')@14..15 joint",
+            "Punct('.')@15..16",
+            "Int(2)@16..17",
+        ]
+    );
+
+    let kinds: Vec<FloatKind> = Lexer::new("1.5 1e3 .5 1.")
+        .with_config(config)
+        .map(|token| match token.unwrap() {
+            TokenTree::Float(float) => float.kind,
+            token => panic!("expected a float, found {:?}", token),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            FloatKind::Decimal,
+            FloatKind::Exponent,
+            FloatKind::LeadingDot,
+            FloatKind::TrailingDot,
+        ]
+    );
+}
+
+#[test]
+fn hexadecimal_float() {
+    assert_tokens!(
+        "0x1.8p3 0x1p-2 -0xA_0.8P+0 0x1.max",
+        [
+            "Float(12.0)@0..7 ws",
+            "Float(0.25)@8..14 ws",
+            "Float(-160.5)@15..26 ws",
+            "Int(0x1)@27..30",
+            "Punct('.')@30..31",
+            "Iden(\"max\")@31..34",
+        ]
+    );
+
+    let value = |source: &str| match Lexer::new(source).next() {
+        Some(Ok(TokenTree::Float(float))) => {
+            assert_eq!(float.kind, FloatKind::Hexadecimal);
+            float.value
+        }
+        token => panic!("expected a float, found {:?}", token),
+    };
+
+    // halfway cases round to even, and subnormals are only rounded once.
+    assert_eq!(value("0x1.00000000000008p0"), 1.0);
+    assert_eq!(value("0x1.00000000000018p0"), 1.0 + f64::EPSILON * 2.0);
+    assert_eq!(value("0x1p-1074"), f64::from_bits(1));
+    assert_eq!(value("0x1.8p-1074"), f64::from_bits(2));
+    assert_eq!(value("0x1p-1076"), 0.0);
+    assert_eq!(value("0x1.fffffffffffffp1023"), f64::MAX);
+    assert_eq!(value(&format!("0x1{}p-160", "0".repeat(40))), 1.0);
+    assert_eq!(value(&format!("0x1{}1p-164", "0".repeat(40))), 1.0);
+
+    for (source, code) in [
+        ("0x1p", "E0004"),
+        ("0x1.8px", "E0005"),
+        ("0x1.fffffffffffff8p1023", "E0006"),
+    ] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert_eq!(error.code.as_deref(), Some(code), "{}", source);
+    }
+}
+
 #[test]
 fn code_block_group() {
     let mut lexer = Lexer::new("{ iden }");
@@ -115,14 +309,1891 @@ fn code_block_group() {
         lexer.next(),
         Some(Ok(TokenTree::Group(Group {
             loc: 0..8,
+            delimiter: Delimiter::Brace,
             tokens: vec![TokenTree::Iden(Iden {
                 loc: 2..6,
                 value: "iden".to_string(),
-                comments: vec![],
+                raw: false,
+                comments: Box::default(),
                 spacing: Spacing::Whitespace,
+                trailing_comments: Box::default(),
+                trivia: None,
             })],
-            comments: vec![],
+            inner_comments: Box::default(),
+            comments: Box::default(),
+            spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })))
+    );
+}
+
+#[test]
+fn group_comments() {
+    let source = "/* a */ { b /* c */ } // d\n(e // f\n// g\n)";
+    let (tokens, _, _) = Lexer::lex_all(source);
+    let group = match &tokens[0] {
+        TokenTree::Group(group) => group,
+        token => panic!("expected a group, found {:?}", token),
+    };
+
+    let comment = |loc: Loc, value: &str, kind| Comment {
+        loc,
+        value: value.to_string(),
+        kind,
+    };
+    assert_eq!(*group.comments, [comment(0..7, "a", CommentKind::Block)]);
+    assert!(group.tokens[0].comments().is_empty());
+    assert_eq!(
+        *group.inner_comments,
+        [comment(12..19, "c", CommentKind::Block)]
+    );
+    assert_eq!(
+        *group.trailing_comments,
+        [comment(22..26, "d", CommentKind::Line)]
+    );
+
+    // comments at the end of a group are printed before its closing
+    // delimiter, without ending up in the trailing comment of its last token.
+    let printed = tokens.to_source();
+    let (reprinted, _, errors) = Lexer::lex_all(&printed);
+    assert_eq!(errors, []);
+    assert_eq!(
+        reprinted
+            .iter()
+            .map(TokenTree::stripped)
+            .collect::<Vec<_>>(),
+        tokens.iter().map(TokenTree::stripped).collect::<Vec<_>>()
+    );
+    match &reprinted[1] {
+        TokenTree::Group(group) => {
+            assert_eq!(group.tokens[0].trailing_comments().len(), 1);
+            assert_eq!(group.inner_comments.len(), 1);
+        }
+        token => panic!("expected a group, found {:?}", token),
+    }
+
+    let (tokens, _, _) = Lexer::lex_all(r#""${ a /* b */ }""#);
+    match &tokens[0] {
+        TokenTree::Str(str) => match &str.value[0] {
+            StrSegment::Interpolation(group) => {
+                assert!(group.comments.is_empty());
+                assert_eq!(
+                    *group.inner_comments,
+                    [comment(6..13, "b", CommentKind::Block)]
+                );
+            }
+            segment => panic!("expected an interpolation, found {:?}", segment),
+        },
+        token => panic!("expected a string, found {:?}", token),
+    }
+}
+
+#[test]
+fn recovery_invalid_character() {
+    let mut lexer = Lexer::new("a ` b ` c").with_recovery(true);
+    let tokens: Vec<_> = lexer.by_ref().collect();
+
+    assert_eq!(tokens.len(), 5);
+    assert_eq!(
+        tokens[1],
+        Ok(TokenTree::Error(Error {
+            loc: 2..3,
+            comments: Box::default(),
+            spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
+        }))
+    );
+    assert_eq!(
+        tokens[4],
+        Ok(TokenTree::Iden(Iden {
+            loc: 8..9,
+            value: "c".to_string(),
+            raw: false,
+            comments: Box::default(),
+            spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
+        }))
+    );
+    assert_eq!(lexer.errors().len(), 2);
+}
+
+#[test]
+fn recovery_unclosed_group() {
+    let mut lexer = Lexer::new("(a").with_recovery(true);
+
+    assert_eq!(
+        lexer.next(),
+        Some(Ok(TokenTree::Group(Group {
+            loc: 0..2,
+            delimiter: Delimiter::Paren,
+            tokens: vec![TokenTree::Iden(Iden {
+                loc: 1..2,
+                value: "a".to_string(),
+                raw: false,
+                comments: Box::default(),
+                spacing: Spacing::None,
+                trailing_comments: Box::default(),
+                trivia: None,
+            })],
+            inner_comments: Box::default(),
+            comments: Box::default(),
+            spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })))
+    );
+    assert_eq!(lexer.next(), None);
+    assert_eq!(lexer.errors().len(), 1);
+}
+
+#[test]
+fn recovery_mismatched_delimiter() {
+    let mut lexer = Lexer::new("( foo ] bar").with_recovery(true);
+    let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+
+    assert_eq!(tokens.len(), 2);
+    assert!(matches!(
+        &tokens[0],
+        TokenTree::Group(Group { loc, tokens, .. }) if *loc == (0..7) && tokens.len() == 1
+    ));
+    assert!(matches!(&tokens[1], TokenTree::Iden(Iden { loc, .. }) if *loc == (8..11)));
+
+    assert_eq!(
+        lexer.errors(),
+        [LexError::MismatchedDelimiter {
+            loc: 6..7,
+            open: 0..1,
+            expected: ')',
+            found: ']',
+        }]
+    );
+    let diagnostic = lexer.errors()[0].to_diagnostic(());
+    assert_eq!(diagnostic.code.as_deref(), Some("E0019"));
+    assert_eq!(diagnostic.labels[0].range, 6..7);
+    assert_eq!(diagnostic.labels[0].message, "expected `)`, found `]`");
+    assert_eq!(diagnostic.labels[1].range, 0..1);
+
+    // a delimiter which closes an enclosing group is left for that group.
+    let (tokens, _, errors) = Lexer::lex_all("{ ( } a");
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&tokens[0], TokenTree::Group(Group { loc, .. }) if *loc == (0..5)));
+    assert_eq!(tokens.len(), 2);
+
+    // without recovery, the mismatch is an error.
+    let error = Lexer::new("[a)").next().unwrap().unwrap_err();
+    assert_eq!(error.code(), Some("E0019"));
+}
+
+#[test]
+fn group_depth() {
+    // groups are lexed without recursing, so deep nesting doesn't overflow
+    // the stack.
+    let depth = 2000;
+    let source = format!("{}{}", "(".repeat(depth), ")".repeat(depth));
+    let config = LexerConfig::new().with_max_depth(depth);
+    let (tokens, _, errors) = Lexer::new(&source).with_config(config).tokenize_all(());
+    assert_eq!(tokens.len(), 1);
+    assert!(errors.is_empty());
+
+    let config = LexerConfig::new().with_max_depth(2);
+    assert_tokens!(
+        Lexer::new("[(a)] (\"${b}\")").with_config(config.clone()) => [
+            r#"Group[Group[Iden("a")@2..3]@1..4]@0..5 ws"#,
+            r#"Group[Str[Group[Iden("b")@10..11]@9..12]@7..13]@6..14"#,
+        ]
+    );
+
+    // a group which is too deep is skipped with a single error.
+    let mut lexer = Lexer::new("[([a] b)] c")
+        .with_config(config.clone())
+        .with_recovery(true);
+    let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+    assert_eq!(
+        lexer.errors(),
+        [LexError::GroupTooDeep {
+            loc: 2..3,
+            max_depth: 2,
+        }]
+    );
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[0].to_source(), "[( b)]");
+    assert!(tokens[1].is_iden("c"));
+
+    let diagnostic = lexer.errors()[0].to_diagnostic(());
+    assert_eq!(diagnostic.code.as_deref(), Some("E0021"));
+    assert_eq!(
+        diagnostic.labels[0].message,
+        "this group is more than 2 groups deep"
+    );
+
+    let error = Lexer::new("(((a)))")
+        .with_config(config)
+        .next()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(error.code(), Some("E0021"));
+}
+
+#[test]
+fn recovery_unclosed_string() {
+    let mut lexer = Lexer::new("a \"b c").with_recovery(true);
+    let tokens: Vec<_> = lexer.by_ref().collect();
+
+    assert_eq!(tokens.len(), 2);
+    assert!(matches!(
+        tokens[1],
+        Ok(TokenTree::Error(Error { loc: _, .. }))
+    ));
+    assert_eq!(lexer.errors().len(), 1);
+}
+
+#[test]
+fn byte_offsets() {
+    let mut lexer = Lexer::new("é a");
+
+    assert_eq!(
+        lexer.nth(1),
+        Some(Ok(TokenTree::Iden(Iden {
+            loc: 3..4,
+            value: "a".to_string(),
+            raw: false,
+            comments: Box::default(),
+            spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })))
+    );
+}
+
+#[test]
+fn line_comment_at_eof() {
+    let mut lexer = Lexer::new("a // b");
+
+    assert!(matches!(lexer.next(), Some(Ok(TokenTree::Iden(_)))));
+    assert_eq!(lexer.next(), None);
+}
+
+#[test]
+fn joint_punct() {
+    let spacing: Vec<_> = Lexer::new("a -> b = -1")
+        .map(|token| match token.unwrap() {
+            TokenTree::Punct(punct) => Some(punct.spacing),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        spacing,
+        vec![
+            None,
+            Some(Spacing::Joint),
+            Some(Spacing::Whitespace),
+            None,
+            Some(Spacing::Whitespace),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn stripped_tokens() {
+    let (tokens, _, _) = Lexer::lex_all("/* a */ foo ( 1,\n\"x${ y }\" ) // b\n[0x1F]");
+    let stripped: Vec<TokenTree> = tokens.iter().map(TokenTree::stripped).collect();
+
+    assert_eq!(stripped, tokens!(r#"foo(1, "x${y}")[0x1F]"#));
+    assert_ne!(tokens!("-1"), tokens!("- 1"));
+    assert_eq!(
+        tokens!("a"),
+        [TokenTree::Iden(Iden {
+            loc: 0..0,
+            value: "a".to_string(),
+            raw: false,
+            comments: Box::default(),
+            spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })]
+    );
+}
+
+#[test]
+fn compact_rendering() {
+    assert_tokens!(
+        "r#a (-0x1F, 'c')\n2.5 r#\"s\"#",
+        [
+            r#"RawIden("a")@0..3 ws"#,
+            r#"Group[Int(-0x1F)@5..10, Punct(',')@10..11 ws, Char('c')@12..15]@4..16 lb"#,
+            "Float(2.5)@17..20 ws",
+            r#"RawStr1("s")@21..27"#,
+        ]
+    );
+}
+
+#[test]
+fn raw_string() {
+    let mut lexer = Lexer::new(r##"r"C:\path" r#"a "quoted" \d+"#"##);
+
+    assert_eq!(
+        lexer.next(),
+        Some(Ok(TokenTree::Str(Str {
+            loc: 0..10,
+            value: vec![StrSegment::Text(r"C:\path".to_string())],
+            kind: StrKind::Raw(0),
+            comments: Box::default(),
+            spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })))
+    );
+
+    assert_eq!(
+        lexer.next(),
+        Some(Ok(TokenTree::Str(Str {
+            loc: 11..30,
+            value: vec![StrSegment::Text(r#"a "quoted" \d+"#.to_string())],
+            kind: StrKind::Raw(1),
+            comments: Box::default(),
+            spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })))
+    );
+}
+
+#[test]
+fn string_interpolation() {
+    assert_tokens!(
+        r#"/* a */ "hi ${name}! ${ "${x}" }\$""#,
+        [
+            r#"Str["hi ", Group[Iden("name")@14..18]@13..19, "! ", Group[Str[Group[Iden("x")@27..28]@26..29]@24..30 ws]@22..32, "$"]@8..35"#,
+        ]
+    );
+
+    match Lexer::new("/* a */ \"${b}\"").next() {
+        Some(Ok(TokenTree::Str(str))) => {
+            assert_eq!(str.comments.len(), 1);
+            assert_eq!(str.text(), None);
+        }
+        token => panic!("expected a string, found {:?}", token),
+    }
+
+    assert!(Lexer::new("\"${a\"").next().unwrap().is_err());
+
+    let (tokens, _, diagnostics) = Lexer::lex_all(r#""\q ${a}" b"#);
+    assert!(matches!(tokens[0], TokenTree::Error(_)));
+    assert!(matches!(&tokens[1], TokenTree::Iden(Iden { value, .. }) if value == "b"));
+    assert_eq!(diagnostics[0].code(), Some("E0011"));
+}
+
+#[test]
+fn triple_quoted_string() {
+    let text =
+        |source: &str, config: LexerConfig| match Lexer::new(source).with_config(config).next() {
+            Some(Ok(TokenTree::Str(str))) => {
+                assert_eq!(str.kind, StrKind::Triple);
+                str.text().unwrap().to_string()
+            }
+            token => panic!("expected a string, found {:?}", token),
+        };
+
+    let source = "\"\"\"\n    a \"b\"\n      c\\t\n    \"\"\"";
+    assert_eq!(text(source, LexerConfig::new()), "a \"b\"\n  c\t");
+    assert_eq!(
+        text(source, LexerConfig::new().with_indent_stripping(false)),
+        "    a \"b\"\n      c\t"
+    );
+    assert_eq!(text("\"\"\"\r\n  a\r\n  \"\"\"", LexerConfig::new()), "a");
+    assert_eq!(text("\"\"\"a\"\"\"", LexerConfig::new()), "a");
+
+    assert_tokens!(
+        "\"\"\"\n  ${a}\n  b\n  \"\"\"",
+        [r#"TripleStr[Group[Iden("a")@8..9]@7..10, "\nb"]@0..20"#]
+    );
+}
+
+#[test]
+fn unclosed_multiline_string() {
+    let error = Lexer::new("\"abc\nd")
+        .next()
+        .unwrap()
+        .unwrap_err()
+        .to_diagnostic(());
+
+    assert_eq!(error.labels[1].range, 4..4);
+    assert_eq!(error.notes.len(), 1);
+}
+
+#[test]
+fn char_literal() {
+    let mut lexer = Lexer::new(r#"'a' '\n' '"'"#);
+
+    assert_eq!(
+        lexer.next(),
+        Some(Ok(TokenTree::Char(Char {
+            loc: 0..3,
+            value: 'a',
+            comments: Box::default(),
+            spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })))
+    );
+
+    assert!(matches!(
+        lexer.next(),
+        Some(Ok(TokenTree::Char(Char { value: '\n', .. })))
+    ));
+    assert!(matches!(
+        lexer.next(),
+        Some(Ok(TokenTree::Char(Char { value: '"', .. })))
+    ));
+}
+
+#[test]
+fn escapes() {
+    assert_tokens!(
+        r#""\n\r\t\0\\\'\"\$ \x41\u{1F600}" '\x7F' "a\
+            b""#,
+        [
+            r#"Str("\n\r\t\0\\'\"$ A😀")@0..32 ws"#,
+            r"Char('\u{7f}')@33..39 ws",
+            r#"Str("ab")@40..58"#,
+        ]
+    );
+
+    // every invalid escape in a string is labeled, including the ones after
+    // an interpolation.
+    let error = Lexer::new(r#""\q \x80 ${a} \u{110000} \u{}""#)
+        .next()
+        .unwrap()
+        .unwrap_err()
+        .to_diagnostic(());
+    assert_eq!(error.code.as_deref(), Some("E0011"));
+    let labels: Vec<_> = error
+        .labels
+        .iter()
+        .map(|label| (label.range.clone(), label.message.as_str()))
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            (1..3, "unknown escape `\\q`"),
+            (
+                4..8,
+                "`\\x` escapes may be at most `\\x7F`, use `\\u{...}` for other characters"
+            ),
+            (14..24, "not a valid unicode character"),
+            (25..29, "unicode escapes must have between 1 and 6 digits"),
+        ]
+    );
+
+    for (source, code) in [
+        (r"'\x4'", "E0011"),
+        (r"'\u{41'", "E0012"),
+        (r"'\u41'", "E0012"),
+    ] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert_eq!(error.code.as_deref(), Some(code), "{}", source);
+    }
+}
+
+#[test]
+fn invalid_char_literal() {
+    for (source, code) in [
+        ("''", "E0015"),
+        ("'ab'", "E0016"),
+        ("'a", "E0017"),
+        (r"'\q'", "E0011"),
+    ] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert_eq!(error.code.as_deref(), Some(code), "{}", source);
+    }
+}
+
+#[test]
+fn crlf() {
+    let mut lexer = Lexer::new("a // b\r\n/* c\r\nd */ \"e\r\nf\"");
+
+    assert_eq!(
+        lexer.next(),
+        Some(Ok(TokenTree::Iden(Iden {
+            loc: 0..1,
+            value: "a".to_string(),
+            raw: false,
+            comments: Box::default(),
+            spacing: Spacing::LineBreak,
+            trailing_comments: Box::new([Comment {
+                loc: 2..6,
+                value: "b".to_string(),
+                kind: CommentKind::Line,
+            }]),
+            trivia: None,
+        })))
+    );
+
+    match lexer.next() {
+        Some(Ok(TokenTree::Str(str))) => {
+            assert_eq!(str.text(), Some("e\nf"));
+            assert_eq!(str.comments[0].value, "c\nd");
+        }
+        token => panic!("expected a string, found {:?}", token),
+    }
+
+    assert!(lexer.errors().is_empty());
+}
+
+#[test]
+fn stray_carriage_return() {
+    let mut lexer = Lexer::new("a\rb");
+
+    assert_eq!(lexer.by_ref().count(), 2);
+    assert_eq!(
+        lexer.errors(),
+        [LexError::StrayCarriageReturn { loc: 1..2 }]
+    );
+    assert!(lexer.errors()[0].is_warning());
+}
+
+#[test]
+fn escape_suggestions() {
+    let source = r#""\N \x7 \xE9 \u00e9 \U0001F600 \u{41 \u{0000041} \a \v \101 \477 \d""#;
+    let mut lexer = Lexer::new(source);
+    let error = lexer.next().unwrap().unwrap_err().to_diagnostic(());
+
+    assert_eq!(error.labels.len(), 12);
+    assert_eq!(error.notes[0], "help: replace `\\N` with `\\n`");
+
+    let fixes: Vec<_> = lexer
+        .fixes()
+        .iter()
+        .map(|fix| (&source[fix.loc.clone()], fix.replacement.as_str()))
+        .collect();
+    assert_eq!(
+        fixes,
+        [
+            (r"\N", r"\n"),
+            (r"\x7", r"\x07"),
+            (r"\xE9", r"\u{E9}"),
+            (r"\u00e9", r"\u{00e9}"),
+            (r"\U0001F600", r"\u{1F600}"),
+            (r"\u{41", r"\u{41}"),
+            (r"\u{0000041}", r"\u{41}"),
+            (r"\a", r"\x07"),
+            (r"\v", r"\x0B"),
+            (r"\101", r"\x41"),
+            (r"\477", r"\u{13F}"),
+            (r"\d", r"\\d"),
+        ]
+    );
+    assert_eq!(error.notes.len(), fixes.len());
+
+    // the fixed source lexes without errors, to what the escapes mean in the
+    // languages they are from.
+    let mut fixed = source.to_string();
+    for fix in lexer.fixes().iter().rev() {
+        fixed.replace_range(fix.loc.clone(), &fix.replacement);
+    }
+    match Lexer::new(&fixed).next() {
+        Some(Ok(TokenTree::Str(str))) => assert_eq!(
+            str.text(),
+            Some("\n \x07 \u{E9} \u{E9} \u{1F600} A A \x07 \x0B A \u{13F} \\d")
+        ),
+        token => panic!("expected a string, found {:?}", token),
+    }
+
+    // Python's named escapes can't be fixed without their names, and neither
+    // can an unknown escape in a character literal.
+    for source in [r#""\N{DEGREE SIGN}""#, r"'\q'"] {
+        let mut lexer = Lexer::new(source);
+        assert!(lexer.next().unwrap().is_err());
+        assert_eq!(lexer.fixes(), [], "{}", source);
+    }
+    let mut lexer = Lexer::new(r"'\e'");
+    assert!(lexer.next().unwrap().is_err());
+    assert_eq!(lexer.fixes()[0].replacement, r"\x1B");
+}
+
+#[test]
+fn digit_separators() {
+    for (source, value) in [
+        ("1_000_000", 1_000_000),
+        ("0xFF_FF", 0xFF_FF),
+        ("0b1010_1010", 0b1010_1010),
+    ] {
+        match Lexer::new(source).next() {
+            Some(Ok(TokenTree::Int(int))) => {
+                assert_eq!(int.value, value);
+                assert_eq!(int.loc, 0..source.len());
+            }
+            token => panic!("expected an integer, found {:?}", token),
+        }
+    }
+
+    match Lexer::new("1_0.2_5e1_0").next() {
+        Some(Ok(TokenTree::Float(float))) => assert_eq!(float.value, 10.25e10),
+        token => panic!("expected a float, found {:?}", token),
+    }
+}
+
+#[test]
+fn misplaced_digit_separators() {
+    for (source, loc) in [("1__0", 1..2), ("1_", 1..2), ("0x_F", 2..3), ("1._5", 2..3)] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert_eq!(error.code.as_deref(), Some("E0018"), "{}", source);
+        assert_eq!(error.labels[0].range, loc, "{}", source);
+
+        // recovery skips the whole number, rather than lexing the rest of it
+        // as more tokens.
+        let (tokens, _, errors) = Lexer::lex_all(source);
+        assert_eq!(errors.len(), 1, "{}", source);
+        assert_eq!(tokens.len(), 1, "{}", source);
+        assert_eq!(*tokens[0].loc(), 0..source.len(), "{}", source);
+    }
+}
+
+#[test]
+fn octal() {
+    assert_tokens!("0o7_55", ["Int(0o755)@0..6"]);
+
+    for source in ["0o", "0o8", &format!("0o{}", "7".repeat(43))] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert!(error.message.contains("octal"), "{}", source);
+    }
+}
+
+#[test]
+fn invalid_digits() {
+    for (source, loc, label) in [
+        ("0o78", 3..4, "invalid digit for base 8"),
+        ("0b12", 3..4, "invalid digit for base 2"),
+        ("0x1g", 3..4, "invalid digit for base 16"),
+        ("0b2", 2..3, "invalid digit for base 2"),
+        ("-0o1a", 4..5, "invalid digit for base 8"),
+    ] {
+        let error = Lexer::new(source)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_diagnostic(());
+        assert_eq!(error.code.as_deref(), Some("E0022"), "{}", source);
+        assert_eq!(error.labels[0].range, loc, "{}", source);
+        assert_eq!(error.labels[0].message, label, "{}", source);
+    }
+
+    // the rest of the number is skipped, rather than lexed as more tokens.
+    assert_tokens!(
+        "0x1.8p3 0x1.max",
+        [
+            "Float(12.0)@0..7 ws",
+            "Int(0x1)@8..11",
+            "Punct('.')@11..12",
+            "Iden(\"max\")@12..15",
+        ]
+    );
+    let (tokens, _, errors) = Lexer::lex_all("0o78ab c");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(*tokens[0].loc(), 0..6);
+}
+
+#[test]
+fn lex_all() {
+    let (tokens, _, diagnostics) = Lexer::lex_all("a ` b 0x c");
+
+    assert_eq!(tokens.len(), 5);
+    assert!(matches!(tokens[1], TokenTree::Error(_)));
+    assert!(matches!(tokens[3], TokenTree::Error(_)));
+    assert_eq!(diagnostics.len(), 2);
+}
+
+#[test]
+fn offset() {
+    let file = "fn f() {\n    a (b /* c */) \"${d}\" \"\\N\" `\n    // e\n}\n";
+    let offset = file.find('{').unwrap() + 1;
+    let snippet = &file[offset..file.rfind('}').unwrap()];
+
+    let (tokens, _, errors) = Lexer::lex_with_offset(snippet, offset);
+    let sources: Vec<_> = tokens
+        .iter()
+        .map(|token| &file[token.loc().clone()])
+        .collect();
+    assert_eq!(sources, ["a", "(b /* c */)", "\"${d}\"", "\"\\N\"", "`"]);
+
+    let TokenTree::Group(group) = &tokens[1] else {
+        panic!("expected a group, found {:?}", tokens[1]);
+    };
+    assert_eq!(&file[group.tokens[0].loc().clone()], "b");
+    assert_eq!(&file[group.inner_comments[0].loc.clone()], "/* c */");
+
+    let TokenTree::Str(str) = &tokens[2] else {
+        panic!("expected a string, found {:?}", tokens[2]);
+    };
+    let StrSegment::Interpolation(interpolation) = &str.value[0] else {
+        panic!("expected an interpolation, found {:?}", str.value[0]);
+    };
+    assert_eq!(&file[interpolation.loc.clone()], "{d}");
+
+    let labels: Vec<_> = errors
+        .iter()
+        .flat_map(|error| error.to_diagnostic(()).labels)
+        .map(|label| &file[label.range])
+        .collect();
+    assert_eq!(labels, ["\\N", "`"]);
+
+    let mut lexer = Lexer::new(snippet).with_offset(offset).with_recovery(true);
+    assert_eq!(lexer.by_ref().count(), 5);
+    assert_eq!(&file[lexer.fixes()[0].loc.clone()], "\\N");
+    assert_eq!(&file[lexer.eof_comments()[0].loc.clone()], "// e");
+
+    // without an offset, locations are in the snippet.
+    let (tokens, _, _) = Lexer::lex_all(snippet);
+    assert_eq!(&snippet[tokens[0].loc().clone()], "a");
+}
+
+/// Consumes every token of `source`, checking that peeking and consuming
+/// agree, and returns the tokens along with the location and the spacing of
+/// each one.
+fn drain(source: &mut impl TokenSource) -> (Vec<TokenTree>, Vec<Loc>, Vec<Spacing>) {
+    let (mut tokens, mut locs, mut spacings) = (vec![], vec![], vec![]);
+    assert_eq!(source.last_spacing(), None);
+
+    while let Some(peeked) = source.peek_token().cloned() {
+        locs.push(source.span());
+
+        let token = source.next_token().unwrap();
+        assert_eq!(token, peeked);
+        tokens.push(token);
+        spacings.push(source.last_spacing().unwrap().clone());
+    }
+
+    assert_eq!(source.next_token(), None);
+    (tokens, locs, spacings)
+}
+
+#[test]
+fn spans() {
+    let file = FileId(3);
+    let (tokens, _, errors) = Lexer::lex_all("/* a */ b (c) 'd");
+
+    assert_eq!(tokens[0].span(file), Span::at(file, 8..9));
+    assert_eq!(tokens[1].span(file), Span::at(file, 10..13));
+    assert_eq!(tokens[0].comments()[0].span(file), Span::at(file, 0..7));
+
+    let diagnostic = errors[0].to_diagnostic(file);
+    assert_eq!(diagnostic.labels[0].file_id, file);
+
+    let lexer = Lexer::new("/* a */ b (c) 'd").with_file(file);
+    assert_eq!(lexer.file(), file);
+    let (_, _, diagnostics) = lexer.tokenize_file();
+    assert_eq!(diagnostics.len(), errors.len());
+    assert!(diagnostics
+        .iter()
+        .flat_map(|diagnostic| &diagnostic.labels)
+        .all(|label| label.file_id == file));
+}
+
+#[test]
+fn token_arena() {
+    let source = "a (b [c] d) \"${e}\"";
+    let (tokens, _, _) = Lexer::lex_all(source);
+    let (arena, _, diagnostics) = Lexer::new(source).tokenize_all_arena(());
+    assert!(diagnostics.is_empty());
+
+    // the tokens of groups are stored after the top-level tokens.
+    assert_eq!(arena.len(), 7);
+    let roots: Vec<_> = arena.roots().collect();
+    assert_eq!(roots.len(), 3);
+    assert_eq!(arena[roots[0]], tokens[0]);
+    assert_eq!(arena.children(roots[0]).len(), 0);
+    assert_eq!(arena.children(roots[2]).len(), 0);
+
+    let group: Vec<_> = arena.children(roots[1]).collect();
+    let values: Vec<_> = group.iter().map(|&id| arena[id].to_source()).collect();
+    assert_eq!(values, ["b", "[]", "d"]);
+    assert_eq!(group[0].index(), 3);
+
+    let nested: Vec<_> = arena.children(group[1]).collect();
+    assert_eq!(
+        arena.get(nested[0]).map(ToSource::to_source),
+        Some("c".to_string())
+    );
+    assert_eq!(arena.children(nested[0]).len(), 0);
+
+    assert_eq!(arena.into_tokens(), tokens);
+    assert!(TokenArena::new(vec![]).is_empty());
+}
+
+#[test]
+fn token_source() {
+    let source = "a b(c)\nd";
+    let (tokens, _, _) = Lexer::lex_all(source);
+    let expected = (
+        tokens.clone(),
+        vec![0..1, 2..3, 3..6, 7..8],
+        vec![
+            Spacing::Whitespace,
+            Spacing::None,
+            Spacing::LineBreak,
+            Spacing::None,
+        ],
+    );
+
+    let mut lexer = Lexer::new(source);
+    assert_eq!(drain(&mut lexer), expected);
+    assert_eq!(lexer.span(), 8..8);
+
+    let mut cursor = TokenCursor::new(&tokens);
+    assert_eq!(drain(&mut cursor), expected);
+    assert_eq!(cursor.span(), 8..8);
+
+    let mut replay = TokenReplay::new(tokens.clone());
+    assert_eq!(drain(&mut replay), expected);
+    assert_eq!(replay.span(), 8..8);
+
+    let TokenTree::Group(group) = &tokens[2] else {
+        panic!("expected a group");
+    };
+    let mut cursor = TokenCursor::group(group);
+    let (tokens, locs, spacings) = drain(&mut cursor);
+    assert_eq!(tokens, group.tokens);
+    assert_eq!((locs.len(), &locs[0]), (1, &(4..5)));
+    assert_eq!(spacings, [Spacing::None]);
+    assert_eq!(cursor.span(), 5..5);
+
+    // errors are recorded in the lexer, and replaced with error tokens.
+    let mut lexer = Lexer::new("a \u{1} b");
+    assert!(matches!(drain(&mut lexer).0[1], TokenTree::Error(_)));
+    assert_eq!(lexer.take_errors().len(), 1);
+
+    // a token peeked after a checkpoint is forgotten when rewinding to it.
+    let mut lexer = Lexer::new("a b");
+    let state = lexer.checkpoint();
+    lexer.peek_token();
+    lexer.rewind(state);
+    assert_eq!(lexer.map(Result::unwrap).count(), 2);
+}
+
+#[test]
+fn tokenize_all() {
+    let config = LexerConfig::new().with_keywords(&["fn"]);
+    let (tokens, _, diagnostics) = Lexer::new("fn ` \r").with_config(config).tokenize_all(7);
+
+    assert!(matches!(tokens[0], TokenTree::Keyword(_)));
+    assert!(matches!(tokens[1], TokenTree::Error(_)));
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].code.as_deref(), Some("E0013"));
+    assert_eq!(diagnostics[0].labels[0].file_id, 7);
+    assert_eq!(diagnostics[1].severity, Severity::Warning);
+}
+
+#[test]
+fn relex() {
+    let source = "let a = 1; // one\nf(b, \"${c}\") /* d */ e";
+    let (old_tokens, _, _) = Lexer::lex_all(source);
+
+    for (loc, text) in [
+        (4..5, "abc"),
+        (5..5, "b"),
+        (3..4, ""),
+        (9..9, "\n"),
+        (14..17, "two"),
+        (21..22, "bb"),
+        (27..27, " + x"),
+        (33..35, "*/ g /*"),
+        (0..0, "#!cherry\n"),
+        (source.len()..source.len(), ";"),
+        (0..source.len(), "h"),
+    ] {
+        let edit = TextEdit {
+            loc,
+            text: text.to_string(),
+        };
+        let edited = edit.apply(source);
+        let tokens = Lexer::new(&edited).relex(&old_tokens, &edit);
+
+        assert_eq!(tokens, Lexer::lex_all(&edited).0, "{:?}", edited);
+    }
+
+    // the edit turns `r##` before it into the prefix of a raw string.
+    let source = "#r##!.\n([(0x1.8p3\u{301}<";
+    let edit = TextEdit {
+        loc: 4..20,
+        text: "\"".to_string(),
+    };
+    let edited = edit.apply(source);
+    let tokens = Lexer::new(&edited).relex(&Lexer::lex_all(source).0, &edit);
+    assert_eq!(tokens, Lexer::lex_all(&edited).0);
+}
+
+#[test]
+fn large_and_negative_integers() {
+    match Lexer::new("0xFFFF_FFFF_FFFF_FFFF").next() {
+        Some(Ok(TokenTree::Int(int))) => {
+            assert_eq!(int.value, u64::MAX as u128);
+            assert_eq!(int.digits, "FFFFFFFFFFFFFFFF");
+        }
+        token => panic!("expected an integer, found {:?}", token),
+    }
+
+    match Lexer::new("-0x10").next() {
+        Some(Ok(TokenTree::Int(int))) => {
+            assert_eq!(int.loc, 0..5);
+            assert_eq!((int.value, int.negative), (16, true));
+        }
+        token => panic!("expected an integer, found {:?}", token),
+    }
+
+    match Lexer::new("-1.5").next() {
+        Some(Ok(TokenTree::Float(float))) => {
+            assert_eq!(float.loc, 0..4);
+            assert_eq!(float.value, -1.5);
+        }
+        token => panic!("expected a float, found {:?}", token),
+    }
+}
+
+#[test]
+fn checkpoint_and_rewind() {
+    let mut lexer = Lexer::new("a /* x */ b ` c").with_recovery(true);
+    assert_tokens!(lexer.by_ref().take(1) => ["Iden(\"a\")@0..1 ws"]);
+
+    let state = lexer.checkpoint();
+    assert_eq!(lexer.by_ref().count(), 3);
+    assert_eq!(lexer.errors().len(), 1);
+
+    lexer.rewind(state.clone());
+    assert!(lexer.errors().is_empty());
+    match lexer.next() {
+        Some(Ok(TokenTree::Iden(iden))) => {
+            assert_eq!(iden.value, "b");
+            assert_eq!(iden.comments[0].value, "x");
+        }
+        token => panic!("expected an identifier, found {:?}", token),
+    }
+
+    let mut lexer = Lexer::new("a /* x */ b").with_comment_table(true);
+    lexer.next();
+    let state = lexer.checkpoint();
+    lexer.next();
+    assert_eq!(lexer.comment_table().unwrap().len(), 1);
+    lexer.rewind(state);
+    assert!(lexer.comment_table().unwrap().is_empty());
+}
+
+#[test]
+fn to_source() {
+    let source =
+        "let x = r#fn + 0x1F-1.5e3; /* note */ f(\"a\\n${b}\\t$\", '\\'', r#\"raw\"#)\n{ y }";
+    let tokens: Vec<TokenTree> = Lexer::new(source).map(Result::unwrap).collect();
+    let printed = tokens.to_source();
+
+    assert_eq!(
+        printed,
+        "let x = r#fn + 0x1F-1500.0; /* note */ f(\"a\\n${b}\\t\\$\", '\\'', r#\"raw\"#)\n{y}"
+    );
+
+    // without locations, the printed source lexes to the same tokens.
+    let shape = |tokens: &[TokenTree]| -> Vec<String> {
+        tokens
+            .iter()
+            .map(|token| {
+                let compact = token.compact().to_string();
+                compact.split('@').next().unwrap().to_string()
+            })
+            .collect()
+    };
+    let reprinted: Vec<TokenTree> = Lexer::new(&printed).map(Result::unwrap).collect();
+    assert_eq!(shape(&reprinted), shape(&tokens));
+    assert_eq!(reprinted.to_source(), printed);
+
+    let triple = "\"\"\"\n    a \"b\"\n    \"\"\"";
+    let tokens: Vec<TokenTree> = Lexer::new(triple).map(Result::unwrap).collect();
+    assert_eq!(tokens.to_source(), "\"\"\"\na \\\"b\\\"\n\"\"\"");
+
+    // a `#!` at the start would be read as a shebang line.
+    let (tokens, _, _) = Lexer::lex_all(" #!x");
+    assert_eq!(tokens.to_source(), " #!x");
+
+    // a line comment after the last token of a group is followed by a line
+    // break, so it doesn't swallow the closing delimiter.
+    for (source, expected) in [
+        ("{ a // c\n}", "{a // c\n}"),
+        ("\"${a // c\n}\"", "\"${a // c\n}\""),
+        ("(a /* b */ // c\n// d\n)", "(a /* b */ // c\n// d\n)"),
+        ("[a /* b */]", "[a /* b */ ]"),
+        ("{ a /* b */ }", "{a /* b */ }"),
+    ] {
+        let (tokens, _, errors) = Lexer::lex_all(source);
+        assert_eq!(errors, [], "{:?}", source);
+
+        let printed = tokens.to_source();
+        assert_eq!(printed, expected, "{:?}", source);
+
+        let (reprinted, _, errors) = Lexer::lex_all(&printed);
+        assert_eq!(errors, [], "{:?}", printed);
+        assert_eq!(
+            reprinted
+                .iter()
+                .map(TokenTree::stripped)
+                .collect::<Vec<_>>(),
+            tokens.iter().map(TokenTree::stripped).collect::<Vec<_>>(),
+            "{:?}",
+            printed
+        );
+    }
+}
+
+#[test]
+fn to_source_floats() {
+    let config = LexerConfig::new()
+        .with_leading_dot_floats(true)
+        .with_trailing_dot_floats(true);
+    let source = "1.5 1.0e20 1.5e3 2.5e-8 1e3 15E2 1.5e-3 0e0 .5 .05 .5e10 1. 0x1p3 0x1.8p-2 \
+                  0x1p-1074 -0x0p0 -1e3";
+    let tokens: Vec<TokenTree> = Lexer::new(source)
+        .with_config(config.clone())
+        .map(Result::unwrap)
+        .collect();
+
+    let printed = tokens.to_source();
+    assert_eq!(
+        printed,
+        "1.5 1.0e20 1500.0 2.5e-8 1e3 15e2 0.0015 0e0 .5 .05 .5e10 1. 0x1p3 0x1.8p-2 \
+         0x0.0000000000001p-1022 -0x0p0 -1e3"
+    );
+
+    // every float lexes again with the same value and kind.
+    let reprinted: Vec<TokenTree> = Lexer::new(&printed)
+        .with_config(config)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        reprinted
+            .iter()
+            .map(TokenTree::stripped)
+            .collect::<Vec<_>>(),
+        tokens.iter().map(TokenTree::stripped).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn display() {
+    let (tokens, _, _) = Lexer::lex_all("foo \"a${b}\" 0x1F ; (c d) r#fn 'e");
+    let displayed: Vec<_> = tokens.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        displayed,
+        ["foo", "\"a${b}\"", "0x1F", ";", "(", "r#fn", "<error>"]
+    );
+
+    assert_eq!(format!("found `{}`", tokens[4].kind()), "found `group`");
+    assert_eq!(tokens[4].display().to_string(), "(c d)");
+    assert_eq!(tokens[..4].display().to_string(), "foo \"a${b}\" 0x1F ;");
+}
+
+#[test]
+fn trivia_tokens() {
+    let source = "#!sh\na  /* b */ (c // d\n) \"${ e }\"\n// f\n";
+    let tokens: Vec<TokenTree> = Lexer::new(source)
+        .with_trivia(true)
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        tokens
+            .iter()
+            .map(|token| token.compact().to_string())
+            .collect::<Vec<_>>(),
+        [
+            "Shebang@0..4",
+            "LineBreak@4..5",
+            "Iden(\"a\")@5..6 ws",
+            "Whitespace@6..8",
+            "Comment(\"b\")@8..15",
+            "Whitespace@15..16",
+            "Group[Iden(\"c\")@17..18 lb, Whitespace@18..19, Comment(\"d\")@19..23, \
+             LineBreak@23..24]@16..25 ws",
+            "Whitespace@25..26",
+            "Str[Group[Whitespace@29..30, Iden(\"e\")@30..31 ws, Whitespace@31..32]@28..33]@26..34 lb",
+            "LineBreak@34..35",
+            "Comment(\"f\")@35..39",
+            "LineBreak@39..40",
+        ]
+    );
+
+    // comments are only returned as trivia, and every byte but the shebang
+    // line is covered by a token.
+    assert!(tokens[2].comments().is_empty());
+    assert_eq!(
+        tokens.to_source(),
+        "\na /* b */ (c // d\n) \"${ e }\"\n// f\n"
+    );
+    assert!(tokens
+        .iter()
+        .filter(|token| token.is_trivia())
+        .all(|token| *token.spacing() == Spacing::None
+            && token.comments().is_empty()
+            && token.trailing_comments().is_empty()
+            && token.trivia().is_none()));
+
+    // the trivia tokens are kept by the token cache.
+    assert_eq!(decode_tokens(&encode_tokens(&tokens)).unwrap(), tokens);
+
+    // without trivia mode, the same source lexes as it always has.
+    let (plain, _, _) = Lexer::lex_all(source);
+    assert_eq!(plain.len(), 3);
+    assert_eq!(plain[0].comments().len(), 0);
+    assert_eq!(plain[1].comments().len(), 1);
+}
+
+#[test]
+fn lossless() {
+    let source = "#!/usr/bin/env cherry\n// a\nfn  main ( ) {\r\n\tlet x = 0x1F-1e3; /* b */\n  \"\\x41${ y }\"\n}  \n// end\n";
+    let tokens: Vec<TokenTree> = Lexer::new(source)
+        .with_lossless(true)
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(tokens.to_source(), source);
+    assert_eq!(
+        tokens[0],
+        TokenTree::Iden(Iden {
+            loc: 27..29,
+            value: "fn".into(),
+            raw: false,
+            comments: Box::new([Comment {
+                loc: 22..26,
+                value: "a".into(),
+                kind: CommentKind::Line,
+            }]),
+            spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: Some(Trivia {
+                leading: "#!/usr/bin/env cherry\n// a\n".into(),
+                source: "fn".into(),
+                trailing: "".into(),
+            }),
+        })
+    );
+
+    let trivia = match &tokens[3] {
+        TokenTree::Group(group) => group.trivia.as_ref().unwrap(),
+        _ => panic!("expected a group"),
+    };
+    assert_eq!(trivia.source, "{");
+    assert_eq!(trivia.trailing, "\n}  \n// end\n");
+
+    // the trivia is kept by the token cache, and in recovery mode.
+    let decoded = decode_tokens(&encode_tokens(&tokens)).unwrap();
+    assert_eq!(decoded.to_source(), source);
+
+    let source = "a ` (b /* c */ ";
+    let tokens: Vec<TokenTree> = Lexer::new(source)
+        .with_lossless(true)
+        .with_recovery(true)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(tokens.to_source(), source);
+}
+
+#[test]
+fn doc_comments() {
+    let source = "//! a\n/*! b */\n/// c\n/** d */\n// e\n//// f\n/* g */\n/*** h */\n/**/\ni";
+    let comments = match Lexer::new(source).next() {
+        Some(Ok(TokenTree::Iden(iden))) => iden.comments,
+        token => panic!("expected an identifier, found {:?}", token),
+    };
+    let kinds: Vec<_> = comments
+        .iter()
+        .map(|comment| comment.kind.clone())
+        .collect();
+
+    assert_eq!(
+        kinds,
+        [
+            CommentKind::InnerDoc,
+            CommentKind::InnerBlockDoc,
+            CommentKind::Doc,
+            CommentKind::BlockDoc,
+            CommentKind::Line,
+            CommentKind::Line,
+            CommentKind::Block,
+            CommentKind::Block,
+            CommentKind::Block,
+        ]
+    );
+    assert_eq!(comments[0].value, "a");
+    assert_eq!(comments[3].value, "d");
+    assert!(comments[1].kind.is_doc() && comments[1].kind.is_inner());
+    assert!(comments[2].kind.is_doc() && !comments[2].kind.is_inner());
+    assert!(!comments[4].kind.is_doc());
+
+    let source = "//! a\n/*! b */ /// c\n/** d */ // e\ni";
+    let tokens: Vec<TokenTree> = Lexer::new(source).map(Result::unwrap).collect();
+    assert_eq!(tokens.to_source(), source);
+}
+
+#[test]
+fn trailing_comments() {
+    let source = "let x = 1; // counter\nf(a /* b */ c) /* d */\n// e\ng // f";
+    let tokens: Vec<TokenTree> = Lexer::new(source).map(Result::unwrap).collect();
+    let comments = |token: &TokenTree| -> (Vec<String>, Vec<String>) {
+        let values = |comments: &[Comment]| comments.iter().map(|c| c.value.clone()).collect();
+        match token {
+            TokenTree::Iden(token) => (values(&token.comments), values(&token.trailing_comments)),
+            TokenTree::Punct(token) => (values(&token.comments), values(&token.trailing_comments)),
+            TokenTree::Group(token) => (values(&token.comments), values(&token.trailing_comments)),
+            token => panic!("unexpected token {:?}", token),
+        }
+    };
+
+    // a block comment followed by another token on the same line is before
+    // that token, rather than after the last one.
+    assert_eq!(comments(&tokens[4]), (vec![], vec!["counter".to_string()]));
+    match &tokens[6] {
+        TokenTree::Group(group) => {
+            assert_eq!(comments(&group.tokens[0]), (vec![], vec![]));
+            assert_eq!(comments(&group.tokens[1]), (vec!["b".to_string()], vec![]));
+        }
+        token => panic!("expected a group, found {:?}", token),
+    }
+    assert_eq!(comments(&tokens[6]), (vec![], vec!["d".to_string()]));
+    assert_eq!(
+        comments(&tokens[7]),
+        (vec!["e".to_string()], vec!["f".to_string()])
+    );
+
+    assert_eq!(
+        tokens.to_source(),
+        "let x = 1; // counter\nf(a /* b */ c) /* d */\n// e\ng // f"
+    );
+}
+
+#[test]
+fn comment_table() {
+    let mut lexer = Lexer::new("a /* x */ b // y\nc // z").with_comment_table(true);
+    let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+
+    for token in &tokens {
+        assert!(matches!(token, TokenTree::Iden(iden) if iden.comments.is_empty()));
+    }
+
+    let table: CommentTable = lexer.take_comment_table().unwrap();
+    let values: Vec<_> = table
+        .all()
+        .iter()
+        .map(|comment| comment.value.as_str())
+        .collect();
+    assert_eq!(values, vec!["x", "y", "z"]);
+
+    assert_eq!(table.between(1, 10).len(), 1);
+    assert_eq!(table.between(1, 10)[0].value, "x");
+    assert_eq!(table.between(11, 18)[0].value, "y");
+    assert!(table.between(2, 8).is_empty());
+}
+
+#[test]
+fn nested_block_comment() {
+    match Lexer::new("/* outer /* inner */ still outer */ a").next() {
+        Some(Ok(TokenTree::Iden(iden))) => {
+            assert_eq!(iden.value, "a");
+            assert_eq!(iden.comments[0].value, "outer /* inner */ still outer");
+        }
+        token => panic!("expected an identifier, found {:?}", token),
+    }
+
+    let error = Lexer::new("/* a /* b")
+        .next()
+        .unwrap()
+        .unwrap_err()
+        .to_diagnostic(());
+    assert_eq!(error.labels[1].range, 5..7);
+}
+
+#[test]
+fn iden_continue_config() {
+    let config = LexerConfig::new().with_iden_continue(&['-', '\'']).unwrap();
+    let tokens: Vec<_> = Lexer::new("kebab-case - x' a-")
+        .with_config(config)
+        .map(|token| match token.unwrap() {
+            TokenTree::Iden(iden) => iden.value,
+            TokenTree::Punct(punct) => punct.value.to_string(),
+            token => panic!("unexpected token {:?}", token),
+        })
+        .collect();
+
+    assert_eq!(tokens, vec!["kebab-case", "-", "x'", "a", "-"]);
+
+    for char in [' ', '\n', '(', '"'] {
+        assert!(LexerConfig::new().with_iden_continue(&[char]).is_err());
+    }
+}
+
+#[test]
+fn syntax_config() {
+    let config = LexerConfig::new()
+        .with_puncts(&['=', '^', '\\', '/', '*'])
+        .unwrap()
+        .with_line_comments(&["#", "--"])
+        .unwrap()
+        .with_block_comments(false)
+        .with_single_quote_strings(true);
+    assert_tokens!(
+        Lexer::new("a = 'b' # c\n^\\ -- d\n/* 'e' */").with_config(config) => [
+            r#"Iden("a")@0..1 ws"#,
+            "Punct('=')@2..3 ws",
+            r#"Str("b")@4..7 lb"#,
+            "Punct('^')@12..13 joint",
+            r"Punct('\\')@13..14 lb",
+            "Punct('/')@20..21 joint",
+            "Punct('*')@21..22 ws",
+            r#"Str("e")@23..26 ws"#,
+            "Punct('*')@27..28 joint",
+            "Punct('/')@28..29",
+        ]
+    );
+
+    let config = LexerConfig::new().with_line_comments(&[]).unwrap();
+    let (tokens, _, errors) = Lexer::new("// a").with_config(config).tokenize_all(());
+    assert_eq!(tokens.len(), 3);
+    assert!(errors.is_empty());
+
+    for char in [' ', '\n', '(', '"', 'a', '1'] {
+        assert!(LexerConfig::new().with_puncts(&[char]).is_err());
+    }
+    for marker in ["", " #", "\"", "rem", "1"] {
+        assert!(LexerConfig::new().with_line_comments(&[marker]).is_err());
+    }
+}
+
+#[test]
+fn negative_literals() {
+    assert_tokens!(
+        Lexer::new("a-1 - -2.5") => [
+            r#"Iden("a")@0..1"#,
+            "Int(-1)@1..3 ws",
+            "Punct('-')@4..5 ws",
+            "Float(-2.5)@6..10",
+        ]
+    );
+
+    let config = LexerConfig::new().with_negative_literals(false);
+    assert_tokens!(
+        Lexer::new("a-1 - -2.5 --0x1").with_config(config) => [
+            r#"Iden("a")@0..1"#,
+            "Punct('-')@1..2",
+            "Int(1)@2..3 ws",
+            "Punct('-')@4..5 ws",
+            "Punct('-')@6..7",
+            "Float(2.5)@7..10 ws",
+            "Punct('-')@11..12 joint",
+            "Punct('-')@12..13",
+            "Int(0x1)@13..16",
+        ]
+    );
+}
+
+#[test]
+fn unicode_identifiers() {
+    // identifiers are normalized to NFC.
+    let (tokens, _, errors) = Lexer::lex_all("cafe\u{301} αβγ");
+    assert!(matches!(&tokens[0], TokenTree::Iden(iden) if iden.value == "caf\u{e9}"));
+    assert!(errors.is_empty());
+
+    let (_, _, errors) = Lexer::lex_all("p\u{430}th x");
+    assert_eq!(
+        errors,
+        [LexError::ConfusableIdentifier {
+            loc: 0..5,
+            value: "p\u{430}th".to_string(),
+            skeleton: "path".to_string(),
+        }]
+    );
+
+    let diagnostic = errors[0].to_diagnostic(());
+    assert_eq!(diagnostic.severity, Severity::Warning);
+    assert_eq!(
+        diagnostic.message,
+        "identifier `p\u{430}th` looks like `path`"
+    );
+    assert_eq!(diagnostic.notes, ["`\u{430}` (U+0430) looks like `a`"]);
+
+    let (_, _, errors) = Lexer::lex_all("latinλ");
+    assert_eq!(
+        errors[0].to_diagnostic(()).message,
+        "identifier mixes Latin and Greek letters"
+    );
+    assert!(errors[0].is_warning());
+
+    let config = LexerConfig::new().with_confusable_warnings(false);
+    let (_, _, errors) = Lexer::new("p\u{430}th latinλ")
+        .with_config(config)
+        .tokenize_all(());
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn accessors() {
+    let config = LexerConfig::new().with_keywords(&["let"]);
+    let (tokens, _, _) = Lexer::new("let x = // value\n[1, 'a']")
+        .with_config(config)
+        .tokenize_all(());
+
+    let kinds: Vec<_> = tokens.iter().map(TokenTree::kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            TokenKind::Keyword,
+            TokenKind::Iden,
+            TokenKind::Punct,
+            TokenKind::Group,
+        ]
+    );
+
+    assert_eq!(tokens[1].loc(), &(4..5));
+    assert_eq!(tokens[2].spacing(), &Spacing::LineBreak);
+    assert_eq!(tokens[2].trailing_comments()[0].value, "value");
+    assert!(tokens[3].comments().is_empty());
+    assert!(tokens[3].trivia().is_none());
+
+    assert!(tokens[0].is_keyword("let"));
+    assert!(!tokens[0].is_iden("let"));
+    assert!(tokens[1].is_iden("x"));
+    assert!(tokens[2].is_punct('='));
+    assert!(!tokens[2].is_punct('-'));
+    assert!(tokens[3].is_group(Delimiter::Bracket));
+    assert!(!tokens[3].is_group(Delimiter::Paren));
+}
+
+#[test]
+fn keywords() {
+    let config = LexerConfig::new().with_keywords(&["fn", "let"]);
+    let tokens: Vec<_> = Lexer::new("fn r#let lets")
+        .with_config(config)
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(
+        tokens[0],
+        TokenTree::Keyword(Keyword {
+            loc: 0..2,
+            value: "fn".to_string(),
+            comments: Box::default(),
+            spacing: Spacing::Whitespace,
+            trailing_comments: Box::default(),
+            trivia: None,
+        })
+    );
+    assert!(matches!(&tokens[1], TokenTree::Iden(Iden { value, raw: true, .. }) if value == "let"));
+    assert!(
+        matches!(&tokens[2], TokenTree::Iden(Iden { value, raw: false, .. }) if value == "lets")
+    );
+}
+
+#[test]
+fn shebang() {
+    let mut lexer = Lexer::new("#!/usr/bin/env cherry\na");
+
+    assert!(matches!(lexer.next(), Some(Ok(TokenTree::Iden(Iden { loc, .. }))) if loc == (22..23)));
+    assert_eq!(lexer.shebang(), Some("#!/usr/bin/env cherry"));
+
+    let mut lexer = Lexer::new("#![no_prelude]");
+
+    assert!(matches!(
+        lexer.next(),
+        Some(Ok(TokenTree::Punct(Punct { value: '#', .. })))
+    ));
+    assert_eq!(lexer.shebang(), None);
+}
+
+#[test]
+fn raw_iden() {
+    assert_eq!(
+        Lexer::new("r#type").next(),
+        Some(Ok(TokenTree::Iden(Iden {
+            loc: 0..6,
+            value: "type".to_string(),
+            raw: true,
+            comments: Box::default(),
             spacing: Spacing::None,
+            trailing_comments: Box::default(),
+            trivia: None,
         })))
     );
 }
+
+#[test]
+fn token_buffer() {
+    let (tokens, _, _) = Lexer::lex_all("a + b");
+    let (replacement, _, _) = Lexer::lex_all("c * d");
+    let mut buffer = TokenBuffer::new(tokens);
+
+    let removed = buffer.replace(2..3, replacement);
+    assert_eq!(removed.len(), 1);
+    assert_eq!(buffer.len(), 5);
+    assert_eq!(buffer.origin(1), Some(&TokenOrigin::Source));
+    assert_eq!(
+        buffer.origin(3),
+        Some(&TokenOrigin::Inserted { site: 4..5 })
+    );
+
+    buffer.remove(0..2);
+    assert_eq!(buffer.len(), 3);
+
+    let (semicolon, _, _) = Lexer::lex_all(";");
+    buffer.insert(3, semicolon);
+    assert_eq!(
+        buffer.origin(3),
+        Some(&TokenOrigin::Inserted { site: 5..5 })
+    );
+}
+
+#[test]
+fn delimiters() {
+    let (tokens, _, errors) = Lexer::lex_all("(a) [b] {c} \"${d}\"");
+    assert!(errors.is_empty());
+
+    let delimiters: Vec<_> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            TokenTree::Group(group) => Some(group.delimiter),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        delimiters,
+        [Delimiter::Paren, Delimiter::Bracket, Delimiter::Brace]
+    );
+
+    match &tokens[3] {
+        TokenTree::Str(Str { value, .. }) => match &value[0] {
+            StrSegment::Interpolation(group) => assert_eq!(group.delimiter, Delimiter::Brace),
+            segment => panic!("expected an interpolation, found {:?}", segment),
+        },
+        token => panic!("expected a string, found {:?}", token),
+    }
+}
+
+#[test]
+fn token_cursor() {
+    let config = LexerConfig::new().with_keywords(&["fn"]);
+    let tokens: Vec<_> = Lexer::new("fn main(a) { b; }")
+        .with_config(config)
+        .map(Result::unwrap)
+        .collect();
+    let mut cursor = TokenCursor::new(&tokens);
+
+    assert!(matches!(cursor.lookahead(2), Some(TokenTree::Group(_))));
+    assert!(cursor.eat_keyword("fn"));
+    assert_eq!(cursor.expect_iden().unwrap().value, "main");
+
+    let saved = cursor;
+    let error = cursor.expect_punct(';').unwrap_err();
+    assert_eq!(error.message, "expected `;`, found `(`");
+    assert_eq!(error.labels[0].range, 7..8);
+    assert_eq!(cursor.position(), saved.position());
+
+    cursor.expect_group(Delimiter::Paren).unwrap();
+    let body = cursor.expect_group(Delimiter::Brace).unwrap();
+    assert!(cursor.is_eof());
+    assert_eq!(
+        cursor.expected("`;`").message,
+        "expected `;`, found end of input"
+    );
+
+    let mut body = TokenCursor::group(body);
+    assert!(body.next().is_some());
+    assert!(body.eat_punct(';'));
+    let error = body.expect_iden().unwrap_err();
+    assert_eq!(error.message, "expected an identifier, found `}`");
+    assert_eq!(error.labels[0].range, 16..17);
+}
+
+#[test]
+fn token_cache() {
+    let (tokens, _, _) = Lexer::lex_all("// hi\nfn(-0x1_F, 'c', r#\"s\"#) [2.5] {} `");
+    let bytes = encode_tokens(&tokens);
+
+    assert_eq!(decode_tokens(&bytes).unwrap(), tokens);
+
+    for len in 0..bytes.len() {
+        assert!(decode_tokens(&bytes[..len]).is_err());
+    }
+
+    let mut old_version = bytes.clone();
+    old_version[4] = 0;
+    let error = decode_tokens(&old_version).unwrap_err();
+    assert!(error.message.contains("version"));
+}
+
+#[test]
+fn ambiguous_operator_lint() {
+    let (tokens, _, _) = Lexer::lex_all("a-1 x =- 1 y <-1 (z)-2.5 e - 1 f(-1, \"${g-3}\")");
+    let warnings = ambiguous_operators(&tokens);
+    let messages: Vec<_> = warnings
+        .iter()
+        .map(|lint| {
+            let diagnostic = &lint.diagnostic;
+            (
+                diagnostic.message.as_str(),
+                diagnostic.labels[0].range.clone(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        messages,
+        vec![
+            ("ambiguous negative number literal", 1..2),
+            ("ambiguous operator `=-`", 6..8),
+            ("ambiguous negative number literal", 14..15),
+            ("ambiguous negative number literal", 20..21),
+            ("ambiguous negative number literal", 41..42),
+        ]
+    );
+
+    let source = "a-1 x =- 1 (b)-2";
+    let (tokens, _, _) = Lexer::lex_all(source);
+    assert_eq!(
+        apply_fixes(source, &ambiguous_operators(&tokens)),
+        "a -1 x =- 1 (b) -2"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde() {
+    let source = "a /* b */ (1.5 \"c${d}\")";
+    let tokens: Vec<TokenTree> = Lexer::new(source).map(Result::unwrap).collect();
+
+    let json = serde_json::to_string(&tokens).unwrap();
+    assert!(json.starts_with(r#"[{"Iden":{"loc":{"start":0,"end":1},"value":"a""#));
+
+    let decoded: Vec<TokenTree> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, tokens);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn lex_files() {
+    let files = [(0, "a (b"), (1, "1 2 3\n// c"), (2, "'ab'")];
+    let results = ccherry_lexer::lex_files(&files);
+
+    assert_eq!(
+        results
+            .iter()
+            .map(|result| result.file_id)
+            .collect::<Vec<_>>(),
+        [0, 1, 2]
+    );
+    assert_eq!(results[1].tokens.len(), 3);
+    assert_eq!(results[1].eof_comments.len(), 1);
+    assert!(results[1].diagnostics.is_empty());
+
+    for (file_id, result) in [(0, &results[0]), (2, &results[2])] {
+        assert_eq!(result.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics[0].labels[0].file_id, file_id);
+    }
+}
+
+#[cfg(feature = "proc-macro2")]
+#[test]
+fn proc_macro2() {
+    let (tokens, _, _) = Lexer::lex_all(r#"a r#b += (1, 0x1F) [2.5 'c'] { "d${e}" } `"#);
+    let stream: proc_macro2::TokenStream = tokens.iter().cloned().collect();
+    assert_eq!(
+        stream.to_string(),
+        r#"a r#b += (1 , 0x1F) [2.5 'c'] { "d${e}" } "#
+    );
+
+    // every token but the error comes back the same, without its location.
+    let converted: Vec<TokenTree> = stream.into_iter().map(TokenTree::from).collect();
+    assert_eq!(converted.len(), tokens.len());
+    for (converted, token) in converted.iter().zip(&tokens).take(tokens.len() - 1) {
+        assert_eq!(converted.stripped(), token.stripped());
+    }
+    assert_eq!(*converted[2].spacing(), Spacing::Joint);
+    assert_eq!(*converted[3].spacing(), Spacing::None);
+
+    // Rust splits negative numbers in token streams.
+    let stream: proc_macro2::TokenStream = Lexer::new("-1").map(Result::unwrap).collect();
+    assert_eq!(stream.to_string(), "- 1");
+
+    let stream: proc_macro2::TokenStream = "f(x: u8) -> u8 { x + 1u8 }".parse().unwrap();
+    let converted: Vec<TokenTree> = stream.into_iter().map(TokenTree::from).collect();
+    assert_eq!(converted.to_source(), "f(x:u8)->u8{x+}");
+    assert!(matches!(
+        &converted[5],
+        TokenTree::Group(group) if matches!(group.tokens[2], TokenTree::Error(_))
+    ));
+
+    // an escaped `$` stays text, rather than becoming an interpolation.
+    let (tokens, _, _) = Lexer::lex_all(r#""\${a} ${b} \"c\" $""#);
+    let stream: proc_macro2::TokenStream = tokens.iter().cloned().collect();
+    let converted: Vec<TokenTree> = stream.into_iter().map(TokenTree::from).collect();
+    assert_eq!(converted[0].stripped(), tokens[0].stripped());
+
+    // identifiers which aren't Rust identifiers have no Rust token.
+    let config = LexerConfig::new().with_iden_continue(&['-']).unwrap();
+    let tokens: Vec<TokenTree> = Lexer::new("foo-bar baz")
+        .with_config(config)
+        .map(Result::unwrap)
+        .collect();
+    let stream: proc_macro2::TokenStream = tokens.into_iter().collect();
+    let stream: Vec<_> = stream.into_iter().collect();
+    assert!(matches!(
+        &stream[0],
+        proc_macro2::TokenTree::Group(group) if group.stream().is_empty()
+    ));
+    assert_eq!(stream[1].to_string(), "baz");
+}
+
+/// Generates sources made of pieces of Cherry tokens, which are much more
+/// likely than random strings to reach the interesting parts of the lexer,
+/// such as unclosed groups and strings, bad escapes and number edge cases.
+fn source_strategy() -> impl proptest::strategy::Strategy<Value = String> {
+    use proptest::prelude::*;
+
+    let piece = prop_oneof![
+        "[a-zA-Z_][a-zA-Z0-9_]{0,6}",
+        "r#[a-z]{0,3}",
+        "-?[0-9][0-9_]{0,5}",
+        "-?0[xob][0-9a-fA-F_]{0,5}",
+        "[0-9]{1,3}\\.[0-9_]{0,3}([eE][+-]?[0-9]{0,3})?",
+        "0x[0-9a-f]{1,3}(\\.[0-9a-f]{0,2})?[pP]?[+-]?[0-9]{0,2}",
+        "[-+*/%=<>!&|^~.,;:?#@$]{1,3}",
+        "[()\\[\\]{}]",
+        "\"[a-z ${}\\\\'nt0u]{0,8}\"?",
+        "r#{0,2}\"[a-z \"#]{0,6}\"?#{0,2}",
+        "\"\"\"[a-z \n]{0,6}(\"\"\")?",
+        "'[a-z\\\\'nu{}]{0,3}'?",
+        "//[a-z ]{0,5}",
+        "/\\*[a-z */]{0,6}",
+        "[ \t\n\r]{1,2}",
+        "\\PC",
+    ];
+
+    prop::collection::vec(piece, 0..24).prop_map(|pieces| pieces.concat())
+}
+
+/// Returns the compact rendering of `tokens`, without their locations and
+/// spacing, which the printer doesn't keep.
+fn shape(tokens: &[TokenTree]) -> Vec<String> {
+    tokens
+        .iter()
+        .map(|token| {
+            let compact = token.compact().to_string();
+            let mut out = String::with_capacity(compact.len());
+            let mut rest = compact.as_str();
+
+            while let Some(at) = rest.find('@') {
+                out.push_str(&rest[..at]);
+                rest = rest[at + 1..]
+                    .trim_start_matches(|char: char| char.is_ascii_digit() || char == '.');
+                for spacing in [" ws", " lb", " joint"] {
+                    rest = rest.strip_prefix(spacing).unwrap_or(rest);
+                }
+            }
+
+            out.push_str(rest);
+            out
+        })
+        .collect()
+}
+
+proptest::proptest! {
+    #[test]
+    fn never_panics(source in "\\PC*") {
+        Lexer::lex_all(&source);
+    }
+
+    #[test]
+    fn never_panics_on_tokens(source in source_strategy()) {
+        Lexer::lex_all(&source);
+    }
+
+    #[test]
+    fn relex_matches_lex_all(
+        source in source_strategy(),
+        text in source_strategy(),
+        start in proptest::prelude::any::<proptest::sample::Index>(),
+        len in proptest::prelude::any::<proptest::sample::Index>(),
+    ) {
+        let boundaries: Vec<usize> = (0..=source.len())
+            .filter(|&idx| source.is_char_boundary(idx))
+            .collect();
+        let start = start.index(boundaries.len());
+        let end = start + len.index(boundaries.len() - start);
+        let edit = TextEdit {
+            loc: boundaries[start]..boundaries[end],
+            text,
+        };
+        let edited = edit.apply(&source);
+
+        let (old_tokens, _, _) = Lexer::lex_all(&source);
+        let tokens = Lexer::new(&edited).relex(&old_tokens, &edit);
+        proptest::prop_assert_eq!(tokens, Lexer::lex_all(&edited).0, "{:?}", edited);
+    }
+
+    #[test]
+    fn lossless_roundtrip(source in source_strategy()) {
+        let (tokens, _, _) = Lexer::lex_all(&source);
+        proptest::prop_assume!(!tokens.is_empty());
+
+        let lossless: Vec<TokenTree> = Lexer::new(&source)
+            .with_lossless(true)
+            .with_recovery(true)
+            .filter_map(Result::ok)
+            .collect();
+
+        proptest::prop_assert_eq!(lossless.len(), tokens.len());
+        proptest::prop_assert_eq!(lossless.to_source(), source);
+    }
+}
+
+proptest::proptest! {
+    // most generated sources have errors, which the printer can't render.
+    #![proptest_config(proptest::test_runner::Config {
+        max_global_rejects: 1 << 16,
+        ..Default::default()
+    })]
+
+    #[test]
+    fn to_source_relexes(source in source_strategy()) {
+        let (tokens, _, errors) = Lexer::lex_all(&source);
+        proptest::prop_assume!(errors.iter().all(LexError::is_warning));
+        // a raw string can't hold a `\r` right before a line break, since
+        // `\r\n` in it is read as `\n`.
+        proptest::prop_assume!(!source.contains("\r\r"));
+
+        let printed = tokens.to_source();
+        let (reprinted, _, errors) = Lexer::lex_all(&printed);
+
+        proptest::prop_assert!(
+            errors.iter().all(LexError::is_warning),
+            "{:?} has errors {:?}",
+            printed,
+            errors
+        );
+        proptest::prop_assert_eq!(shape(&reprinted), shape(&tokens));
+        proptest::prop_assert_eq!(reprinted.to_source(), printed);
+    }
+}
@@ -0,0 +1,115 @@
+//! Locations in the source files of a compilation.
+//!
+//! A [`Span`] is a range of bytes in a specific file, so diagnostics can point
+//! at more than one file, and code which moves tokens between files (such as
+//! macro expansion) doesn't lose track of where they came from.
+
+use std::fmt;
+use std::ops::Range;
+
+/// The identifier of a file in a compilation, such as a file in a
+/// `SourceMap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(pub usize);
+
+/// A range of bytes in a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The file this span is in.
+    pub file: FileId,
+
+    /// The byte index this span starts at.
+    pub start: usize,
+
+    /// The byte index after the end of this span.
+    pub end: usize,
+}
+
+impl Span {
+    /// Creates a span from `start` to `end` in `file`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is after `end`.
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        assert!(
+            start <= end,
+            "span starts at {} after its end {}",
+            start,
+            end
+        );
+        Self { file, start, end }
+    }
+
+    /// Creates a span covering `loc` in `file`, such as the location of a
+    /// token lexed from it.
+    pub fn at(file: FileId, loc: Range<usize>) -> Self {
+        Self::new(file, loc.start, loc.end)
+    }
+
+    /// Creates an empty span at `idx` in `file`, such as the end of the file.
+    pub fn empty(file: FileId, idx: usize) -> Self {
+        Self::new(file, idx, idx)
+    }
+
+    /// Returns the range of bytes this span covers in its file.
+    pub fn loc(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the number of bytes this span covers.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns whether or not this span covers no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns whether or not `other` is inside of this span.  Spans in
+    /// different files never contain each other.
+    pub fn contains(&self, other: Span) -> bool {
+        self.file == other.file && self.start <= other.start && other.end <= self.end
+    }
+
+    /// Returns whether or not the byte at `idx` in the file of this span is
+    /// inside of it.
+    pub fn contains_idx(&self, idx: usize) -> bool {
+        self.start <= idx && idx < self.end
+    }
+
+    /// Returns the smallest span covering both this span and `other`, and
+    /// everything between them, or `None` if they are in different files.
+    pub fn join(&self, other: Span) -> Option<Span> {
+        (self.file == other.file).then(|| {
+            Span::new(
+                self.file,
+                self.start.min(other.start),
+                self.end.max(other.end),
+            )
+        })
+    }
+
+    /// Returns the empty span at the start of this span.
+    pub fn shrink_to_start(&self) -> Span {
+        Span::empty(self.file, self.start)
+    }
+
+    /// Returns the empty span at the end of this span.
+    pub fn shrink_to_end(&self) -> Span {
+        Span::empty(self.file, self.end)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.loc()
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}..{}", self.file.0, self.start, self.end)
+    }
+}
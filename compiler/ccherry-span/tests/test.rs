@@ -0,0 +1,38 @@
+extern crate ccherry_span;
+
+use ccherry_span::{FileId, Span};
+
+#[test]
+fn spans() {
+    let main = FileId(0);
+    let other = FileId(1);
+
+    let span = Span::at(main, 4..10);
+    assert_eq!(span, Span::new(main, 4, 10));
+    assert_eq!(span.loc(), 4..10);
+    assert_eq!(span.len(), 6);
+    assert!(!span.is_empty());
+    assert!(Span::empty(main, 3).is_empty());
+    assert_eq!(span.to_string(), "0:4..10");
+
+    assert!(span.contains(Span::at(main, 5..10)));
+    assert!(span.contains(span.shrink_to_end()));
+    assert!(!span.contains(Span::at(main, 3..5)));
+    assert!(!span.contains(Span::at(other, 5..6)));
+    assert!(span.contains_idx(4));
+    assert!(!span.contains_idx(10));
+
+    assert_eq!(
+        span.join(Span::at(main, 12..14)),
+        Some(Span::at(main, 4..14))
+    );
+    assert_eq!(span.join(Span::at(main, 5..6)), Some(span));
+    assert_eq!(span.join(Span::at(other, 0..1)), None);
+    assert_eq!(span.shrink_to_start(), Span::empty(main, 4));
+}
+
+#[test]
+#[should_panic]
+fn backwards_span() {
+    Span::new(FileId(0), 2, 1);
+}
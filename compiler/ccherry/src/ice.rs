@@ -0,0 +1,256 @@
+//! Internal compiler error (ICE) reporting.
+//!
+//! When the compiler panics, the panic is caught and a reproduction bundle is
+//! written to a new `ccherry-ice-<time>-<pid>` directory, containing the
+//! compiler version, the command line, the panic message with a backtrace and
+//! the source file which caused it.  Optionally, the source is also minimized
+//! first, by removing as many of its tokens as possible, so bug reports arrive
+//! small.
+
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ccherry_lexer::{Lexer, ToSource, TokenTree};
+
+/// The maximum number of times the minimizer will run the compiler.
+const MAX_MINIMIZE_ATTEMPTS: usize = 2000;
+
+/// Information about a panic inside the compiler.
+#[derive(Clone, Debug)]
+pub struct Ice {
+    /// The panic message.
+    pub message: String,
+
+    /// The location of the panic in the compiler's source code, if known.
+    pub location: Option<String>,
+
+    /// The backtrace of the panic.
+    pub backtrace: String,
+}
+
+/// The most recent panic, stored by the panic hook.
+static LAST_ICE: Mutex<Option<Ice>> = Mutex::new(None);
+
+thread_local! {
+    /// Whether or not this thread is running inside of [`catch`].
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs a panic hook which records panics inside of [`catch`] instead of
+/// printing them.  The recorded panic can be retrieved with [`take`].  Panics
+/// anywhere else are passed to the previous hook, so they are still printed.
+pub fn install_hook() {
+    let previous = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        if !CATCHING.with(Cell::get) {
+            return previous(info);
+        }
+
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "<unknown panic payload>".to_string()
+        };
+
+        let ice = Ice {
+            message,
+            location: info.location().map(|location| location.to_string()),
+            backtrace: Backtrace::force_capture().to_string(),
+        };
+
+        if let Ok(mut last) = LAST_ICE.lock() {
+            *last = Some(ice);
+        }
+    }));
+}
+
+/// Returns the most recent panic recorded by the hook, if any.
+pub fn take() -> Option<Ice> {
+    LAST_ICE.lock().ok().and_then(|mut last| last.take())
+}
+
+/// Runs `f`, returning the panic it caused, if any.  [`install_hook`] must be
+/// called first.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, Ice> {
+    let catching = CATCHING.with(|catching| catching.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|state| state.set(catching));
+
+    match result {
+        Ok(value) => Ok(value),
+        Err(_) => Err(take().unwrap_or(Ice {
+            message: "<unknown panic>".to_string(),
+            location: None,
+            backtrace: String::new(),
+        })),
+    }
+}
+
+/// Splits `source` into chunks, each a run of non-whitespace characters
+/// followed by the whitespace after it.  This is a rough approximation of
+/// tokens, for sources the lexer itself panics on.
+fn chunks(source: &str) -> Vec<String> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut in_whitespace = false;
+
+    for (idx, char) in source.char_indices() {
+        if char.is_whitespace() {
+            in_whitespace = true;
+        } else if in_whitespace {
+            chunks.push(source[start..idx].to_string());
+            start = idx;
+            in_whitespace = false;
+        }
+    }
+
+    if start < source.len() {
+        chunks.push(source[start..].to_string());
+    }
+
+    chunks
+}
+
+/// Pushes the source of each of `tokens` to `pieces`, splitting groups into
+/// their opening delimiter, the tokens in them and their closing delimiter,
+/// so the tokens in a group can be removed without removing the group.
+fn push_token_pieces(tokens: &[TokenTree], pieces: &mut Vec<String>) {
+    for token in tokens {
+        match (token, token.trivia()) {
+            (TokenTree::Group(group), Some(trivia)) => {
+                pieces.push(format!("{}{}", trivia.leading, trivia.source));
+                push_token_pieces(&group.tokens, pieces);
+                pieces.push(trivia.trailing.clone());
+            }
+            _ => pieces.push(token.to_source()),
+        }
+    }
+}
+
+/// Splits `source` into the pieces the minimizer removes: its tokens, each
+/// with the whitespace and comments around it, lexed in lossless mode so they
+/// add up to exactly `source`.  If the lexer panics on `source`, or can't give
+/// it back exactly, it is split into whitespace-separated [`chunks`] instead.
+fn pieces(source: &str) -> Vec<String> {
+    let tokens = catch(|| {
+        Lexer::new(source)
+            .with_lossless(true)
+            .with_recovery(true)
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>()
+    });
+
+    if let Ok(tokens) = tokens {
+        let mut pieces = vec![];
+        push_token_pieces(&tokens, &mut pieces);
+        if pieces.concat() == source {
+            return pieces;
+        }
+    }
+
+    chunks(source)
+}
+
+/// Minimizes `source` with delta debugging over its tokens, returning the
+/// smallest source found for which `fails` still returns `true`.
+pub fn minimize(source: &str, mut fails: impl FnMut(&str) -> bool) -> String {
+    let mut pieces = pieces(source);
+    let mut granularity = 2;
+    let mut attempts = 0;
+
+    while pieces.len() >= 2 && attempts < MAX_MINIMIZE_ATTEMPTS {
+        let size = pieces.len().div_ceil(granularity);
+        let mut reduced = false;
+
+        for start in (0..pieces.len()).step_by(size) {
+            let end = (start + size).min(pieces.len());
+            let candidate: Vec<String> = pieces[..start]
+                .iter()
+                .chain(&pieces[end..])
+                .cloned()
+                .collect();
+
+            attempts += 1;
+            if fails(&candidate.concat()) {
+                pieces = candidate;
+                granularity = (granularity - 1).max(2);
+                reduced = true;
+                break;
+            }
+
+            if attempts >= MAX_MINIMIZE_ATTEMPTS {
+                break;
+            }
+        }
+
+        if !reduced {
+            if granularity >= pieces.len() {
+                break;
+            }
+
+            granularity = (granularity * 2).min(pieces.len());
+        }
+    }
+
+    pieces.concat()
+}
+
+/// Writes a reproduction bundle for `ice` to a new directory in the current
+/// working directory, and returns the path to the directory.
+pub fn write_bundle(
+    ice: &Ice,
+    input: &str,
+    source: &str,
+    minimized: Option<&str>,
+) -> std::io::Result<PathBuf> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    // the directory is always new, so a bundle is never written over another
+    // one from the same second.
+    let mut attempt = 0;
+    let dir = loop {
+        let mut name = format!("ccherry-ice-{}-{}", time, std::process::id());
+        if attempt > 0 {
+            let _ = write!(name, "-{}", attempt);
+        }
+
+        let dir = PathBuf::from(name);
+        match std::fs::create_dir(&dir) {
+            Ok(()) => break dir,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    };
+
+    let mut report = String::new();
+    let _ = writeln!(report, "ccherry {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "command: {}", std::env::args().collect::<Vec<_>>().join(" "));
+    let _ = writeln!(report, "input: {}", input);
+    let _ = writeln!(report);
+    let _ = writeln!(report, "panicked at '{}'", ice.message);
+    if let Some(location) = &ice.location {
+        let _ = writeln!(report, "  at {}", location);
+    }
+    let _ = writeln!(report);
+    let _ = writeln!(report, "{}", ice.backtrace);
+
+    std::fs::write(dir.join("report.txt"), report)?;
+    std::fs::write(dir.join("input.ch"), source)?;
+    if let Some(minimized) = minimized {
+        std::fs::write(dir.join("minimized.ch"), minimized)?;
+    }
+
+    Ok(dir)
+}
@@ -0,0 +1,130 @@
+//! Resource limits for `--limit`, so untrusted input, such as on the
+//! playground server or in CI, can't use up the machine it is compiled on.
+
+use std::time::{Duration, Instant};
+
+use ccherry_diagnostics::Diagnostic;
+
+/// The names of the limits, as used with `--limit NAME=VALUE`.
+pub const LIMITS: &[&str] = &["file-size", "tokens", "diagnostics", "time"];
+
+/// The resource limits of a compilation.  Every limit is off by default.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    /// The largest input file, in bytes.
+    pub file_size: Option<u64>,
+
+    /// The most token trees in the input file, including the ones nested in
+    /// groups.
+    pub tokens: Option<usize>,
+
+    /// The most diagnostics shown.  The rest are counted, but not shown.
+    pub diagnostics: Option<usize>,
+
+    /// How long the compilation may take.  This is checked between phases, so
+    /// a single phase may run past it.
+    pub time: Option<Duration>,
+}
+
+impl Limits {
+    /// Sets a limit from a `NAME=VALUE` option, such as `file-size=1M`.
+    /// Returns an error message if the option is invalid.
+    pub fn set(&mut self, option: &str) -> Result<(), String> {
+        let (name, value) = option.split_once('=')
+            .ok_or_else(|| format!("invalid limit `{}`, expected `<{}>=<value>`", option, LIMITS.join("/")))?;
+        let invalid = |expected: &str| format!("invalid value `{}` for `{}`, expected {}", value, name, expected);
+
+        match name {
+            "file-size" => {
+                let size = parse_size(value).ok_or_else(|| invalid("a size, such as `512K` or `4M`"))?;
+                self.file_size = Some(size);
+            }
+            "tokens" => self.tokens = Some(value.parse().map_err(|_| invalid("a number"))?),
+            "diagnostics" => self.diagnostics = Some(value.parse().map_err(|_| invalid("a number"))?),
+            "time" => {
+                let time = parse_duration(value).ok_or_else(|| invalid("a duration, such as `500ms` or `10s`"))?;
+                self.time = Some(time);
+            }
+            _ => return Err(format!("unknown limit `{}`, options: {}", name, LIMITS.join(", "))),
+        }
+
+        Ok(())
+    }
+
+    /// Returns an error if the input file, which is `size` bytes long, is too
+    /// large.
+    pub fn check_file_size(&self, size: u64) -> Result<(), Diagnostic<()>> {
+        match self.file_size {
+            Some(limit) if size > limit => Err(exceeded("input file is too large", format!(
+                "the file is {} bytes long, but the limit is {} bytes (`--limit file-size`)", size, limit))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if there are more than the allowed number of token
+    /// trees.
+    pub fn check_tokens(&self, count: usize) -> Result<(), Diagnostic<()>> {
+        match self.tokens {
+            Some(limit) if count > limit => Err(exceeded("input file has too many tokens", format!(
+                "the file has {} token trees, but the limit is {} (`--limit tokens`)", count, limit))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the compilation, which started at `start`, has run
+    /// past its time budget.  `phase` is the phase which just finished.
+    pub fn check_time(&self, start: Instant, phase: &str) -> Result<(), Diagnostic<()>> {
+        let elapsed = start.elapsed();
+
+        match self.time {
+            Some(limit) if elapsed > limit => Err(exceeded("compilation ran out of time", format!(
+                "stopped after {} took the compilation to {}ms, but the limit is {}ms (`--limit time`)",
+                phase, elapsed.as_millis(), limit.as_millis()))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes the diagnostics past the allowed number from `diagnostics`.
+    /// If any were removed, a note saying how many is added in their place.
+    pub fn truncate_diagnostics(&self, diagnostics: &mut Vec<Diagnostic<()>>) {
+        let limit = match self.diagnostics {
+            Some(limit) if diagnostics.len() > limit => limit,
+            _ => return,
+        };
+
+        let hidden = diagnostics.len() - limit;
+        diagnostics.truncate(limit);
+        diagnostics.push(Diagnostic::note()
+            .with_message(format!("{} more diagnostics were not shown", hidden))
+            .with_notes(vec![format!("only the first {} are shown (`--limit diagnostics`)", limit)]));
+    }
+}
+
+/// Returns the error for an exceeded limit.
+fn exceeded(message: &str, note: String) -> Diagnostic<()> {
+    Diagnostic::error()
+        .with_message(message)
+        .with_notes(vec![note])
+}
+
+/// Parses a size in bytes, with an optional `K`, `M` or `G` suffix for
+/// kibibytes, mebibytes or gibibytes.
+fn parse_size(value: &str) -> Option<u64> {
+    let (digits, scale) = match value.char_indices().last()? {
+        (idx, 'K' | 'k') => (&value[..idx], 1 << 10),
+        (idx, 'M' | 'm') => (&value[..idx], 1 << 20),
+        (idx, 'G' | 'g') => (&value[..idx], 1 << 30),
+        _ => (value, 1),
+    };
+
+    digits.parse::<u64>().ok()?.checked_mul(scale)
+}
+
+/// Parses a duration in milliseconds (`ms`) or seconds (`s`).
+fn parse_duration(value: &str) -> Option<Duration> {
+    if let Some(millis) = value.strip_suffix("ms") {
+        millis.parse().ok().map(Duration::from_millis)
+    } else {
+        value.strip_suffix('s')?.parse().ok().map(Duration::from_secs)
+    }
+}
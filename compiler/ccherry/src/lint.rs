@@ -0,0 +1,150 @@
+//! The `lint` subcommand, which only runs the lint passes, as a fast check
+//! before committing.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ccherry_diagnostics::{page, Diagnostic, DiagnosticEmitter, DiagnosticTheme, Severity};
+use ccherry_lexer::{ambiguous_operators, apply_fixes, Lexer, Lint};
+
+/// The name of every lint, which may be passed to `--deny`.
+pub const LINTS: &[&str] = &["ambiguous_operators"];
+
+/// Configuration for the `lint` subcommand.
+pub struct LintConfig {
+    /// The files to lint, and the directories to search for `.ch` files.
+    pub paths: Vec<String>,
+
+    /// Whether or not to apply the fixes of warnings to the linted files.
+    pub fix: bool,
+
+    /// The lints which are reported as errors.  `warnings` denies every lint.
+    pub deny: Vec<String>,
+}
+
+impl LintConfig {
+    /// Returns whether or not `lint` is denied.
+    fn is_denied(&self, lint: &str) -> bool {
+        self.deny.iter().any(|denied| denied == "warnings" || denied == lint)
+    }
+}
+
+/// Runs every lint pass over the source string `source`.
+fn lint(source: &str) -> (Vec<Diagnostic<()>>, Vec<Lint>) {
+    let (tokens, _, diagnostics) = Lexer::new(source).tokenize_all(());
+    (diagnostics, ambiguous_operators(&tokens))
+}
+
+/// Adds `path` to `files` if it is a file, or every `.ch` file in it if it is
+/// a directory.  Hidden directories and `target` directories are skipped.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+
+        if entry.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_files(&entry, files)?;
+            }
+        } else if entry.extension().is_some_and(|extension| extension == "ch") {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Emits `diagnostics` with `emitter`, or adds them to `paged` if the output
+/// is shown in a pager once every file has been linted.
+fn emit(emitter: DiagnosticEmitter, diagnostics: Vec<Diagnostic<()>>, paged: &mut Option<Vec<u8>>) {
+    match paged {
+        Some(paged) => paged.extend(emitter.render_all(&diagnostics)),
+        None => emitter.emit_all(&diagnostics),
+    }
+}
+
+/// Runs the `lint` subcommand.  Returns whether or not an error was reported,
+/// including warnings from denied lints.  With `pager`, the diagnostics of
+/// every file are shown in a pager at the end.
+pub fn run(config: &LintConfig, theme: DiagnosticTheme, pager: bool) -> bool {
+    let mut failed = false;
+    let mut files = vec![];
+    let mut paged = pager.then(Vec::new);
+
+    for path in &config.paths {
+        if let Err(err) = collect_files(Path::new(path), &mut files) {
+            let emitter = DiagnosticEmitter::new("".into(), "".into())
+                .with_theme(theme.clone());
+            emit(emitter, vec![Diagnostic::error()
+                .with_message(format!("unable to read `{}`: {}", path, err))], &mut paged);
+            failed = true;
+        }
+    }
+
+    for file in files {
+        let mut source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(err) => {
+                let emitter = DiagnosticEmitter::new("".into(), "".into())
+                    .with_theme(theme.clone());
+                emit(emitter, vec![Diagnostic::error()
+                    .with_message(format!("unable to open `{}`: {}", file.display(), err))], &mut paged);
+                failed = true;
+                continue;
+            }
+        };
+
+        let (mut diagnostics, mut lints) = lint(&source);
+
+        // fixes are only applied to files which lexed without errors, since
+        // recovery may have guessed wrong about what the source meant.
+        let has_errors = diagnostics.iter().any(|diagnostic| diagnostic.severity >= Severity::Error);
+        if config.fix && !has_errors && lints.iter().any(|lint| lint.fix.is_some()) {
+            source = apply_fixes(&source, &lints);
+
+            if let Err(err) = fs::write(&file, &source) {
+                let emitter = DiagnosticEmitter::new("".into(), "".into())
+                    .with_theme(theme.clone());
+                emit(emitter, vec![Diagnostic::error()
+                    .with_message(format!("unable to write fixes to `{}`: {}", file.display(), err))], &mut paged);
+                failed = true;
+            }
+
+            (diagnostics, lints) = lint(&source);
+        }
+
+        for lint in lints {
+            let mut diagnostic = lint.diagnostic;
+
+            if config.is_denied(lint.name) {
+                diagnostic.severity = Severity::Error;
+                diagnostic.notes.push(format!("the `{}` lint is denied", lint.name));
+            } else {
+                diagnostic.notes.push(format!("this is the `{}` lint", lint.name));
+            }
+
+            diagnostics.push(diagnostic);
+        }
+
+        failed |= diagnostics.iter().any(|diagnostic| diagnostic.severity >= Severity::Error);
+
+        let emitter = DiagnosticEmitter::new(file.display().to_string(), source)
+            .with_theme(theme.clone());
+        emit(emitter, diagnostics, &mut paged);
+    }
+
+    if let Some(paged) = paged {
+        page(&paged).unwrap();
+    }
+
+    failed
+}
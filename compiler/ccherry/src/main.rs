@@ -1,19 +1,49 @@
+mod ice;
+mod limits;
+mod lint;
+mod memory;
+
+use std::io::IsTerminal;
 use std::process::exit;
+use std::time::Instant;
 
 use clap::{Arg, Command};
-use ccherry_diagnostics::{Diagnostic, DiagnosticTheme, DiagnosticEmitter, DisplayStyle};
-use ccherry_lexer::Lexer;
+use ccherry_diagnostics::{page, Diagnostic, DiagnosticFormat, DiagnosticTheme, DiagnosticEmitter, DisplayStyle, Severity};
+use ccherry_lexer::{ambiguous_operators, Lexer, TokenTree};
+use limits::Limits;
+use lint::LintConfig;
+use memory::{CountingAllocator, MemoryStats};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::new();
 
 /// Configuration for the Cherry command line.
 pub struct CherryConfig {
     /// The path to the file to compile.
     input: String,
 
+    /// The configuration of the `lint` subcommand, if it is being run instead
+    /// of compiling `input`.
+    lint: Option<LintConfig>,
+
     /// The diagnostic style to use.
     diagnostic_style: DisplayStyle,
 
     /// The diagnostic theme to use.
     theme: DiagnosticTheme,
+
+    /// Whether or not to show diagnostics in a pager (`--pager`).
+    pager: bool,
+
+    /// The resource limits of the compilation (`--limit`).
+    limits: Limits,
+
+    /// Whether or not to print memory usage statistics (`-Z memory-stats`).
+    memory_stats: bool,
+
+    /// Whether or not to minimize the input of an internal compiler error
+    /// (`-Z minimize-ice`).
+    minimize_ice: bool,
 }
 
 impl CherryConfig {
@@ -21,6 +51,26 @@ impl CherryConfig {
         let args = Command::new("ccherry")
             .about("the Cherry compiler")
             .bin_name("ccherry")
+            .subcommand_negates_reqs(true)
+            .args_conflicts_with_subcommands(true)
+            .subcommand(Command::new("lint")
+                .about("runs the lint passes without compiling, exiting with an error if a denied lint fires")
+                .arg(Arg::new("paths")
+                    .index(1)
+                    .takes_value(true)
+                    .multiple_values(true)
+                    .default_value(".")
+                    .help("the files to lint, and the directories to search for `.ch` files"))
+                .arg(Arg::new("fix")
+                    .long("fix")
+                    .help("apply the fixes of warnings which have one"))
+                .arg(Arg::new("deny")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .short('D')
+                    .long("deny")
+                    .value_name("LINT")
+                    .help("report a lint as an error, or every lint with `warnings`")))
             .arg(Arg::new("input")
                 .index(1)
                 .takes_value(true)
@@ -30,34 +80,87 @@ impl CherryConfig {
                 .takes_value(true)
                 .required(false)
                 .long("diagnostic-style")
+                .global(true)
                 .alias("style")
                 .alias("d-style")
                 .alias("diag-style")
                 .alias("diagstyle")
                 .alias("display-style")
                 .alias("displaystyle")
-                .help("what diagnostic style to use (rich, medium, short)"))
+                .help("what diagnostic style to use (rich, medium, short, json)"))
+            .arg(Arg::new("severity-style")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .long("severity-style")
+                .global(true)
+                .value_name("SEVERITY=STYLE")
+                .help("the diagnostic style to use for one severity, such as `note=short`"))
             .arg(Arg::new("theme")
                 .takes_value(true)
                 .required(false)
                 .long("theme")
+                .global(true)
                 .alias("diagnostic-theme")
                 .alias("d-theme")
                 .help("the diagnostic theme to use."))
+            .arg(Arg::new("error-docs-url")
+                .takes_value(true)
+                .required(false)
+                .long("error-docs-url")
+                .global(true)
+                .help("the base URL of the error code documentation, linked from JSON diagnostics"))
+            .arg(Arg::new("pager")
+                .long("pager")
+                .global(true)
+                .help("show diagnostics in `$PAGER` (or `less`) when writing to a terminal"))
+            .arg(Arg::new("limit")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .long("limit")
+                .value_name("LIMIT=VALUE")
+                .help("limit the resources the compilation may use (file-size, tokens, diagnostics, time), such as `file-size=4M`"))
+            .arg(Arg::new("unstable")
+                .takes_value(true)
+                .required(false)
+                .multiple_occurrences(true)
+                .short('Z')
+                .help("unstable options for debugging the compiler (memory-stats, minimize-ice)"))
             .get_matches();
         
-        let input = args.value_of("input").unwrap();
+        let input = args.value_of("input").unwrap_or_default();
+
+        let lint = args.subcommand_matches("lint").map(|lint| {
+            let deny: Vec<String> = lint.values_of("deny").into_iter().flatten().map(String::from).collect();
+            for denied in &deny {
+                if denied != "warnings" && !lint::LINTS.contains(&denied.as_str()) {
+                    let emitter = DiagnosticEmitter::new("".into(), "".into());
+                    emitter.emit(&Diagnostic::error()
+                        .with_message(format!("unknown lint `{}`, options: warnings, {}", denied, lint::LINTS.join(", "))));
+                    exit(1);
+                }
+            }
+
+            LintConfig {
+                paths: lint.values_of("paths").into_iter().flatten().map(String::from).collect(),
+                fix: lint.is_present("fix"),
+                deny,
+            }
+        });
 
         let mut diagnostic_style = DisplayStyle::Rich;
+        let mut diagnostic_format = DiagnosticFormat::Human;
         if let Some(display_style) = args.value_of("diagnostic-style") {
             match display_style.to_lowercase().as_str() {
                 "rich" | "default" => diagnostic_style = DisplayStyle::Rich,
                 "medium" => diagnostic_style = DisplayStyle::Medium,
                 "short" => diagnostic_style = DisplayStyle::Short,
+                "json" => diagnostic_format = DiagnosticFormat::Json,
                 _ => {
                     let emitter = DiagnosticEmitter::new("".into(), "".into());
                     emitter.emit(&Diagnostic::error()
-                        .with_message("invalid diagnostic style, options: rich/default, medium, short"));
+                        .with_message("invalid diagnostic style, options: rich/default, medium, short, json"));
                 }
             }
         }
@@ -75,34 +178,223 @@ impl CherryConfig {
             }
         }
 
+        theme.format = diagnostic_format;
+        for style_override in args.values_of("severity-style").into_iter().flatten() {
+            let parsed = style_override.split_once('=').and_then(|(severity, style)| {
+                let severity = match severity.to_lowercase().as_str() {
+                    "bug" => Severity::Bug,
+                    "error" => Severity::Error,
+                    "warning" => Severity::Warning,
+                    "note" => Severity::Note,
+                    "help" => Severity::Help,
+                    _ => return None,
+                };
+                let style = match style.to_lowercase().as_str() {
+                    "rich" | "default" => DisplayStyle::Rich,
+                    "medium" => DisplayStyle::Medium,
+                    "short" => DisplayStyle::Short,
+                    _ => return None,
+                };
+
+                Some((severity, style))
+            });
+
+            match parsed {
+                Some((severity, style)) => theme = theme.with_style_override(severity, style),
+                None => {
+                    let emitter = DiagnosticEmitter::new("".into(), "".into());
+                    emitter.emit(&Diagnostic::error()
+                        .with_message(format!("invalid severity style `{}`, expected `<bug/error/warning/note/help>=<rich/medium/short>`", style_override)));
+                    exit(1);
+                }
+            }
+        }
+        if let Some(url) = args.value_of("error-docs-url") {
+            theme = theme.with_documentation_url(url.into());
+        }
+
+        // JSON diagnostics are for other programs, and a pager only gets in
+        // their way.
+        let pager = args.is_present("pager")
+            && diagnostic_format == DiagnosticFormat::Human
+            && std::io::stdout().is_terminal();
+
+        let mut limits = Limits::default();
+        for option in args.values_of("limit").into_iter().flatten() {
+            if let Err(message) = limits.set(option) {
+                let emitter = DiagnosticEmitter::new("".into(), "".into());
+                emitter.emit(&Diagnostic::error()
+                    .with_message(message));
+                exit(1);
+            }
+        }
+
+        let mut memory_stats = false;
+        let mut minimize_ice = false;
+        for option in args.values_of("unstable").into_iter().flatten() {
+            match option {
+                "memory-stats" => memory_stats = true,
+                "minimize-ice" => minimize_ice = true,
+                _ => {
+                    let emitter = DiagnosticEmitter::new("".into(), "".into());
+                    emitter.emit(&Diagnostic::error()
+                        .with_message(format!("unknown unstable option `{}`, options: memory-stats, minimize-ice", option)));
+                    exit(1);
+                }
+            }
+        }
+
         Self {
             input: input.into(),
+            lint,
             diagnostic_style,
             theme,
+            pager,
+            limits,
+            memory_stats,
+            minimize_ice,
         }
     }
 }
 
+/// Counts the token trees in `tokens`, including the ones nested in groups,
+/// and the comments attached to them.
+fn count_tokens(tokens: &[TokenTree]) -> (usize, usize) {
+    let mut count = 0;
+    let mut comments = 0;
+
+    for token in tokens {
+        count += 1;
+        comments += token.comments().len();
+
+        if let TokenTree::Group(group) = token {
+            let (nested, nested_comments) = count_tokens(&group.tokens);
+            count += nested;
+            comments += nested_comments;
+        }
+    }
+
+    (count, comments)
+}
+
+/// Reports an internal compiler error, writing a reproduction bundle.
+fn report_ice(ice: ice::Ice, args: &CherryConfig, source: &str, theme: DiagnosticTheme) -> ! {
+    let minimized = if args.minimize_ice {
+        Some(ice::minimize(source, |candidate| match ice::catch(|| analyze(candidate)) {
+            Ok(_) => false,
+            // the message may contain details that change as the input shrinks,
+            // such as lengths, so only the location is compared.
+            Err(other) => other.location == ice.location,
+        }))
+    } else {
+        None
+    };
+
+    let mut notes = vec![
+        format!("panicked at '{}'", ice.message),
+        "this is a bug in the Cherry compiler, please report it at https://github.com/trimorphdev/cherry/issues".to_string(),
+    ];
+    match ice::write_bundle(&ice, &args.input, source, minimized.as_deref()) {
+        Ok(path) => notes.push(format!("a reproduction bundle was written to `{}`", path.display())),
+        Err(err) => notes.push(format!("unable to write a reproduction bundle: {}", err)),
+    }
+
+    let emitter = DiagnosticEmitter::new("".into(), "".into())
+        .with_theme(theme);
+    emitter.emit(&Diagnostic::new(Severity::Bug)
+        .with_message("internal compiler error")
+        .with_notes(notes));
+    exit(101);
+}
+
+/// Lexes `source` and runs the lints on its tokens, returning the tokens and
+/// the diagnostics found.
+fn analyze(source: &str) -> (Vec<TokenTree>, Vec<Diagnostic<()>>) {
+    let (tokens, _, mut diagnostics) = Lexer::new(source).tokenize_all(());
+    diagnostics.extend(ambiguous_operators(&tokens).into_iter().map(|lint| lint.diagnostic));
+    (tokens, diagnostics)
+}
+
+/// Compiles `source`, the contents of the input file, printing its
+/// diagnostics and tokens.  Any panic in here is an internal compiler error.
+fn compile(args: &CherryConfig, source: &str, theme: DiagnosticTheme, start: Instant, stats: &mut MemoryStats) {
+    let (tokens, mut diagnostics) = analyze(source);
+    let mut failed = diagnostics.iter().any(|diagnostic| diagnostic.severity >= Severity::Error);
+    args.limits.truncate_diagnostics(&mut diagnostics);
+
+    // exceeded limits are reported after truncating, so they are always shown.
+    for result in [
+        args.limits.check_tokens(count_tokens(&tokens).0),
+        args.limits.check_time(start, "lexing"),
+    ] {
+        if let Err(diagnostic) = result {
+            diagnostics.push(diagnostic);
+            failed = true;
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        let emitter = DiagnosticEmitter::new(args.input.clone(), source.to_string())
+            .with_theme(theme);
+        if args.pager {
+            page(&emitter.render_all(&diagnostics)).unwrap();
+        } else {
+            emitter.emit_all(&diagnostics);
+        }
+
+        if failed {
+            exit(1);
+        }
+    }
+
+    for token in &tokens {
+        println!("{:#?}", token);
+    }
+
+    let (token_count, comment_count) = count_tokens(&tokens);
+    stats.record("lex", &ALLOCATOR, vec![
+        ("top-level tokens", tokens.len()),
+        ("token trees", token_count),
+        ("comments", comment_count),
+        ("token tree size (bytes)", std::mem::size_of::<TokenTree>()),
+    ]);
+
+    if args.memory_stats {
+        stats.print();
+    }
+}
+
 fn main() {
     let args = CherryConfig::parse();
+    ice::install_hook();
+
+    let mut theme = args.theme.clone();
+    theme.display_style = args.diagnostic_style.clone();
 
-    let mut theme = args.theme;
-    theme.display_style = args.diagnostic_style;
+    if let Some(lint) = &args.lint {
+        exit(lint::run(lint, theme, args.pager) as i32);
+    }
+
+    let start = Instant::now();
+    let mut stats = MemoryStats::new();
+
+    // the size is checked before reading the file, so a huge file is never
+    // loaded into memory.
+    if let Ok(metadata) = std::fs::metadata(&args.input) {
+        if let Err(diagnostic) = args.limits.check_file_size(metadata.len()) {
+            let emitter = DiagnosticEmitter::new("".into(), "".into())
+                .with_theme(theme);
+            emitter.emit(&diagnostic);
+            exit(1);
+        }
+    }
 
     match std::fs::read_to_string(args.input.clone()) {
         Ok(str) => {
-            let lexer = Lexer::new(&str.clone());
-
-            for token in lexer {
-                match token {
-                    Ok(token) => println!("{:#?}", token),
-                    Err(diagnostic) => {
-                        let emitter = DiagnosticEmitter::new(args.input, str)
-                            .with_theme(theme);
-                        emitter.emit(&diagnostic);
-                        exit(1);
-                    }
-                }
+            stats.record("read", &ALLOCATOR, vec![("source bytes", str.len())]);
+
+            if let Err(ice) = ice::catch(|| compile(&args, &str, theme.clone(), start, &mut stats)) {
+                report_ice(ice, &args, &str, theme);
             }
         },
         Err(_) => {
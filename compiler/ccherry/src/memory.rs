@@ -0,0 +1,156 @@
+//! Memory usage tracking for `-Z memory-stats`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A global allocator which keeps track of the current and peak number of
+/// bytes allocated, forwarding the allocations themselves to the system
+/// allocator.
+pub struct CountingAllocator {
+    /// The number of bytes currently allocated.
+    current: AtomicUsize,
+
+    /// The largest number of bytes allocated at once.
+    peak: AtomicUsize,
+}
+
+impl CountingAllocator {
+    /// Creates a new [`CountingAllocator`].
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes currently allocated.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Returns the largest number of bytes allocated at once.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Records that `size` more bytes are allocated.
+    fn add(&self, size: usize) {
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    /// Records that `size` bytes were deallocated.
+    fn sub(&self, size: usize) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            self.add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.sub(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            self.sub(layout.size());
+            self.add(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// The memory usage at the end of a single compiler phase.
+pub struct Phase {
+    /// The name of the phase.
+    pub name: &'static str,
+
+    /// The number of bytes allocated when the phase ended.
+    pub current: usize,
+
+    /// The peak number of bytes allocated so far.
+    pub peak: usize,
+
+    /// Extra statistics about what the phase produced, such as node counts.
+    pub details: Vec<(&'static str, usize)>,
+}
+
+/// Memory statistics collected over a compilation.
+pub struct MemoryStats {
+    /// The phases recorded so far, in order.
+    phases: Vec<Phase>,
+}
+
+impl MemoryStats {
+    /// Creates a new, empty [`MemoryStats`].
+    pub fn new() -> Self {
+        Self { phases: vec![] }
+    }
+
+    /// Records the end of a phase, using the current state of `allocator`.
+    pub fn record(
+        &mut self,
+        name: &'static str,
+        allocator: &CountingAllocator,
+        details: Vec<(&'static str, usize)>,
+    ) {
+        self.phases.push(Phase {
+            name,
+            current: allocator.current(),
+            peak: allocator.peak(),
+            details,
+        });
+    }
+
+    /// Prints the statistics to stderr.
+    pub fn print(&self) {
+        eprintln!("{:<12} {:>14} {:>14}", "phase", "current", "peak");
+
+        for phase in &self.phases {
+            eprintln!(
+                "{:<12} {:>14} {:>14}",
+                phase.name,
+                format_bytes(phase.current),
+                format_bytes(phase.peak)
+            );
+
+            for (name, value) in &phase.details {
+                eprintln!("  {:<24} {:>14}", name, value);
+            }
+        }
+    }
+}
+
+impl Default for MemoryStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats a number of bytes in a human readable way.
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.2} MiB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.2} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
@@ -0,0 +1,133 @@
+//! The ui tests, which compile each `.ch` file in `tests/ui` at the root of the
+//! workspace and compare what the compiler prints against the `.expected` file
+//! next to it.
+//!
+//! The output is normalized before it is compared: colors are turned off, and
+//! the path of `tests/ui` is replaced with `$DIR`, so the expected files are
+//! the same on every machine.  A case can pass extra options to the compiler
+//! with a `// compile-flags: ...` line at the top of the file.
+//!
+//! After an intended change to the output, run
+//! `cargo test -p ccherry --test ui -- --bless` to rewrite the expected files,
+//! and review the diff.  Any other argument only runs the cases whose path
+//! contains it.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let filters: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../tests/ui")
+        .canonicalize()
+        .expect("`tests/ui` should exist at the root of the workspace");
+
+    let mut cases = vec![];
+    collect_cases(&root, &mut cases);
+    cases.sort();
+
+    let mut passed = 0;
+    let mut failed = vec![];
+
+    for case in &cases {
+        let name = case.strip_prefix(&root).unwrap().display().to_string();
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue;
+        }
+
+        let actual = run(&root, case);
+        let expected_path = case.with_extension("expected");
+        let expected = fs::read_to_string(&expected_path).ok();
+
+        if expected.as_deref() == Some(actual.as_str()) {
+            passed += 1;
+        } else if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            println!("blessed {}", name);
+            passed += 1;
+        } else {
+            println!("ui test {} failed", name);
+            match expected {
+                Some(expected) => print_diff(&expected, &actual),
+                None => println!("  no `.expected` file, run with `--bless` to create it"),
+            }
+            failed.push(name);
+        }
+    }
+
+    println!("ui tests: {} passed, {} failed", passed, failed.len());
+    if !failed.is_empty() {
+        for name in failed {
+            println!("    {}", name);
+        }
+        exit(1);
+    }
+}
+
+/// Adds every `.ch` file in `dir` and its subdirectories to `cases`.
+fn collect_cases(dir: &Path, cases: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            collect_cases(&path, cases);
+        } else if path.extension().is_some_and(|extension| extension == "ch") {
+            cases.push(path);
+        }
+    }
+}
+
+/// Compiles `case`, and returns everything the compiler printed, normalized.
+fn run(root: &Path, case: &Path) -> String {
+    let source = fs::read_to_string(case).unwrap();
+    let flags = source
+        .lines()
+        .take_while(|line| line.starts_with("//"))
+        .filter_map(|line| line.strip_prefix("// compile-flags:"))
+        .flat_map(str::split_whitespace);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_ccherry"))
+        .arg(case)
+        .args(flags)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+
+    if output.status.code() == Some(101) {
+        panic!(
+            "the compiler crashed on {}:\n{}",
+            case.display(),
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+    printed.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    printed.replace(&root.display().to_string(), "$DIR")
+}
+
+/// Prints the lines which differ between `expected` and `actual`.
+fn print_diff(expected: &str, actual: &str) {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    for idx in 0..expected.len().max(actual.len()) {
+        match (expected.get(idx), actual.get(idx)) {
+            (Some(expected), Some(actual)) if expected == actual => {}
+            (expected, actual) => {
+                if let Some(expected) = expected {
+                    println!("  {:>4} - {}", idx + 1, expected);
+                }
+                if let Some(actual) = actual {
+                    println!("  {:>4} + {}", idx + 1, actual);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! The public API of the Cherry compiler, for tools such as editors, linters
+//! and formatters.
+//!
+//! The compiler is split into internal crates (`ccherry-lexer`,
+//! `ccherry-diagnostics` and so on), whose layout changes as the compiler
+//! grows.  This crate re-exports the parts of them which are meant to be used
+//! by other tools, and only changes in a breaking way with a new major
+//! version.  Anything which isn't re-exported here is an implementation
+//! detail, even if it is public in an internal crate.
+//!
+//! The parser and type checker don't exist yet.  They will be added here as
+//! `parse` and `check` once they do.
+
+/// Lexing Cherry source code into token trees.
+pub mod lexer {
+    pub use ccherry_lexer::{
+        ambiguous_operators, apply_fixes, Char, Comment, CommentKind, CommentTable, Delimiter,
+        DisplaySource, Error, EscapeError, EscapeSuggestion, Fix, Float, FloatKind, Group, Iden,
+        Int, IntKind, Keyword, LexError, Lexer, LexerConfig, LexerState, Lint, Loc, Punct, Spacing,
+        Str, StrKind, StrSegment, TextEdit, ToSource, TokenArena, TokenId, TokenKind, TokenTree,
+        Trivia, TriviaKind, TriviaToken,
+    };
+    #[cfg(feature = "parallel")]
+    pub use ccherry_lexer::{lex_files, lex_files_with_config, LexResult};
+}
+
+/// Diagnostics, and rendering them for the terminal or as JSON.
+pub mod diagnostics {
+    pub use ccherry_diagnostics::{
+        documentation_url, primary_label, secondary_label, ColumnEncoding, Diagnostic,
+        DiagnosticEmitter, DiagnosticFormat, DiagnosticTheme, DisplayStyle, Expansion, FileId,
+        Generated, Label, LabelStyle, LineCol, LineIndex, OriginMapping, Severity, SourceFile,
+        SourceMap, Span,
+    };
+}
+
+use diagnostics::Diagnostic;
+use lexer::{Comment, Lexer, LexerConfig, TokenTree};
+
+/// Lexes all of `source` with the default configuration, and returns every
+/// token along with the comments after the last one and every diagnostic
+/// found, including the warnings of the token lints.  Errors are replaced with
+/// [`TokenTree::Error`] tokens, so the rest of the source is still lexed.
+pub fn lex(source: &str) -> (Vec<TokenTree>, Vec<Comment>, Vec<Diagnostic<()>>) {
+    lex_with_config(source, LexerConfig::new())
+}
+
+/// Lexes all of `source` like [`lex`], with the provided configuration.
+pub fn lex_with_config(
+    source: &str,
+    config: LexerConfig,
+) -> (Vec<TokenTree>, Vec<Comment>, Vec<Diagnostic<()>>) {
+    let (tokens, eof_comments, mut diagnostics) =
+        Lexer::new(source).with_config(config).tokenize_all(());
+    diagnostics.extend(
+        lexer::ambiguous_operators(&tokens)
+            .into_iter()
+            .map(|lint| lint.diagnostic),
+    );
+
+    (tokens, eof_comments, diagnostics)
+}
@@ -0,0 +1,22 @@
+extern crate cherry;
+
+use cherry::diagnostics::Severity;
+use cherry::lexer::{LexerConfig, TokenTree};
+
+#[test]
+fn lex() {
+    let (tokens, eof_comments, diagnostics) = cherry::lex("a ` b-1\n// c");
+
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(eof_comments.len(), 1);
+    assert!(matches!(tokens[1], TokenTree::Error(_)));
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[1].severity, Severity::Warning);
+
+    let config = LexerConfig::new().with_keywords(&["fn"]);
+    let (tokens, _, diagnostics) = cherry::lex_with_config("fn main", config);
+
+    assert!(matches!(tokens[0], TokenTree::Keyword(_)));
+    assert!(diagnostics.is_empty());
+}
@@ -0,0 +1,24 @@
+//! The Cherry runtime.
+//!
+//! This library is linked into every binary produced by the Cherry compiler.
+//! It only provides what generated code can't reasonably do on its own, such
+//! as formatting panic messages and aborting the process.
+//!
+//! Every function called by generated code is prefixed with `cherry_rt_` and
+//! uses the C calling convention.
+
+mod panic;
+mod profile;
+mod rc;
+
+pub use panic::{
+    cherry_rt_abort, cherry_rt_panic, cherry_rt_panic_bounds_check, cherry_rt_set_stack_trace_hook,
+    format_panic, Location, StackTraceHook,
+};
+pub use profile::{
+    cherry_rt_profile_enter, cherry_rt_profile_exit, cherry_rt_profile_flush, chrome_trace, events,
+    Event, Phase,
+};
+pub use rc::{
+    cherry_rt_rc_alloc, cherry_rt_rc_count, cherry_rt_rc_release, cherry_rt_rc_retain, DropFn,
+};
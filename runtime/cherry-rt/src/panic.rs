@@ -0,0 +1,144 @@
+//! Panics and aborts.
+//!
+//! When generated code panics, such as on a failed assertion or an out of
+//! bounds index, it calls one of the panic functions in this module with a
+//! message and the location of the panic in the Cherry source.  The panic is
+//! printed to stderr, the stack trace hook is called (if there is one), and
+//! the process is aborted.
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::slice;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// A function which prints a stack trace, called after a panic message has
+/// been printed and before the process is aborted.
+pub type StackTraceHook = extern "C" fn();
+
+/// The current stack trace hook, or null if there is none.
+static STACK_TRACE_HOOK: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+
+/// The location of a panic in the Cherry source code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location<'a> {
+    /// The path to the file the panic happened in.
+    pub file: Cow<'a, str>,
+
+    /// The line the panic happened on, starting at `1`.
+    pub line: u32,
+
+    /// The column the panic happened on, starting at `1`.
+    pub column: u32,
+}
+
+/// Formats a panic message, as it is printed to stderr.
+pub fn format_panic(message: &str, location: &Location) -> String {
+    format!(
+        "panicked at '{}', {}:{}:{}",
+        message, location.file, location.line, location.column
+    )
+}
+
+/// Converts a string passed from generated code into a Rust string.  Invalid
+/// UTF-8 is replaced, since a panic should never fail because of its message.
+///
+/// # Safety
+/// `ptr` must point to `len` readable bytes, or be null.
+unsafe fn str_from_raw<'a>(ptr: *const u8, len: usize) -> Cow<'a, str> {
+    if ptr.is_null() {
+        return Cow::Borrowed("");
+    }
+
+    String::from_utf8_lossy(slice::from_raw_parts(ptr, len))
+}
+
+/// Prints a panic, runs the stack trace hook and aborts.
+fn panic_with(message: &str, location: &Location) -> ! {
+    let mut stderr = std::io::stderr().lock();
+    let _ = writeln!(stderr, "{}", format_panic(message, location));
+    let _ = stderr.flush();
+    drop(stderr);
+
+    let hook = STACK_TRACE_HOOK.load(Ordering::SeqCst);
+    if !hook.is_null() {
+        // SAFETY: the only non-null values ever stored are `StackTraceHook`s.
+        let hook: StackTraceHook = unsafe { std::mem::transmute(hook) };
+        hook();
+    } else if matches!(std::env::var_os("CHERRY_BACKTRACE"), Some(value) if value != "0") {
+        eprintln!("{}", std::backtrace::Backtrace::force_capture());
+    } else {
+        eprintln!("note: run with `CHERRY_BACKTRACE=1` to display a backtrace");
+    }
+
+    cherry_rt_abort()
+}
+
+/// Panics with the provided message.  Called by generated code for explicit
+/// panics and failed assertions.
+///
+/// # Safety
+/// `message` and `file` must point to `message_len` and `file_len` readable
+/// bytes respectively, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_panic(
+    message: *const u8,
+    message_len: usize,
+    file: *const u8,
+    file_len: usize,
+    line: u32,
+    column: u32,
+) -> ! {
+    let message = str_from_raw(message, message_len);
+    let location = Location {
+        file: str_from_raw(file, file_len),
+        line,
+        column,
+    };
+
+    panic_with(&message, &location)
+}
+
+/// Panics because `index` was out of bounds for a value of length `len`.
+///
+/// # Safety
+/// `file` must point to `file_len` readable bytes, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_panic_bounds_check(
+    index: usize,
+    len: usize,
+    file: *const u8,
+    file_len: usize,
+    line: u32,
+    column: u32,
+) -> ! {
+    let message = format!(
+        "index out of bounds: the length is {} but the index is {}",
+        len, index
+    );
+    let location = Location {
+        file: str_from_raw(file, file_len),
+        line,
+        column,
+    };
+
+    panic_with(&message, &location)
+}
+
+/// Aborts the process immediately, without printing anything.
+#[no_mangle]
+pub extern "C" fn cherry_rt_abort() -> ! {
+    std::process::abort()
+}
+
+/// Sets the function called to print a stack trace after a panic.  Passing
+/// `None` restores the default, which prints a backtrace only if the
+/// `CHERRY_BACKTRACE` environment variable is set.
+#[no_mangle]
+pub extern "C" fn cherry_rt_set_stack_trace_hook(hook: Option<StackTraceHook>) {
+    let hook = match hook {
+        Some(hook) => hook as *mut (),
+        None => std::ptr::null_mut(),
+    };
+
+    STACK_TRACE_HOOK.store(hook, Ordering::SeqCst);
+}
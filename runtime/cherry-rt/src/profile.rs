@@ -0,0 +1,168 @@
+//! Profiling hooks.
+//!
+//! Programs built with `--instrument=profile` call [`cherry_rt_profile_enter`]
+//! at the start of every function and [`cherry_rt_profile_exit`] before every
+//! return.  The events are buffered in memory and written as a Chrome trace
+//! (which can be opened in `chrome://tracing` or Perfetto) when
+//! [`cherry_rt_profile_flush`] is called at exit.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::slice;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Whether an event is the start or the end of a function call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// The function was entered.
+    Enter,
+
+    /// The function returned.
+    Exit,
+}
+
+/// A single profiling event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// The name of the function.
+    pub name: String,
+
+    /// Whether the function was entered or exited.
+    pub phase: Phase,
+
+    /// The time of the event, in microseconds since the first event.
+    pub timestamp: u128,
+
+    /// An identifier for the thread the event happened on.
+    pub thread: u64,
+}
+
+/// The events recorded so far, and the time of the first event.
+static EVENTS: Mutex<(Option<Instant>, Vec<Event>)> = Mutex::new((None, Vec::new()));
+
+/// Returns an identifier for the current thread, which is stable for the
+/// lifetime of the thread.
+fn thread_id() -> u64 {
+    thread_local! {
+        static ID: u64 = {
+            use std::sync::atomic::{AtomicU64, Ordering};
+            static NEXT: AtomicU64 = AtomicU64::new(1);
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        };
+    }
+
+    ID.with(|id| *id)
+}
+
+/// Records an event.
+fn record(name: Cow<str>, phase: Phase) {
+    let now = Instant::now();
+    let mut events = match EVENTS.lock() {
+        Ok(events) => events,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let start = *events.0.get_or_insert(now);
+    events.1.push(Event {
+        name: name.into_owned(),
+        phase,
+        timestamp: now.duration_since(start).as_micros(),
+        thread: thread_id(),
+    });
+}
+
+/// Renders a list of events in the Chrome trace event format.
+pub fn chrome_trace(events: &[Event]) -> String {
+    let mut trace = String::from("[");
+
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            trace.push(',');
+        }
+
+        let mut name = String::new();
+        for char in event.name.chars() {
+            match char {
+                '"' => name.push_str("\\\""),
+                '\\' => name.push_str("\\\\"),
+                char if char.is_control() => {
+                    let _ = write!(name, "\\u{:04x}", char as u32);
+                }
+                char => name.push(char),
+            }
+        }
+
+        let _ = write!(
+            trace,
+            "\n{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{},\"pid\":{},\"tid\":{}}}",
+            name,
+            match event.phase {
+                Phase::Enter => "B",
+                Phase::Exit => "E",
+            },
+            event.timestamp,
+            std::process::id(),
+            event.thread
+        );
+    }
+
+    trace.push_str("\n]\n");
+    trace
+}
+
+/// Returns all events recorded so far, without clearing them.
+pub fn events() -> Vec<Event> {
+    match EVENTS.lock() {
+        Ok(events) => events.1.clone(),
+        Err(poisoned) => poisoned.into_inner().1.clone(),
+    }
+}
+
+/// Records that the function `name` was entered.
+///
+/// # Safety
+/// `name` must point to `name_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_profile_enter(name: *const u8, name_len: usize) {
+    record(
+        String::from_utf8_lossy(slice::from_raw_parts(name, name_len)),
+        Phase::Enter,
+    );
+}
+
+/// Records that the function `name` returned.
+///
+/// # Safety
+/// `name` must point to `name_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_profile_exit(name: *const u8, name_len: usize) {
+    record(
+        String::from_utf8_lossy(slice::from_raw_parts(name, name_len)),
+        Phase::Exit,
+    );
+}
+
+/// Writes all recorded events to the trace file, and clears them.  The trace
+/// is written to the path in `CHERRY_PROFILE_FILE`, or to
+/// `cherry-<pid>.trace.json` in the working directory.
+#[no_mangle]
+pub extern "C" fn cherry_rt_profile_flush() {
+    let events = match EVENTS.lock() {
+        Ok(mut events) => std::mem::take(&mut events.1),
+        Err(poisoned) => std::mem::take(&mut poisoned.into_inner().1),
+    };
+
+    let path = match std::env::var_os("CHERRY_PROFILE_FILE") {
+        Some(path) => path.into(),
+        None => std::path::PathBuf::from(format!("cherry-{}.trace.json", std::process::id())),
+    };
+
+    if let Err(err) = std::fs::write(&path, chrome_trace(&events)) {
+        eprintln!(
+            "warning: unable to write profile to `{}`: {}",
+            path.display(),
+            err
+        );
+    }
+}
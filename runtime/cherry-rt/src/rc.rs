@@ -0,0 +1,148 @@
+//! Reference counted allocations.
+//!
+//! Memory managed pointers (`Ptr<T>`) are reference counted.  The compiler
+//! inserts calls to [`cherry_rt_rc_retain`] when a pointer is copied and to
+//! [`cherry_rt_rc_release`] when a copy goes out of scope, so the value is
+//! dropped and deallocated as soon as the last pointer to it is gone.
+//!
+//! Every allocation is prefixed with a [`Header`], placed directly before the
+//! value, so generated code only ever deals with pointers to the value itself.
+
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::mem::size_of;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use crate::panic::cherry_rt_abort;
+
+/// A function which drops the value behind a pointer, without deallocating it.
+pub type DropFn = extern "C" fn(*mut u8);
+
+/// The header placed before every reference counted value.
+#[repr(C)]
+struct Header {
+    /// The number of pointers to this value.
+    count: AtomicUsize,
+
+    /// The layout of the whole allocation, including this header.
+    layout: Layout,
+
+    /// The function to drop the value with, if it needs dropping.
+    drop: Option<DropFn>,
+}
+
+/// Returns the offset of the value in an allocation with the provided
+/// alignment, which is the size of the header rounded up to the alignment.
+fn value_offset(align: usize) -> Option<usize> {
+    Some(size_of::<Header>().checked_add(align - 1)? & !(align - 1))
+}
+
+/// Returns the layout of an allocation for a value of the provided size and
+/// alignment, and the offset of the value in it.
+fn layout_for(size: usize, align: usize) -> Option<(Layout, usize)> {
+    let align = align.max(std::mem::align_of::<Header>());
+    let offset = value_offset(align)?;
+    let layout = Layout::from_size_align(offset.checked_add(size)?, align).ok()?;
+
+    Some((layout, offset))
+}
+
+/// Returns the header of a value allocated by [`cherry_rt_rc_alloc`].
+///
+/// # Safety
+/// `ptr` must have been returned by [`cherry_rt_rc_alloc`] and not yet freed.
+unsafe fn header<'a>(ptr: *mut u8) -> &'a Header {
+    &*(ptr.sub(size_of::<Header>()) as *const Header)
+}
+
+/// Allocates a reference counted value with the provided size and alignment,
+/// and a reference count of `1`.  Aborts if the allocation fails.
+///
+/// `drop` is called with the value pointer once the count reaches `0`, before
+/// the memory is deallocated.
+#[no_mangle]
+pub extern "C" fn cherry_rt_rc_alloc(size: usize, align: usize, drop: Option<DropFn>) -> *mut u8 {
+    if !align.is_power_of_two() {
+        cherry_rt_abort();
+    }
+
+    let (layout, offset) = match layout_for(size, align) {
+        Some(layout) => layout,
+        None => cherry_rt_abort(),
+    };
+
+    // SAFETY: the layout is never zero sized, since it includes the header.
+    unsafe {
+        let base = alloc(layout);
+        if base.is_null() {
+            handle_alloc_error(layout);
+        }
+
+        let ptr = base.add(offset);
+        (ptr.sub(size_of::<Header>()) as *mut Header).write(Header {
+            count: AtomicUsize::new(1),
+            layout,
+            drop,
+        });
+
+        ptr
+    }
+}
+
+/// Increments the reference count of a value.  Does nothing if `ptr` is null.
+///
+/// # Safety
+/// `ptr` must be null, or have been returned by [`cherry_rt_rc_alloc`] and not
+/// yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_rc_retain(ptr: *mut u8) {
+    if ptr.is_null() {
+        return;
+    }
+
+    if header(ptr).count.fetch_add(1, Ordering::Relaxed) == usize::MAX {
+        cherry_rt_abort();
+    }
+}
+
+/// Decrements the reference count of a value, dropping and deallocating it if
+/// this was the last pointer to it.  Does nothing if `ptr` is null.
+///
+/// # Safety
+/// `ptr` must be null, or have been returned by [`cherry_rt_rc_alloc`] and not
+/// yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_rc_release(ptr: *mut u8) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let header = header(ptr);
+    if header.count.fetch_sub(1, Ordering::Release) != 1 {
+        return;
+    }
+
+    fence(Ordering::Acquire);
+
+    if let Some(drop) = header.drop {
+        drop(ptr);
+    }
+
+    // the offset was already computed without overflowing when allocating.
+    let layout = header.layout;
+    let offset = value_offset(layout.align()).unwrap_or_default();
+    dealloc(ptr.sub(offset), layout);
+}
+
+/// Returns the reference count of a value, or `0` if `ptr` is null.
+///
+/// # Safety
+/// `ptr` must be null, or have been returned by [`cherry_rt_rc_alloc`] and not
+/// yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn cherry_rt_rc_count(ptr: *mut u8) -> usize {
+    if ptr.is_null() {
+        return 0;
+    }
+
+    header(ptr).count.load(Ordering::Acquire)
+}
@@ -0,0 +1,79 @@
+extern crate cherry_rt;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cherry_rt::{
+    cherry_rt_profile_enter, cherry_rt_profile_exit, cherry_rt_rc_alloc, cherry_rt_rc_count,
+    cherry_rt_rc_release, cherry_rt_rc_retain, chrome_trace, events, format_panic, Event, Location,
+    Phase,
+};
+
+#[test]
+fn panic_message() {
+    let location = Location {
+        file: "src/main.ch".into(),
+        line: 4,
+        column: 13,
+    };
+
+    assert_eq!(
+        format_panic("assertion failed: x == 1", &location),
+        "panicked at 'assertion failed: x == 1', src/main.ch:4:13"
+    );
+}
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn count_drop(ptr: *mut u8) {
+    assert_eq!(unsafe { *(ptr as *mut u64) }, 42);
+    DROPPED.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn rc_drops_once() {
+    let ptr = cherry_rt_rc_alloc(8, 16, Some(count_drop));
+    assert_eq!(ptr as usize % 16, 0);
+
+    unsafe {
+        *(ptr as *mut u64) = 42;
+
+        cherry_rt_rc_retain(ptr);
+        assert_eq!(cherry_rt_rc_count(ptr), 2);
+
+        cherry_rt_rc_release(ptr);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+
+        cherry_rt_rc_release(ptr);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[test]
+fn profile_events() {
+    let name = "main";
+
+    unsafe {
+        cherry_rt_profile_enter(name.as_ptr(), name.len());
+        cherry_rt_profile_exit(name.as_ptr(), name.len());
+    }
+
+    let events = events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "main");
+    assert_eq!(events[0].phase, Phase::Enter);
+    assert_eq!(events[1].phase, Phase::Exit);
+    assert!(events[0].timestamp <= events[1].timestamp);
+}
+
+#[test]
+fn chrome_trace_escapes_names() {
+    let trace = chrome_trace(&[Event {
+        name: "weird\"name".to_string(),
+        phase: Phase::Enter,
+        timestamp: 5,
+        thread: 1,
+    }]);
+
+    assert!(trace.starts_with("[\n{\"name\":\"weird\\\"name\",\"ph\":\"B\",\"ts\":5,"));
+    assert!(trace.ends_with("\"tid\":1}\n]\n"));
+}